@@ -0,0 +1,99 @@
+//! Key-release-to-final-transcript latency against a local mock Deepgram.
+//!
+//!   cargo bench -p fnkey-core --bench release_latency [-- SESSIONS]
+//!
+//! Each session holds the key for a second of replayed audio, releases it and
+//! times until the Final event. The mock answers CloseStream at once, so the
+//! figures are FnKey's own overhead: forwarding the tail, flushing it,
+//! reading the result and handing it to the paste path.
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use common::FakePlatform;
+use fnkey_core::chain::Chain;
+use fnkey_core::deepgram::DeepgramOptions;
+use fnkey_core::session::SessionEvent;
+use fnkey_core::source::{AudioSource, ReplaySource};
+use fnkey_core::{handle_hotkey, AppState, Config};
+use tungstenite::Message;
+
+const FINAL: &str = r#"{"type":"Results","start":0.0,"duration":1.0,"is_final":true,
+    "channel":{"alternatives":[{"transcript":"hello world","confidence":0.98}]}}"#;
+
+/// How long the key is held per session
+const HOLD: Duration = Duration::from_secs(1);
+
+/// Serve Listen-API connections until the process exits: take audio until
+/// CloseStream, then send one final result and close.
+fn mock_deepgram() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = format!("ws://{}", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let _ = stream.set_nodelay(true);
+            thread::spawn(move || {
+                let Ok(mut ws) = tungstenite::accept(stream) else { return };
+                while let Ok(message) = ws.read() {
+                    if matches!(message, Message::Text(ref text) if text.contains("CloseStream")) {
+                        let _ = ws.send(Message::Text(FINAL.to_string()));
+                        let _ = ws.close(None);
+                    }
+                }
+            });
+        }
+    });
+    base_url
+}
+
+fn main() {
+    // cargo bench passes its own flags; the first number is the session count
+    let sessions: usize = std::env::args().skip(1).find_map(|arg| arg.parse().ok()).unwrap_or(20).max(1);
+
+    let mut options = DeepgramOptions::default();
+    options.configure(&[("base_url".to_string(), mock_deepgram())]).unwrap();
+    let config = Config { deepgram_key: Some("bench".to_string()), deepgram: options, ..Config::default() };
+    let chain = Chain::from_config(&config);
+    // Real-time replay, 20ms at a time like a microphone
+    let source: Arc<dyn AudioSource> = Arc::new(ReplaySource::new(vec![0.1; 48000 * 2], 48000, 1.0));
+    let state = AppState::with_parts(Arc::new(FakePlatform::default()), config, Some(source), chain);
+    let events = state.subscribe();
+
+    let mut latencies = Vec::with_capacity(sessions);
+    for _ in 0..sessions {
+        handle_hotkey(&state, true);
+        thread::sleep(HOLD);
+        let released = Instant::now();
+        handle_hotkey(&state, false);
+        loop {
+            match events.recv_timeout(Duration::from_secs(10)) {
+                Ok(SessionEvent::Final { .. }) => break,
+                Ok(_) => {}
+                Err(_) => panic!("no transcript within 10s"),
+            }
+        }
+        latencies.push(released.elapsed());
+        while state.is_transcribing() {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    latencies.sort();
+    let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+    let percentile = |p: usize| latencies[(latencies.len() - 1) * p / 100];
+    let mean = latencies.iter().map(|&d| ms(d)).sum::<f64>() / latencies.len() as f64;
+    println!(
+        "release → final over {} sessions: min {:.2}ms  median {:.2}ms  mean {:.2}ms  p95 {:.2}ms  max {:.2}ms",
+        latencies.len(),
+        ms(latencies[0]),
+        ms(percentile(50)),
+        mean,
+        ms(percentile(95)),
+        ms(latencies[latencies.len() - 1]),
+    );
+}
@@ -0,0 +1,449 @@
+//! AssemblyAI Universal Streaming (v3) — a second real-time provider.
+//! PCM goes up as binary frames; the server answers with `Begin`, a `Turn`
+//! per update of the current utterance, and `Termination` after we send
+//! `Terminate`.
+
+use std::collections::BTreeMap;
+use std::net::TcpStream;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use tungstenite::client::IntoClientRequest;
+use tungstenite::protocol::frame::coding::CloseCode;
+use tungstenite::protocol::Message;
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::WebSocket;
+
+use crate::audio::to_pcm16;
+use crate::config::{log_error, read_config_file, Config};
+use crate::readiness;
+use crate::send_queue::{would_block, SendQueue};
+use crate::transcriber::{Segment, StreamSession, TranscribeError, Transcriber, Transcript, Word};
+
+pub const ASSEMBLYAI_URL: &str = "wss://streaming.assemblyai.com/v3/ws";
+
+pub const ASSEMBLYAI_SAMPLE_RATE: u32 = 16000;
+
+/// The server rejects chunks shorter than 50ms, so audio is batched into
+/// frames of at least 100ms (16kHz i16)
+const MIN_CHUNK_BYTES: usize = 3200;
+const SHORTEST_CHUNK_BYTES: usize = 1600;
+
+/// Batch mode sends the clip in chunks of this many bytes (1s, the maximum)
+const CLIP_CHUNK_BYTES: usize = 32000;
+
+const BYTES_PER_MS: usize = ASSEMBLYAI_SAMPLE_RATE as usize * 2 / 1000;
+
+/// Audio the send queue holds at most (10s); more waits in the channel
+const MAX_QUEUED_BYTES: usize = 10_000 * BYTES_PER_MS;
+
+/// `keyterms_prompt` limits: at most 100 terms of up to 50 characters
+const MAX_KEYTERMS: usize = 100;
+const MAX_KEYTERM_CHARS: usize = 50;
+
+/// How long to wait for `Termination` after `Terminate`
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(3);
+
+// ============================================================================
+// Messages
+// ============================================================================
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type")]
+enum ServerMessage {
+    Begin,
+    Turn(Turn),
+    Termination,
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct Turn {
+    #[serde(default)]
+    turn_order: u32,
+    #[serde(default)]
+    transcript: String,
+    #[serde(default)]
+    end_of_turn: bool,
+    #[serde(default)]
+    turn_is_formatted: bool,
+    #[serde(default)]
+    words: Vec<TurnWord>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct TurnWord {
+    text: String,
+    /// Milliseconds from the start of the stream
+    start: u64,
+    end: u64,
+    confidence: f32,
+    #[serde(default)]
+    word_is_final: bool,
+}
+
+impl Turn {
+    fn segment(&self) -> Segment {
+        let start = self.words.first().map_or(0, |w| w.start);
+        let end = self.words.last().map_or(start, |w| w.end);
+        Segment {
+            transcript: self.transcript.trim().to_string(),
+            is_final: self.end_of_turn,
+            start: start as f64 / 1000.0,
+            duration: end.saturating_sub(start) as f64 / 1000.0,
+        }
+    }
+
+    /// Later messages for a turn replace earlier ones, except that the
+    /// unformatted end of turn never replaces its formatted version
+    fn supersedes(&self, other: &Turn) -> bool {
+        self.turn_is_formatted || !other.turn_is_formatted
+    }
+}
+
+/// Turns in order, keeping the best message seen for each
+#[derive(Default)]
+struct Turns(BTreeMap<u32, Turn>);
+
+impl Turns {
+    fn update(&mut self, turn: Turn) {
+        match self.0.get(&turn.turn_order) {
+            Some(existing) if !turn.supersedes(existing) => {}
+            _ => {
+                self.0.insert(turn.turn_order, turn);
+            }
+        }
+    }
+
+    fn into_transcript(self) -> Transcript {
+        let mut text = Vec::new();
+        let mut words = Vec::new();
+        for turn in self.0.into_values() {
+            let transcript = turn.transcript.trim();
+            if transcript.is_empty() {
+                continue;
+            }
+            text.push(transcript.to_string());
+            words.extend(turn.words.into_iter().filter(|w| w.word_is_final || turn.end_of_turn).map(|w| Word {
+                word: w.text,
+                start: w.start as f64 / 1000.0,
+                end: w.end as f64 / 1000.0,
+                confidence: Some(w.confidence),
+            }));
+        }
+        let confidence = (!words.is_empty()).then(|| words.iter().filter_map(|w| w.confidence).sum::<f32>() / words.len() as f32);
+        Transcript { text: text.join(" "), provider: "AssemblyAI".to_string(), words, confidence }
+    }
+}
+
+// ============================================================================
+// Provider
+// ============================================================================
+
+pub struct AssemblyAiTranscriber {
+    key: String,
+    keywords: Vec<String>,
+    url: String,
+    /// `speech_model`; None uses the server default
+    model: Option<String>,
+}
+
+impl AssemblyAiTranscriber {
+    pub fn new(key: impl Into<String>, keywords: Vec<String>) -> Self {
+        AssemblyAiTranscriber { key: key.into(), keywords, url: ASSEMBLYAI_URL.to_string(), model: None }
+    }
+
+    pub fn with_url(mut self, url: impl Into<String>) -> Self {
+        self.url = url.into();
+        self
+    }
+
+    /// `assemblyai` in the providers file:
+    ///   key=FILE   read the key from ~/.config/fnkey/FILE (default: assemblyai_key)
+    ///   model=NAME e.g. universal-streaming-multilingual
+    ///   url=URL    ws:// or wss:// endpoint
+    pub fn from_config(config: &Config, options: &[(String, String)]) -> Result<Self, String> {
+        let mut key = config.assemblyai_key.clone();
+        let mut transcriber = AssemblyAiTranscriber::new("", config.keywords.clone());
+        for (option, value) in options {
+            match option.as_str() {
+                "key" => key = Some(read_config_file(value).ok_or_else(|| format!("key file '{}' is missing or empty", value))?),
+                "model" => transcriber.model = Some(value.clone()),
+                "url" => {
+                    if !value.starts_with("ws://") && !value.starts_with("wss://") {
+                        return Err(format!("url must be ws:// or wss://, got '{}'", value));
+                    }
+                    transcriber.url = value.clone();
+                }
+                other => return Err(format!("unknown option '{}'", other)),
+            }
+        }
+        transcriber.key = key.ok_or("no API key (assemblyai_key)")?;
+        Ok(transcriber)
+    }
+
+    /// Streaming URL with audio format and keyword boosting
+    pub fn request_url(&self) -> String {
+        let mut url = format!(
+            "{}?sample_rate={}&encoding=pcm_s16le&format_turns=true",
+            self.url, ASSEMBLYAI_SAMPLE_RATE
+        );
+        let terms: Vec<&str> = self
+            .keywords
+            .iter()
+            .map(|k| k.as_str())
+            .filter(|k| k.chars().count() <= MAX_KEYTERM_CHARS)
+            .take(MAX_KEYTERMS)
+            .collect();
+        if !terms.is_empty() {
+            let terms = serde_json::to_string(&terms).unwrap_or_default();
+            url.push_str(&format!("&keyterms_prompt={}", urlencoding::encode(&terms)));
+        }
+        if let Some(ref model) = self.model {
+            url.push_str(&format!("&speech_model={}", urlencoding::encode(model)));
+        }
+        url
+    }
+
+    /// Stream in frames of `chunk_bytes` from a background thread
+    fn spawn(&self, segments: Option<mpsc::Sender<Segment>>, chunk_bytes: usize) -> AssemblyAiStream {
+        let (tx, rx) = mpsc::channel();
+        let (result_tx, result_rx) = mpsc::channel();
+        let url = self.request_url();
+        let key = self.key.clone();
+        thread::spawn(move || {
+            // Audio queues up in the channel while we connect
+            let result = run_session(&url, &key, rx, segments, chunk_bytes).map_err(|e| {
+                log_error(&format!("AssemblyAI: {}", e));
+                TranscribeError::Failed(e)
+            });
+            let _ = result_tx.send(result);
+        });
+        AssemblyAiStream { tx, result_rx, sample_rate: ASSEMBLYAI_SAMPLE_RATE, early: None }
+    }
+}
+
+enum AaiCommand {
+    /// 16kHz 16-bit little-endian PCM
+    Audio(Vec<u8>),
+    Stop,
+}
+
+type Socket = WebSocket<MaybeTlsStream<TcpStream>>;
+
+fn tcp(ws: &Socket) -> Option<&TcpStream> {
+    match ws.get_ref() {
+        MaybeTlsStream::Plain(s) => Some(s),
+        MaybeTlsStream::NativeTls(s) => Some(s.get_ref()),
+        _ => None,
+    }
+}
+
+/// Connect, forward audio in frames of `chunk_bytes`, and collect turns
+/// until `Termination` (or the socket closes) after Stop. The socket is
+/// non-blocking; between events the thread sleeps in [`readiness::wait`].
+fn run_session(
+    url: &str,
+    key: &str,
+    rx: mpsc::Receiver<AaiCommand>,
+    segments: Option<mpsc::Sender<Segment>>,
+    chunk_bytes: usize,
+) -> Result<Transcript, String> {
+    let mut request = url.into_client_request().map_err(|e| format!("bad url: {}", e))?;
+    request.headers_mut().insert("Authorization", key.parse().map_err(|_| "API key is not a valid header value".to_string())?);
+    let (mut ws, _response) = tungstenite::connect(request).map_err(|e| format!("connect failed: {}", e))?;
+    let socket = tcp(&ws).ok_or("unsupported stream type")?.try_clone().map_err(|e| e.to_string())?;
+    socket.set_nonblocking(true).map_err(|e| e.to_string())?;
+    let (waker, wakeups) = readiness::channel().map_err(|e| e.to_string())?;
+    let commands = readiness::relay(rx, waker);
+
+    let mut turns = Turns::default();
+    let mut queue = SendQueue::new(chunk_bytes, MAX_QUEUED_BYTES);
+    // Audio handed to the queue, for padding the tail
+    let mut fed = 0;
+    // Set by Stop: Termination must arrive before this
+    let mut deadline: Option<Instant> = None;
+    let mut terminate_sent = false;
+    // Terminate is still in tungstenite's buffer
+    let mut terminate_blocked = false;
+    let mut readable = true;
+    loop {
+        // 1. Take audio while the queue keeps up; the rest waits in the channel
+        let mut held_back = false;
+        while deadline.is_none() {
+            if queue.queued_bytes() >= MAX_QUEUED_BYTES / 2 {
+                held_back = true;
+                break;
+            }
+            match commands.try_recv() {
+                Ok(AaiCommand::Audio(pcm)) => {
+                    fed += pcm.len();
+                    queue.push(&pcm);
+                }
+                Ok(AaiCommand::Stop) => {
+                    // Pad the tail up to the shortest chunk the server takes
+                    let tail = fed % chunk_bytes;
+                    if tail > 0 && tail < SHORTEST_CHUNK_BYTES {
+                        queue.push(&vec![0; SHORTEST_CHUNK_BYTES - tail]);
+                    }
+                    queue.flush_partial();
+                    deadline = Some(Instant::now() + DRAIN_TIMEOUT);
+                }
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    let _ = ws.close(None);
+                    let _ = ws.flush();
+                    return Err("cancelled".to_string());
+                }
+            }
+        }
+
+        // 2. Send what the socket will take; Terminate follows the last of the audio
+        let mut sent = queue.pump(&mut ws);
+        if sent.is_ok() && terminate_blocked {
+            sent = match ws.flush() {
+                Err(ref e) if would_block(e) => Ok(()),
+                result => result.map_err(|e| e.to_string()),
+            };
+            terminate_blocked = sent.is_err();
+        }
+        if sent.is_ok() && deadline.is_some() && !terminate_sent && !queue.wants_write() {
+            terminate_sent = true;
+            sent = match ws.send(Message::Text(r#"{"type":"Terminate"}"#.to_string())) {
+                Err(ref e) if would_block(e) => {
+                    terminate_blocked = true;
+                    Ok(())
+                }
+                result => result.map_err(|e| e.to_string()),
+            };
+        }
+        sent.map_err(|e| format!("send failed: {}", e))?;
+
+        // 3. Read everything that has arrived, including what TLS has buffered
+        while readable {
+            match ws.read() {
+                Ok(Message::Text(text)) => match serde_json::from_str::<ServerMessage>(&text) {
+                    Ok(ServerMessage::Turn(turn)) => {
+                        if let Some(ref tx) = segments {
+                            // The unformatted end of turn is followed by its formatted copy
+                            let formatting = turn.end_of_turn && !turn.turn_is_formatted;
+                            if !formatting && !turn.transcript.trim().is_empty() {
+                                let _ = tx.send(turn.segment());
+                            }
+                        }
+                        turns.update(turn);
+                    }
+                    Ok(ServerMessage::Termination) => return Ok(finished(ws, turns, queue)),
+                    Ok(ServerMessage::Begin | ServerMessage::Other) => {}
+                    Err(_) => {
+                        // Errors come as {"error": "..."} without a type
+                        let v: serde_json::Value = serde_json::from_str(&text).unwrap_or_default();
+                        if let Some(error) = v.get("error").and_then(|e| e.as_str()) {
+                            return Err(format!("server error: {}", error));
+                        }
+                    }
+                },
+                Ok(Message::Close(frame)) => {
+                    // Auth and protocol errors arrive as close codes, possibly
+                    // after we've already sent Terminate
+                    let normal = frame.as_ref().is_none_or(|f| f.code == CloseCode::Normal);
+                    if deadline.is_none() || !normal {
+                        let reason = frame.map_or_else(|| "no close frame".to_string(), |f| format!("code={}, reason='{}'", f.code, f.reason));
+                        return Err(format!("server closed the connection: {}", reason));
+                    }
+                    return Ok(finished(ws, turns, queue));
+                }
+                Ok(_) => {}
+                Err(ref e) if would_block(e) => readable = false,
+                Err(tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed) if deadline.is_some() => {
+                    return Ok(finished(ws, turns, queue));
+                }
+                Err(e) => return Err(format!("WebSocket error: {}", e)),
+            }
+        }
+
+        // 4. Sleep until the socket or the relay has something for us. Audio
+        // held back in the channel needs no wake-up, so fetch it now.
+        if held_back && !queue.wants_write() {
+            continue;
+        }
+        let timeout = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+        if timeout.is_some_and(|t| t.is_zero()) {
+            log_error("AssemblyAI: no Termination after Terminate");
+            return Ok(finished(ws, turns, queue));
+        }
+        let ready = readiness::wait(&socket, &wakeups, queue.wants_write() || terminate_blocked, timeout)
+            .map_err(|e| format!("waiting on socket failed: {}", e))?;
+        readable = ready.readable;
+    }
+}
+
+/// Close the socket (best effort, it's non-blocking) and collect the turns
+fn finished(mut ws: Socket, turns: Turns, queue: SendQueue) -> Transcript {
+    let _ = ws.close(None);
+    let _ = ws.flush();
+    if queue.stats.stalls > 0 {
+        log_error(&format!("AssemblyAI: {}", queue.stats.summary(BYTES_PER_MS)));
+    }
+    turns.into_transcript()
+}
+
+struct AssemblyAiStream {
+    tx: mpsc::Sender<AaiCommand>,
+    result_rx: mpsc::Receiver<Result<Transcript, TranscribeError>>,
+    sample_rate: u32,
+    /// A result that came before Stop: the session failed early
+    early: Option<Result<Transcript, TranscribeError>>,
+}
+
+impl StreamSession for AssemblyAiStream {
+    fn push_audio(&mut self, samples: &[f32]) {
+        let _ = self.tx.send(AaiCommand::Audio(to_pcm16(samples, self.sample_rate, ASSEMBLYAI_SAMPLE_RATE)));
+    }
+
+    fn finish(self: Box<Self>, timeout: Duration) -> Result<Transcript, TranscribeError> {
+        let _ = self.tx.send(AaiCommand::Stop);
+        let result = match self.early {
+            Some(result) => Ok(result),
+            None => self.result_rx.recv_timeout(timeout),
+        };
+        match result {
+            Ok(Ok(transcript)) if transcript.text.is_empty() => Err(TranscribeError::Empty),
+            Ok(result) => result,
+            Err(_) => Err(TranscribeError::Timeout),
+        }
+    }
+
+    fn failed(&mut self) -> bool {
+        if self.early.is_none() {
+            self.early = self.result_rx.try_recv().ok();
+        }
+        matches!(self.early, Some(Err(_)))
+    }
+}
+
+impl Transcriber for AssemblyAiTranscriber {
+    fn name(&self) -> &str {
+        "AssemblyAI"
+    }
+
+    fn start_stream(&self, sample_rate: u32, segments: Option<mpsc::Sender<Segment>>) -> Option<Box<dyn StreamSession>> {
+        let mut stream = self.spawn(segments, MIN_CHUNK_BYTES);
+        stream.sample_rate = sample_rate;
+        Some(Box::new(stream))
+    }
+
+    /// Push the whole clip through a stream; allow the clip's length on top
+    /// of the usual 5s like Deepgram
+    fn transcribe(&self, audio: &[f32], sample_rate: u32) -> Result<Transcript, TranscribeError> {
+        let stream = self.spawn(None, CLIP_CHUNK_BYTES);
+        for chunk in to_pcm16(audio, sample_rate, ASSEMBLYAI_SAMPLE_RATE).chunks(CLIP_CHUNK_BYTES) {
+            let _ = stream.tx.send(AaiCommand::Audio(chunk.to_vec()));
+        }
+        let clip_secs = audio.len() as u64 / sample_rate.max(1) as u64;
+        Box::new(stream).finish(Duration::from_secs(5 + clip_secs))
+    }
+}
@@ -0,0 +1,202 @@
+//! Sample-rate conversion, clean-up, encoding and decoding of audio.
+
+use std::io::Cursor;
+use std::path::Path;
+
+use hound::{WavSpec, WavWriter};
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as DecodeError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+/// Simple linear resampling
+pub fn resample(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+    if from_rate == to_rate || samples.is_empty() {
+        return samples.to_vec();
+    }
+    let ratio = from_rate as f64 / to_rate as f64;
+    let out_len = (samples.len() as f64 / ratio) as usize;
+    let mut out = Vec::with_capacity(out_len);
+    for i in 0..out_len {
+        let src_idx = i as f64 * ratio;
+        let idx = src_idx as usize;
+        let frac = src_idx - idx as f64;
+        let s = if idx + 1 < samples.len() {
+            samples[idx] as f64 * (1.0 - frac) + samples[idx + 1] as f64 * frac
+        } else {
+            samples[idx.min(samples.len() - 1)] as f64
+        };
+        out.push(s as f32);
+    }
+    out
+}
+
+/// Resample to `to_rate` and convert to 16-bit little-endian PCM bytes.
+pub fn to_pcm16(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<u8> {
+    let resampled = resample(samples, from_rate, to_rate);
+    let mut bytes = Vec::with_capacity(resampled.len() * 2);
+    for &sample in &resampled {
+        let s = (sample * 32767.0).clamp(-32768.0, 32767.0) as i16;
+        bytes.extend_from_slice(&s.to_le_bytes());
+    }
+    bytes
+}
+
+/// Raw PCM sample layout read from a pipe (`fnkey stream --format`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PcmFormat {
+    F32Le,
+    S16Le,
+}
+
+impl PcmFormat {
+    /// Accepts the names arecord/parec/ffmpeg use: `f32le`, `float32le`, `s16le`
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().replace('_', "").as_str() {
+            "f32le" | "float32le" => Some(PcmFormat::F32Le),
+            "s16le" => Some(PcmFormat::S16Le),
+            _ => None,
+        }
+    }
+
+    pub fn bytes_per_sample(self) -> usize {
+        match self {
+            PcmFormat::F32Le => 4,
+            PcmFormat::S16Le => 2,
+        }
+    }
+
+    /// Decode whole interleaved frames of `channels` samples to mono f32.
+    /// A trailing partial frame is ignored.
+    pub fn to_mono(self, bytes: &[u8], channels: usize) -> Vec<f32> {
+        let channels = channels.max(1);
+        let frame_bytes = self.bytes_per_sample() * channels;
+        bytes
+            .chunks_exact(frame_bytes)
+            .map(|frame| {
+                let sum: f32 = frame
+                    .chunks_exact(self.bytes_per_sample())
+                    .map(|b| match self {
+                        PcmFormat::F32Le => f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+                        PcmFormat::S16Le => i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0,
+                    })
+                    .sum();
+                sum / channels as f32
+            })
+            .collect()
+    }
+}
+
+// ============================================================================
+// Audio encoding (for Groq fallback only)
+// ============================================================================
+
+/// DC offset removal, 80 Hz high-pass, peak normalization (capped at 4x gain)
+pub fn enhance_audio(samples: &[f32], sample_rate: u32) -> Vec<f32> {
+    if samples.is_empty() {
+        return Vec::new();
+    }
+    let mean: f32 = samples.iter().sum::<f32>() / samples.len() as f32;
+    let dc_removed: Vec<f32> = samples.iter().map(|&s| s - mean).collect();
+    let fc = 80.0_f32;
+    let a = (-2.0 * std::f32::consts::PI * fc / sample_rate as f32).exp();
+    let mut filtered = Vec::with_capacity(dc_removed.len());
+    let mut prev_x = 0.0_f32;
+    let mut prev_y = 0.0_f32;
+    for &x in &dc_removed {
+        let y = a * (prev_y + x - prev_x);
+        filtered.push(y);
+        prev_x = x;
+        prev_y = y;
+    }
+    let peak = filtered.iter().map(|&s| s.abs()).fold(1.0_f32, f32::max);
+    let target = 0.707_f32;
+    let raw_gain = target / peak;
+    let gain = raw_gain.min(4.0);
+    if gain > 1.05 {
+        filtered.iter().map(|&s| (s * gain).clamp(-1.0, 1.0)).collect()
+    } else {
+        filtered.iter().map(|&s| s.clamp(-1.0, 1.0)).collect()
+    }
+}
+
+/// Enhance and encode as a mono 16-bit WAV file in memory.
+pub fn encode_wav(samples: &[f32], sample_rate: u32) -> Result<Vec<u8>, hound::Error> {
+    let enhanced = enhance_audio(samples, sample_rate);
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut cursor = Cursor::new(Vec::new());
+    {
+        let mut writer = WavWriter::new(&mut cursor, spec)?;
+        for &sample in &enhanced {
+            let sample_i16 = (sample * 32767.0) as i16;
+            writer.write_sample(sample_i16)?;
+        }
+        writer.finalize()?;
+    }
+    Ok(cursor.into_inner())
+}
+
+// ============================================================================
+// Audio file decoding (for `fnkey transcribe`)
+// ============================================================================
+
+/// Decode a WAV/FLAC/MP3/OGG file to mono f32 samples at its native rate.
+/// Channels are averaged; corrupt frames are skipped.
+pub fn decode_file(path: &Path) -> Result<(Vec<f32>, u32), String> {
+    let file = std::fs::File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext);
+    }
+    let probed = symphonia::default::get_probe()
+        .format(&hint, stream, &FormatOptions::default(), &MetadataOptions::default())
+        .map_err(|e| format!("{}: unsupported audio format ({})", path.display(), e))?;
+    let mut format = probed.format;
+    let track = format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or_else(|| format!("{}: no audio track", path.display()))?;
+    let track_id = track.id;
+    let mut sample_rate = track.codec_params.sample_rate;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(|e| format!("{}: unsupported codec ({})", path.display(), e))?;
+
+    let mut samples = Vec::new();
+    loop {
+        let packet = match format.next_packet() {
+            Ok(p) => p,
+            Err(DecodeError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(DecodeError::ResetRequired) => break,
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(d) => d,
+            Err(DecodeError::DecodeError(_)) => continue,
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+        let spec = *decoded.spec();
+        sample_rate.get_or_insert(spec.rate);
+        let channels = spec.channels.count().max(1);
+        let mut buf = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        buf.copy_interleaved_ref(decoded);
+        for frame in buf.samples().chunks(channels) {
+            samples.push(frame.iter().sum::<f32>() / channels as f32);
+        }
+    }
+    let sample_rate = sample_rate.ok_or_else(|| format!("{}: unknown sample rate", path.display()))?;
+    Ok((samples, sample_rate))
+}
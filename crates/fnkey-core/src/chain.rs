@@ -0,0 +1,357 @@
+//! Ordered fallback chain of transcription providers. Each link has its own
+//! timeout and its own rules for when the next link gets a turn; without a
+//! `providers` file the chain is Deepgram, AssemblyAI, then Groq, for whichever
+//! keys exist.
+//!
+//! ~/.config/fnkey/providers, one provider per line, tried top to bottom:
+//!   deepgram timeout=5 language=de endpointing=800
+//!   assemblyai timeout=5
+//!   groq timeout=20 fallthrough=error,empty,low-confidence min_confidence=0.6
+//!   openai name=Local base_url=http://localhost:8000/v1 model=Systran/faster-whisper-small
+//!   whisper model=ggml-base.en.bin   (offline, with the `whisper` feature)
+//!   vosk model=vosk-model-small-en-us-0.15   (offline streaming, `vosk` feature)
+//!   wyoming uri=tcp://localhost:10300 language=en
+
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+use crate::assemblyai::AssemblyAiTranscriber;
+use crate::config::{log_error, Config};
+use crate::deepgram::DeepgramTranscriber;
+use crate::groq::groq_transcriber;
+use crate::openai::openai_from_config;
+use crate::wyoming::WyomingTranscriber;
+use crate::transcriber::{Segment, StreamSession, TranscribeError, Transcriber, Transcript};
+
+/// How long a stream gets for its final transcript unless the link says otherwise
+pub const DEFAULT_STREAM_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// When a link hands over to the next one
+#[derive(Clone, Debug, PartialEq)]
+pub struct FallThrough {
+    /// Provider error or timeout
+    pub on_error: bool,
+    /// Finished without any text
+    pub on_empty: bool,
+    /// Text whose confidence is below this; kept as a last resort
+    pub min_confidence: Option<f32>,
+}
+
+impl Default for FallThrough {
+    fn default() -> Self {
+        FallThrough { on_error: true, on_empty: true, min_confidence: None }
+    }
+}
+
+/// One line of the `providers` file
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProviderSpec {
+    pub name: String,
+    pub timeout: Option<Duration>,
+    pub fall_through: FallThrough,
+    /// Provider-specific `key=value` options, checked when the chain is built
+    pub options: Vec<(String, String)>,
+}
+
+/// Parse the `providers` file; blank lines and `#` comments are skipped.
+pub fn parse_providers(content: &str) -> Result<Vec<ProviderSpec>, String> {
+    let mut specs = Vec::new();
+    for (n, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        parse_provider_line(line).map(|spec| specs.push(spec)).map_err(|e| format!("providers line {}: {}", n + 1, e))?;
+    }
+    Ok(specs)
+}
+
+fn parse_provider_line(line: &str) -> Result<ProviderSpec, String> {
+    let mut fields = line.split_whitespace();
+    let name = fields.next().unwrap_or_default().to_lowercase();
+    let mut spec = ProviderSpec { name, timeout: None, fall_through: FallThrough::default(), options: Vec::new() };
+    let mut conditions: Option<Vec<&str>> = None;
+    for field in fields {
+        let (key, value) = field.split_once('=').ok_or_else(|| format!("expected key=value, got '{}'", field))?;
+        match key {
+            "timeout" => {
+                let secs: f32 = value.parse().map_err(|_| format!("bad timeout '{}'", value))?;
+                // try_from rejects what doesn't fit a Duration (e.g. 1e20) instead of panicking
+                let timeout = Duration::try_from_secs_f32(secs).ok().filter(|t| !t.is_zero());
+                spec.timeout = Some(timeout.ok_or_else(|| format!("bad timeout '{}'", value))?);
+            }
+            "fallthrough" => conditions = Some(value.split(',').filter(|c| !c.is_empty()).collect()),
+            "min_confidence" => {
+                let min: f32 = value.parse().map_err(|_| format!("bad min_confidence '{}'", value))?;
+                if !(0.0..=1.0).contains(&min) {
+                    return Err(format!("min_confidence must be between 0 and 1, got '{}'", value));
+                }
+                spec.fall_through.min_confidence = Some(min);
+            }
+            other => spec.options.push((other.to_string(), value.to_string())),
+        }
+    }
+    // Without an explicit list, min_confidence alone adds low-confidence to the defaults
+    let Some(conditions) = conditions else {
+        return Ok(spec);
+    };
+    let rules = &mut spec.fall_through;
+    rules.on_error = false;
+    rules.on_empty = false;
+    let mut low_confidence = false;
+    for condition in conditions {
+        match condition {
+            "error" => rules.on_error = true,
+            "empty" => rules.on_empty = true,
+            "low-confidence" => low_confidence = true,
+            other => return Err(format!("unknown fallthrough condition '{}'", other)),
+        }
+    }
+    match (low_confidence, rules.min_confidence) {
+        (true, None) => Err("low-confidence needs min_confidence=".to_string()),
+        (false, Some(_)) => Err("min_confidence needs low-confidence in fallthrough=".to_string()),
+        _ => Ok(spec),
+    }
+}
+
+/// Instantiate the provider a `providers` line names.
+pub fn build_provider(spec: &ProviderSpec, config: &Config) -> Result<Arc<dyn Transcriber>, String> {
+    match spec.name.as_str() {
+        "deepgram" => {
+            let mut options = config.deepgram.clone();
+            options.configure(&spec.options).map_err(|e| format!("deepgram: {}", e))?;
+            let key = config.deepgram_key.clone().or_else(|| options.is_self_hosted().then(String::new));
+            let key = key.ok_or("deepgram: no API key (deepgram_key)")?;
+            Ok(Arc::new(DeepgramTranscriber::new(key, config.keywords.clone()).with_options(options).prewarmed()))
+        }
+        "assemblyai" => Ok(Arc::new(AssemblyAiTranscriber::from_config(config, &spec.options).map_err(|e| format!("assemblyai: {}", e))?)),
+        "groq" => {
+            let key = config.groq_key.as_deref().ok_or("groq: no API key (api_key)")?;
+            let groq = groq_transcriber(key, config.keywords.clone()).configure(&spec.options);
+            Ok(Arc::new(groq.map_err(|e| format!("groq: {}", e))?))
+        }
+        "openai" => Ok(Arc::new(openai_from_config(config, &spec.options).map_err(|e| format!("openai: {}", e))?)),
+        "wyoming" => Ok(Arc::new(WyomingTranscriber::from_config(config, &spec.options).map_err(|e| format!("wyoming: {}", e))?)),
+        #[cfg(feature = "whisper")]
+        "whisper" => {
+            let whisper = crate::whisper::WhisperTranscriber::from_config(config, &spec.options);
+            Ok(Arc::new(whisper.map_err(|e| format!("whisper: {}", e))?))
+        }
+        #[cfg(not(feature = "whisper"))]
+        "whisper" => Err("whisper: built without the `whisper` feature".to_string()),
+        #[cfg(feature = "vosk")]
+        "vosk" => Ok(Arc::new(crate::vosk::VoskTranscriber::from_config(config, &spec.options).map_err(|e| format!("vosk: {}", e))?)),
+        #[cfg(not(feature = "vosk"))]
+        "vosk" => Err("vosk: built without the `vosk` feature".to_string()),
+        other => Err(format!("unknown provider '{}'", other)),
+    }
+}
+
+// ============================================================================
+// Chain
+// ============================================================================
+
+pub struct Link {
+    pub transcriber: Arc<dyn Transcriber>,
+    /// Streams: wait for the final transcript. Batch: abandon the request.
+    pub timeout: Option<Duration>,
+    pub fall_through: FallThrough,
+}
+
+impl Link {
+    pub fn new(transcriber: Arc<dyn Transcriber>) -> Self {
+        Link { transcriber, timeout: None, fall_through: FallThrough::default() }
+    }
+
+    fn name(&self) -> &str {
+        self.transcriber.name()
+    }
+
+    fn transcribe(&self, audio: &[f32], sample_rate: u32) -> Result<Transcript, TranscribeError> {
+        let Some(timeout) = self.timeout else {
+            return self.transcriber.transcribe(audio, sample_rate);
+        };
+        // Run on a thread we can walk away from; a late result is simply dropped
+        let (tx, rx) = mpsc::channel();
+        let transcriber = Arc::clone(&self.transcriber);
+        let audio = audio.to_vec();
+        thread::spawn(move || {
+            let _ = tx.send(transcriber.transcribe(&audio, sample_rate));
+        });
+        rx.recv_timeout(timeout).unwrap_or_else(|e| match e {
+            mpsc::RecvTimeoutError::Timeout => Err(TranscribeError::Timeout),
+            mpsc::RecvTimeoutError::Disconnected => Err(TranscribeError::Failed("provider thread died".to_string())),
+        })
+    }
+}
+
+/// A provider that was tried and didn't produce text
+#[derive(Clone, Debug)]
+pub struct ChainFailure {
+    pub provider: String,
+    pub error: TranscribeError,
+}
+
+/// How a session's live stream ended
+pub struct Streamed {
+    /// The link that had the audio when the key was released
+    pub index: usize,
+    pub result: Result<Transcript, TranscribeError>,
+    /// Links before it whose streams failed while the key was held
+    pub failures: Vec<ChainFailure>,
+}
+
+#[derive(Default)]
+pub struct Chain {
+    links: Vec<Link>,
+}
+
+impl From<Vec<Arc<dyn Transcriber>>> for Chain {
+    /// Default timeouts and fall-through rules for every provider
+    fn from(providers: Vec<Arc<dyn Transcriber>>) -> Self {
+        Chain::new(providers.into_iter().map(Link::new).collect())
+    }
+}
+
+impl Chain {
+    pub fn new(links: Vec<Link>) -> Self {
+        Chain { links }
+    }
+
+    /// The chain from the `providers` file, or Deepgram, AssemblyAI, Groq for
+    /// whichever keys are set. Unusable entries are logged and left out; a
+    /// `providers` file that doesn't parse gives no chain at all.
+    pub fn from_config(config: &Config) -> Self {
+        if config.providers_error.is_some() {
+            return Chain::default();
+        }
+        if config.providers.is_empty() {
+            let mut providers: Vec<Arc<dyn Transcriber>> = Vec::new();
+            for name in ["deepgram", "assemblyai", "groq"] {
+                let spec = ProviderSpec { name: name.to_string(), timeout: None, fall_through: FallThrough::default(), options: Vec::new() };
+                if let Ok(provider) = build_provider(&spec, config) {
+                    providers.push(provider);
+                }
+            }
+            return providers.into();
+        }
+        let mut links = Vec::new();
+        for spec in &config.providers {
+            match build_provider(spec, config) {
+                Ok(transcriber) => links.push(Link { transcriber, timeout: spec.timeout, fall_through: spec.fall_through.clone() }),
+                Err(e) => log_error(&format!("Skipping provider: {}", e)),
+            }
+        }
+        Chain::new(links)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+
+    pub fn links(&self) -> &[Link] {
+        &self.links
+    }
+
+    /// Open a stream with the first link, if it supports one. A later link
+    /// only gets a turn once the ones before it fall through, so it isn't
+    /// streamed to ahead of them. Returns its index, the session and how
+    /// long to wait for its result.
+    pub fn start_stream(&self, sample_rate: u32, segments: mpsc::Sender<Segment>) -> Option<(usize, Box<dyn StreamSession>, Duration)> {
+        self.stream_at(0, sample_rate, segments)
+    }
+
+    /// The stream on link `failed` failed while the key was held: open one on
+    /// the next link, if the failed link falls through on errors and the next
+    /// one streams.
+    pub fn hand_over(&self, failed: usize, sample_rate: u32, segments: mpsc::Sender<Segment>) -> Option<(usize, Box<dyn StreamSession>, Duration)> {
+        if !self.links.get(failed)?.fall_through.on_error {
+            return None;
+        }
+        self.stream_at(failed + 1, sample_rate, segments)
+    }
+
+    fn stream_at(&self, index: usize, sample_rate: u32, segments: mpsc::Sender<Segment>) -> Option<(usize, Box<dyn StreamSession>, Duration)> {
+        let link = self.links.get(index)?;
+        let session = link.transcriber.start_stream(sample_rate, Some(segments))?;
+        Some((index, session, link.timeout.unwrap_or(DEFAULT_STREAM_TIMEOUT)))
+    }
+
+    /// Walk the links in order until one produces text it doesn't fall
+    /// through on. The link at `streamed.index` contributes that stream's
+    /// result and the links before it count as its `failures`; the others
+    /// transcribe `audio`. `on_fallback(from, to)` is called each time the
+    /// chain moves on.
+    ///
+    /// Ok with empty text means a link accepted "nothing was said". Err lists
+    /// the links that failed, empty if none could run.
+    pub fn run(
+        &self,
+        streamed: Option<Streamed>,
+        audio: &[f32],
+        sample_rate: u32,
+        mut on_fallback: impl FnMut(&str, &str),
+    ) -> Result<Transcript, Vec<ChainFailure>> {
+        let (mut streamed, mut failures) = match streamed {
+            Some(s) => (Some((s.index, s.result)), s.failures),
+            None => (None, Vec::new()),
+        };
+        // Best text that fell through on confidence, used if nothing beats it
+        let mut unsure: Option<Transcript> = None;
+        let mut previous: Option<&str> = None;
+        for (i, link) in self.links.iter().enumerate() {
+            let result = match streamed.take() {
+                Some((index, result)) if index == i => result,
+                // Already failed live and handed the audio on
+                Some((index, result)) if i < index => {
+                    streamed = Some((index, result));
+                    continue;
+                }
+                other => {
+                    streamed = other;
+                    if audio.is_empty() {
+                        continue;
+                    }
+                    if let Some(prev) = previous {
+                        on_fallback(prev, link.name());
+                    }
+                    link.transcribe(audio, sample_rate)
+                }
+            };
+            let rules = &link.fall_through;
+            let result = match result {
+                Err(TranscribeError::Empty) => Ok(Transcript::new(link.name(), "")),
+                other => other,
+            };
+            match result {
+                Ok(transcript) if transcript.text.is_empty() => {
+                    if !rules.on_empty {
+                        return Ok(transcript);
+                    }
+                    log_error(&format!("{}: empty transcript", link.name()));
+                    failures.push(ChainFailure { provider: link.name().to_string(), error: TranscribeError::Empty });
+                }
+                Ok(transcript) => match (rules.min_confidence, transcript.confidence) {
+                    (Some(min), Some(confidence)) if confidence < min => {
+                        log_error(&format!("{}: confidence {:.2} below {:.2}", link.name(), confidence, min));
+                        if unsure.as_ref().is_none_or(|u| u.confidence < Some(confidence)) {
+                            unsure = Some(transcript);
+                        }
+                    }
+                    _ => return Ok(transcript),
+                },
+                Err(TranscribeError::Unsupported) => continue,
+                Err(error) => {
+                    log_error(&format!("{} failed: {}", link.name(), error));
+                    failures.push(ChainFailure { provider: link.name().to_string(), error });
+                    if !rules.on_error {
+                        break;
+                    }
+                }
+            }
+            previous = Some(link.name());
+        }
+        unsure.ok_or(failures)
+    }
+}
@@ -0,0 +1,30 @@
+//! Transcribe an already-recorded clip through the same provider chain as a
+//! live session.
+
+use crate::chain::Chain;
+use crate::config::Config;
+
+/// Run `samples` through the configured chain and return the transcript.
+pub fn transcribe_clip(config: &Config, samples: &[f32], sample_rate: u32) -> Result<String, String> {
+    if let Some(ref e) = config.providers_error {
+        return Err(e.clone());
+    }
+    let chain = Chain::from_config(config);
+    if chain.is_empty() {
+        return Err("no API key configured".to_string());
+    }
+    transcribe_with(&chain, samples, sample_rate)
+}
+
+/// Walk `chain` in batch mode until a provider returns text.
+pub fn transcribe_with(chain: &Chain, samples: &[f32], sample_rate: u32) -> Result<String, String> {
+    if samples.is_empty() {
+        return Err("no audio".to_string());
+    }
+    match chain.run(None, samples, sample_rate, |_, _| {}) {
+        Ok(transcript) if !transcript.text.is_empty() => Ok(transcript.text),
+        Ok(transcript) => Err(format!("{}: empty transcript", transcript.provider)),
+        Err(failures) if failures.is_empty() => Err("no provider can transcribe a clip".to_string()),
+        Err(failures) => Err(failures.iter().map(|f| format!("{}: {}", f.provider, f.error)).collect::<Vec<_>>().join("; ")),
+    }
+}
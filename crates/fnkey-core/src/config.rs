@@ -0,0 +1,122 @@
+//! Config files under ~/.config/fnkey/ and the shared error log.
+
+use std::env;
+use std::io::Write as IoWrite;
+use std::path::PathBuf;
+
+use crate::chain::{parse_providers, ProviderSpec};
+use crate::deepgram::DeepgramOptions;
+
+/// Settings loaded once at startup.
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    pub deepgram_key: Option<String>,
+    pub groq_key: Option<String>,
+    pub assemblyai_key: Option<String>,
+    /// Custom vocabulary sent as Deepgram `keyterm` / Whisper `prompt`
+    pub keywords: Vec<String>,
+    /// Deepgram query parameters; `deepgram` lines in providers can override
+    pub deepgram: DeepgramOptions,
+    /// Press Return after pasting
+    pub auto_return: bool,
+    /// Type each finalized streaming segment while the key is held
+    pub progressive: bool,
+    /// Ordered provider chain; empty means the default for the keys above
+    pub providers: Vec<ProviderSpec>,
+    /// Why the `providers` file didn't parse. The chain is then empty, not
+    /// the default, so audio never goes to a provider the user left out.
+    pub providers_error: Option<String>,
+}
+
+impl Config {
+    /// Read keys and preferences from ~/.config/fnkey, falling back to
+    /// DEEPGRAM_API_KEY / GROQ_API_KEY / ASSEMBLYAI_API_KEY for the keys.
+    pub fn load() -> Self {
+        let deepgram_key = read_config_file("deepgram_key")
+            .or_else(|| env::var("DEEPGRAM_API_KEY").ok());
+        let groq_key = read_config_file("api_key")
+            .or_else(|| env::var("GROQ_API_KEY").ok());
+        let assemblyai_key = read_config_file("assemblyai_key")
+            .or_else(|| env::var("ASSEMBLYAI_API_KEY").ok());
+        let auto_return = read_config_file("auto_return").is_some_and(|v| v == "1");
+        let progressive = read_config_file("progressive").is_some_and(|v| v == "1");
+        let keywords = read_config_file("keywords")
+            .map(|content| parse_keywords(&content))
+            .unwrap_or_default();
+        let deepgram = read_config_file("deepgram")
+            .map(|content| {
+                DeepgramOptions::parse(&content).unwrap_or_else(|e| {
+                    log_error(&format!("Ignoring deepgram file: {}", e));
+                    DeepgramOptions::default()
+                })
+            })
+            .unwrap_or_default();
+        let (providers, providers_error) = match read_config_file("providers").map(|content| parse_providers(&content)) {
+            Some(Ok(providers)) => (providers, None),
+            Some(Err(e)) => {
+                log_error(&format!("Invalid providers file: {}", e));
+                (Vec::new(), Some(e))
+            }
+            None => (Vec::new(), None),
+        };
+        Config { deepgram_key, groq_key, assemblyai_key, keywords, deepgram, auto_return, progressive, providers, providers_error }
+    }
+
+    /// Deepgram key, or an empty one for a self-hosted server that needs none
+    pub fn deepgram_auth(&self) -> Option<String> {
+        self.deepgram_key.clone().or_else(|| self.deepgram.is_self_hosted().then(String::new))
+    }
+
+    pub fn has_backend(&self) -> bool {
+        self.deepgram_auth().is_some() || self.groq_key.is_some() || self.assemblyai_key.is_some() || !self.providers.is_empty()
+    }
+}
+
+/// One term per line; blank lines and `#` comments are skipped.
+pub fn parse_keywords(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(|l| l.trim().to_string())
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .collect()
+}
+
+/// Path of a file in ~/.config/fnkey, or None if HOME is unset.
+pub fn config_path(name: &str) -> Option<PathBuf> {
+    let home = env::var_os("HOME")?;
+    Some(PathBuf::from(home).join(".config").join("fnkey").join(name))
+}
+
+/// ~/.config/fnkey/models, where offline providers look for their models
+pub fn models_dir() -> Option<PathBuf> {
+    config_path("models")
+}
+
+/// Path of the keywords file, created with an example if it doesn't exist
+/// so the "Edit Keywords…" menu entries have something to open.
+pub fn keywords_file() -> Option<PathBuf> {
+    let path = config_path("keywords")?;
+    if !path.exists() {
+        let _ = std::fs::create_dir_all(path.parent()?);
+        let _ = std::fs::write(&path, "# Custom keywords (one per line)\n# Improves transcription accuracy for these terms\nAnthropic\nClaude\n");
+    }
+    Some(path)
+}
+
+/// Trimmed contents of a config file; None if missing or empty.
+pub fn read_config_file(name: &str) -> Option<String> {
+    let path = config_path(name)?;
+    let key = std::fs::read_to_string(&path).ok()?;
+    let key = key.trim();
+    if key.is_empty() { None } else { Some(key.to_string()) }
+}
+
+pub fn log_error(msg: &str) {
+    if let Some(path) = config_path("error.log") {
+        if let Ok(mut f) = std::fs::OpenOptions::new().create(true).append(true).open(&path) {
+            let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
+            let _ = writeln!(f, "[{}] {}", now, msg);
+        }
+    }
+    eprintln!("[fnkey] {}", msg);
+}
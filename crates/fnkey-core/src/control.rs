@@ -0,0 +1,136 @@
+//! Unix-socket control interface so scripts, WM keybindings and editor
+//! plugins can drive sessions without the hotkey.
+//!
+//! One JSON object per line in each direction:
+//!   {"cmd":"start"}            → {"ok":true}
+//!   {"cmd":"stop"}             → {"ok":true}
+//!   {"cmd":"cancel"}           → {"ok":true}
+//!   {"cmd":"status"}           → {"ok":true,"recording":false,"transcribing":false}
+//!   {"cmd":"last-transcript"}  → {"ok":true,"transcript":"…"|null}
+//!   {"cmd":"subscribe"}        → {"ok":true}, then one line per SessionEvent
+//!                                 ({"event":"interim","text":"…"}, …) until
+//!                                 the client disconnects
+//! Errors come back as {"ok":false,"error":"…"}.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::config::{config_path, log_error};
+use crate::session::{cancel_recording, start_recording, stop_recording, AppState};
+
+#[derive(Deserialize)]
+#[serde(tag = "cmd", rename_all = "kebab-case")]
+enum Request {
+    Start,
+    Stop,
+    Cancel,
+    Status,
+    LastTranscript,
+    Subscribe,
+}
+
+/// `$XDG_RUNTIME_DIR/fnkey.sock`, or ~/.config/fnkey/fnkey.sock without one
+pub fn socket_path() -> Option<PathBuf> {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir).join("fnkey.sock")),
+        _ => config_path("fnkey.sock"),
+    }
+}
+
+/// Bind the control socket at [`socket_path`] and serve it in the background.
+pub fn spawn_server(state: &Arc<AppState>) -> io::Result<PathBuf> {
+    let path = socket_path().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "HOME is not set"))?;
+    spawn_server_at(state, &path)?;
+    Ok(path)
+}
+
+/// Bind `path` (replacing a stale socket) and serve it in the background.
+/// Fails if another instance is already listening there.
+pub fn spawn_server_at(state: &Arc<AppState>, path: &Path) -> io::Result<()> {
+    if UnixStream::connect(path).is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("{} is in use by another fnkey", path.display()),
+        ));
+    }
+    let _ = std::fs::remove_file(path);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+
+    let state = Arc::clone(state);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let state = Arc::clone(&state);
+                    thread::spawn(move || {
+                        let _ = serve_client(&state, stream);
+                    });
+                }
+                Err(e) => log_error(&format!("Control socket accept failed: {}", e)),
+            }
+        }
+    });
+    Ok(())
+}
+
+fn serve_client(state: &Arc<AppState>, stream: UnixStream) -> io::Result<()> {
+    let mut out = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request = match serde_json::from_str::<Request>(&line) {
+            Ok(r) => r,
+            Err(e) => {
+                write_line(&mut out, &json!({"ok": false, "error": e.to_string()}))?;
+                continue;
+            }
+        };
+        if let Request::Subscribe = request {
+            // Subscribe before acknowledging so no event slips in between
+            let events = state.subscribe();
+            write_line(&mut out, &json!({"ok": true}))?;
+            for event in events {
+                write_line(&mut out, &serde_json::to_value(&event).unwrap_or(Value::Null))?;
+            }
+            return Ok(());
+        }
+        write_line(&mut out, &handle(state, request))?;
+    }
+    Ok(())
+}
+
+fn handle(state: &Arc<AppState>, request: Request) -> Value {
+    match request {
+        Request::Start => start_recording(state),
+        Request::Stop => stop_recording(state),
+        Request::Cancel => cancel_recording(state),
+        Request::Status => {
+            return json!({
+                "ok": true,
+                "recording": state.is_recording(),
+                "transcribing": state.is_transcribing(),
+            })
+        }
+        Request::LastTranscript => return json!({"ok": true, "transcript": state.last_transcript()}),
+        Request::Subscribe => unreachable!("handled by serve_client"),
+    }
+    json!({"ok": true})
+}
+
+fn write_line(out: &mut UnixStream, value: &Value) -> io::Result<()> {
+    writeln!(out, "{}", value)?;
+    out.flush()
+}
@@ -0,0 +1,1276 @@
+//! Deepgram streaming — runs entirely on a background thread
+
+use std::collections::HashSet;
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use tungstenite::client::IntoClientRequest;
+use tungstenite::http::{HeaderName, HeaderValue};
+use tungstenite::protocol::frame::coding::CloseCode;
+use tungstenite::protocol::Message;
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::WebSocket;
+
+use crate::audio::to_pcm16;
+use crate::config::{config_path, log_error};
+use crate::readiness;
+use crate::send_queue::{would_block, SendQueue};
+use crate::transcriber::{Segment, StreamSession, TranscribeError, Transcriber, Transcript, Word};
+
+pub const DEEPGRAM_SAMPLE_RATE: u32 = 16000;
+
+/// Hosted API; self-hosted servers are configured with `base_url`
+pub const DEEPGRAM_BASE_URL: &str = "wss://api.deepgram.com";
+
+/// Messages sent from audio callback / event tap to the WebSocket thread
+pub enum WsCommand {
+    /// Raw PCM audio chunk (already resampled to 16kHz, i16 LE bytes)
+    Audio(Vec<u8>),
+    /// Stop streaming, finalize, paste result
+    Stop,
+}
+
+/// Result from Deepgram streaming thread
+pub enum DgResult {
+    /// Transcription succeeded (text may be empty)
+    Ok(Transcript),
+    /// Connection or streaming error
+    Err(String),
+}
+
+/// Audio per channel message when streaming a finished clip (1s of 16kHz i16),
+/// and per hand-over from a stream's history; the send queue re-chunks it
+const CLIP_CHUNK_BYTES: usize = DEEPGRAM_SAMPLE_RATE as usize * 2;
+
+/// 16kHz i16
+const BYTES_PER_MS: usize = DEEPGRAM_SAMPLE_RATE as usize * 2 / 1000;
+const BYTES_PER_SEC: usize = BYTES_PER_MS * 1000;
+
+/// Send queue bound; audio is handed over while the queue is under half of
+/// this and otherwise waits in the stream's history, so nothing overflows
+const MAX_QUEUED_BYTES: usize = 10_000 * BYTES_PER_MS;
+
+/// How long the final flush and drain may take after Stop
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Times a stream may reconnect after its connection drops before it gives
+/// up and the chain falls back
+pub const MAX_RECONNECTS: u32 = 3;
+
+/// Pause before a reconnect, times the attempt number
+const RECONNECT_BACKOFF: Duration = Duration::from_millis(100);
+
+// ============================================================================
+// Messages
+// ============================================================================
+
+/// One message from the Listen WebSocket
+#[derive(Clone, Debug, PartialEq)]
+pub enum DgMessage {
+    Results(DgResults),
+    Metadata(DgMetadata),
+    UtteranceEnd(DgUtteranceEnd),
+    SpeechStarted(DgSpeechStarted),
+    Error(DgError),
+    /// A message type we don't know, by name
+    Unknown(String),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+enum Tagged {
+    Results(DgResults),
+    Metadata(DgMetadata),
+    UtteranceEnd(DgUtteranceEnd),
+    SpeechStarted(DgSpeechStarted),
+    Error(DgError),
+}
+
+impl DgMessage {
+    pub fn parse(json_text: &str) -> Result<Self, String> {
+        let value: serde_json::Value = serde_json::from_str(json_text).map_err(|e| format!("bad JSON: {}", e))?;
+        // Handshake-time failures use {"err_code", "err_msg"} and no type
+        if let Some(code) = value.get("err_code").and_then(|c| c.as_str()) {
+            let message = value.get("err_msg").and_then(|m| m.as_str()).unwrap_or_default();
+            return Ok(DgMessage::Error(DgError { description: message.to_string(), message: String::new(), variant: code.to_string() }));
+        }
+        let kind = value.get("type").and_then(|t| t.as_str()).ok_or("message without a type")?.to_string();
+        if !matches!(kind.as_str(), "Results" | "Metadata" | "UtteranceEnd" | "SpeechStarted" | "Error") {
+            return Ok(DgMessage::Unknown(kind));
+        }
+        Ok(match serde_json::from_value(value).map_err(|e| format!("bad {} message: {}", kind, e))? {
+            Tagged::Results(r) => DgMessage::Results(r),
+            Tagged::Metadata(m) => DgMessage::Metadata(m),
+            Tagged::UtteranceEnd(u) => DgMessage::UtteranceEnd(u),
+            Tagged::SpeechStarted(s) => DgMessage::SpeechStarted(s),
+            Tagged::Error(e) => DgMessage::Error(e),
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct DgResults {
+    /// Seconds from the start of the stream
+    #[serde(default)]
+    pub start: f64,
+    #[serde(default)]
+    pub duration: f64,
+    /// This stretch of audio won't be revised again
+    #[serde(default)]
+    pub is_final: bool,
+    /// Endpointing detected the end of an utterance
+    #[serde(default)]
+    pub speech_final: bool,
+    /// Answer to a Finalize message
+    #[serde(default)]
+    pub from_finalize: bool,
+    pub channel: DgChannel,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct DgChannel {
+    #[serde(default)]
+    pub alternatives: Vec<DgAlternative>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct DgAlternative {
+    #[serde(default)]
+    pub transcript: String,
+    #[serde(default)]
+    pub confidence: f32,
+    #[serde(default)]
+    pub words: Vec<DgWord>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct DgWord {
+    pub word: String,
+    pub start: f64,
+    pub end: f64,
+    #[serde(default)]
+    pub confidence: f32,
+    /// With smart_format / punctuate
+    #[serde(default)]
+    pub punctuated_word: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct DgMetadata {
+    #[serde(default)]
+    pub request_id: String,
+    /// Seconds of audio Deepgram received
+    #[serde(default)]
+    pub duration: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct DgUtteranceEnd {
+    #[serde(default)]
+    pub last_word_end: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct DgSpeechStarted {
+    #[serde(default)]
+    pub timestamp: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct DgError {
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub message: String,
+    #[serde(default)]
+    pub variant: String,
+}
+
+impl std::fmt::Display for DgError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let text = if self.description.is_empty() { &self.message } else { &self.description };
+        match (self.variant.is_empty(), text.is_empty()) {
+            (false, false) => write!(f, "{} ({})", text, self.variant),
+            (false, true) => write!(f, "{}", self.variant),
+            _ => write!(f, "{}", text),
+        }
+    }
+}
+
+impl DgResults {
+    /// Top alternative, if it has any text
+    fn best(&self) -> Option<&DgAlternative> {
+        self.channel.alternatives.first().filter(|a| !a.transcript.is_empty())
+    }
+
+    pub fn segment(&self) -> Option<Segment> {
+        Some(Segment { transcript: self.best()?.transcript.clone(), is_final: self.is_final, start: self.start, duration: self.duration })
+    }
+}
+
+/// Collects a stream's messages into its final transcript
+#[derive(Default)]
+pub struct DgTranscript {
+    finals: Vec<String>,
+    words: Vec<Word>,
+    /// Per-final confidences, for results without word data
+    confidences: Vec<f32>,
+    request_id: Option<String>,
+    error: Option<String>,
+    /// Stream time at which the current connection's audio starts
+    offset: f64,
+    /// End of the last final result, in stream time
+    finalized: f64,
+    /// A final answering Finalize has arrived
+    flushed: bool,
+    /// Unknown message types already logged
+    unknown: HashSet<String>,
+    pub messages: u32,
+}
+
+impl DgTranscript {
+    /// Take one message; returns the update to show, if any
+    pub fn handle(&mut self, json_text: &str) -> Option<Segment> {
+        self.messages += 1;
+        match DgMessage::parse(json_text) {
+            Ok(DgMessage::Results(results)) => {
+                let offset = self.offset;
+                if results.is_final {
+                    self.finalized = self.finalized.max(offset + results.start + results.duration);
+                    self.flushed |= results.from_finalize;
+                }
+                if let (true, Some(best)) = (results.is_final, results.best()) {
+                    self.finals.push(best.transcript.clone());
+                    self.confidences.push(best.confidence);
+                    self.words.extend(best.words.iter().map(|w| Word {
+                        word: w.punctuated_word.clone().unwrap_or_else(|| w.word.clone()),
+                        start: offset + w.start,
+                        end: offset + w.end,
+                        confidence: Some(w.confidence),
+                    }));
+                }
+                results.segment().map(|segment| Segment { start: offset + segment.start, ..segment })
+            }
+            Ok(DgMessage::Metadata(metadata)) => {
+                self.request_id = Some(metadata.request_id).filter(|id| !id.is_empty());
+                None
+            }
+            Ok(DgMessage::Error(error)) => {
+                self.error = Some(error.to_string());
+                None
+            }
+            Ok(DgMessage::UtteranceEnd(_) | DgMessage::SpeechStarted(_)) => None,
+            Ok(DgMessage::Unknown(kind)) => {
+                if self.unknown.insert(kind.clone()) {
+                    log_error(&format!("Deepgram: ignoring unknown message type '{}'", kind));
+                }
+                None
+            }
+            Err(e) => {
+                log_error(&format!("Deepgram: {}", e));
+                None
+            }
+        }
+    }
+
+    /// Error reported by the server, if any
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Request id from the current connection's Metadata, for support tickets
+    pub fn request_id(&self) -> Option<&str> {
+        self.request_id.as_deref()
+    }
+
+    /// Seconds of audio covered by final results so far
+    pub fn finalized(&self) -> f64 {
+        self.finalized
+    }
+
+    /// Whether the server has answered Finalize on the current connection
+    pub fn flushed(&self) -> bool {
+        self.flushed
+    }
+
+    /// Messages from now on come from a new connection whose clock starts
+    /// `offset` seconds into the stream (negative for a reused connection
+    /// that carried earlier audio)
+    pub fn reconnected(&mut self, offset: f64) {
+        self.offset = offset;
+        self.request_id = None;
+        self.flushed = false;
+    }
+
+    /// Finals joined in order, with their words
+    pub fn transcript(&self) -> Transcript {
+        let confidence = if self.words.is_empty() {
+            (!self.confidences.is_empty()).then(|| self.confidences.iter().sum::<f32>() / self.confidences.len() as f32)
+        } else {
+            Some(self.words.iter().filter_map(|w| w.confidence).sum::<f32>() / self.words.len() as f32)
+        };
+        Transcript { text: self.finals.join(" "), provider: "Deepgram".to_string(), words: self.words.clone(), confidence }
+    }
+}
+
+// ============================================================================
+// Query options
+// ============================================================================
+
+/// Model families that stream; a model is a family or `family-variant`
+const STREAMING_MODELS: &[&str] = &["nova-3", "nova-2", "nova", "enhanced", "base"];
+
+const REDACT_VALUES: &[&str] = &["pci", "pii", "phi", "numbers", "ssn"];
+
+/// Where and how to reach the Listen API, from the `deepgram` config file
+/// and per-provider options. Defaults match what fnkey always sent.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeepgramOptions {
+    pub model: String,
+    /// BCP-47 tag, or "multi" for code-switching (nova-2/nova-3)
+    pub language: String,
+    /// Silence (ms) that ends an utterance; None disables endpointing
+    pub endpointing: Option<u32>,
+    pub punctuate: bool,
+    pub smart_format: bool,
+    pub filler_words: bool,
+    pub numerals: bool,
+    pub profanity_filter: bool,
+    /// Entity classes to redact, e.g. ["pci", "ssn"]
+    pub redact: Vec<String>,
+    /// Turn spoken "comma", "period", … into punctuation; needs punctuate
+    pub dictation: bool,
+    /// Send UtteranceEnd after this much silence (ms, at least 1000)
+    pub utterance_end_ms: Option<u32>,
+    /// ws:// or wss:// server root; /v1/listen is appended
+    pub base_url: String,
+    /// Extra handshake headers, e.g. for an auth proxy in front of the server
+    pub headers: Vec<(String, String)>,
+    /// PEM file of extra trusted roots for a self-hosted server
+    pub ca_bundle: Option<PathBuf>,
+    /// Audio per WebSocket message (20-100ms), whatever size it's recorded in
+    pub chunk_ms: u32,
+    /// Keep a connection open between sessions, closing it after this many
+    /// idle seconds (see [`DeepgramPool`]); None connects on each key press
+    pub prewarm: Option<u32>,
+}
+
+impl Default for DeepgramOptions {
+    fn default() -> Self {
+        DeepgramOptions {
+            model: "nova-3".to_string(),
+            language: "multi".to_string(),
+            endpointing: Some(300),
+            punctuate: true,
+            smart_format: true,
+            filler_words: false,
+            numerals: false,
+            profanity_filter: false,
+            redact: Vec::new(),
+            dictation: false,
+            utterance_end_ms: None,
+            base_url: DEEPGRAM_BASE_URL.to_string(),
+            headers: Vec::new(),
+            ca_bundle: None,
+            chunk_ms: 50,
+            prewarm: None,
+        }
+    }
+}
+
+impl DeepgramOptions {
+    /// The `deepgram` config file: `key=value` settings, whitespace or line
+    /// separated; blank lines and `#` comments are skipped.
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let mut settings = Vec::new();
+        for (n, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            for field in line.split_whitespace() {
+                let (key, value) = field
+                    .split_once('=')
+                    .ok_or_else(|| format!("deepgram line {}: expected key=value, got '{}'", n + 1, field))?;
+                settings.push((key.to_string(), value.to_string()));
+            }
+        }
+        let mut options = DeepgramOptions::default();
+        options.configure(&settings)?;
+        Ok(options)
+    }
+
+    /// Apply `key=value` settings on top of these, checking each value.
+    pub fn configure(&mut self, settings: &[(String, String)]) -> Result<(), String> {
+        for (key, value) in settings {
+            let flag = || parse_bool(key, value);
+            match key.as_str() {
+                "model" => {
+                    let family = STREAMING_MODELS.iter().find(|f| value == *f || value.starts_with(&format!("{}-", f)));
+                    if family.is_none() {
+                        return Err(format!("unknown streaming model '{}' (expected {})", value, STREAMING_MODELS.join(", ")));
+                    }
+                    self.model = value.clone();
+                }
+                "language" => {
+                    if value != "multi" && !is_language_tag(value) {
+                        return Err(format!("bad language '{}' (expected e.g. en, en-US, multi)", value));
+                    }
+                    self.language = value.clone();
+                }
+                "endpointing" => {
+                    self.endpointing = match value.as_str() {
+                        "false" => None,
+                        ms => Some(ms.parse().ok().filter(|&ms| ms > 0).ok_or_else(|| format!("bad endpointing '{}' (ms or false)", value))?),
+                    }
+                }
+                "punctuate" => self.punctuate = flag()?,
+                "smart_format" => self.smart_format = flag()?,
+                "filler_words" => self.filler_words = flag()?,
+                "numerals" => self.numerals = flag()?,
+                "profanity_filter" => self.profanity_filter = flag()?,
+                "dictation" => self.dictation = flag()?,
+                "redact" => {
+                    self.redact.clear();
+                    for class in value.split(',').filter(|c| !c.is_empty() && *c != "false") {
+                        if !REDACT_VALUES.contains(&class) {
+                            return Err(format!("unknown redact '{}' (expected {})", class, REDACT_VALUES.join(", ")));
+                        }
+                        self.redact.push(class.to_string());
+                    }
+                }
+                "utterance_end_ms" => {
+                    self.utterance_end_ms = match value.as_str() {
+                        "false" => None,
+                        ms => Some(ms.parse().ok().filter(|&ms| ms >= 1000).ok_or_else(|| format!("bad utterance_end_ms '{}' (at least 1000, or false)", value))?),
+                    }
+                }
+                "chunk_ms" => {
+                    self.chunk_ms = value.parse().ok().filter(|ms| (20..=100).contains(ms)).ok_or_else(|| format!("chunk_ms must be 20-100, got '{}'", value))?;
+                }
+                "prewarm" => {
+                    self.prewarm = match value.as_str() {
+                        "false" => None,
+                        secs => Some(secs.parse().ok().filter(|&secs| secs > 0).ok_or_else(|| format!("bad prewarm '{}' (idle seconds or false)", value))?),
+                    }
+                }
+                "base_url" => {
+                    if !value.starts_with("ws://") && !value.starts_with("wss://") {
+                        return Err(format!("base_url must be ws:// or wss://, got '{}'", value));
+                    }
+                    self.base_url = value.trim_end_matches('/').to_string();
+                }
+                "ca_bundle" => {
+                    // Relative paths are under ~/.config/fnkey
+                    let path = match PathBuf::from(value) {
+                        path if path.is_absolute() => path,
+                        _ => config_path(value).ok_or("HOME is not set")?,
+                    };
+                    load_ca_bundle(&path)?;
+                    self.ca_bundle = Some(path);
+                }
+                other => match other.strip_prefix("header.") {
+                    Some(name) => {
+                        HeaderName::from_bytes(name.as_bytes()).map_err(|_| format!("bad header name '{}'", name))?;
+                        HeaderValue::from_str(value).map_err(|_| format!("bad value for header '{}'", name))?;
+                        self.headers.retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+                        self.headers.push((name.to_string(), value.clone()));
+                    }
+                    None => return Err(format!("unknown option '{}'", other)),
+                },
+            }
+        }
+        if self.dictation && !self.punctuate {
+            return Err("dictation needs punctuate=true".to_string());
+        }
+        if self.language == "multi" && !(self.is_family("nova-3") || self.is_family("nova-2")) {
+            return Err(format!("language=multi needs a nova-2 or nova-3 model, not '{}'", self.model));
+        }
+        Ok(())
+    }
+
+    fn is_family(&self, family: &str) -> bool {
+        self.model == family || self.model.starts_with(&format!("{}-", family))
+    }
+
+    /// Bytes of 16kHz i16 audio per WebSocket message
+    pub fn chunk_bytes(&self) -> usize {
+        self.chunk_ms as usize * BYTES_PER_MS
+    }
+
+    /// A server other than Deepgram's own, which may not need a key
+    pub fn is_self_hosted(&self) -> bool {
+        self.base_url != DEEPGRAM_BASE_URL
+    }
+
+    /// Full streaming URL
+    pub fn listen_url(&self, keywords: &[String]) -> String {
+        format!("{}/v1/listen?{}", self.base_url, self.query(keywords))
+    }
+
+    /// Open the WebSocket: `key` as a token (skipped when empty), extra
+    /// headers, and the CA bundle if one is set.
+    pub fn connect(&self, key: &str, keywords: &[String]) -> Result<WebSocket<MaybeTlsStream<TcpStream>>, String> {
+        let mut request = self.listen_url(keywords).into_client_request().map_err(|e| format!("bad url: {}", e))?;
+        let headers = request.headers_mut();
+        if !key.is_empty() {
+            let token = HeaderValue::from_str(&format!("Token {}", key)).map_err(|_| "API key is not a valid header value".to_string())?;
+            headers.insert("Authorization", token);
+        }
+        for (name, value) in &self.headers {
+            // Checked in configure()
+            if let (Ok(name), Ok(value)) = (HeaderName::from_bytes(name.as_bytes()), HeaderValue::from_str(value)) {
+                headers.insert(name, value);
+            }
+        }
+        let Some(ref bundle) = self.ca_bundle else {
+            return tungstenite::connect(request).map(|(ws, _)| ws).map_err(|e| e.to_string());
+        };
+        let mut tls = native_tls::TlsConnector::builder();
+        for cert in load_ca_bundle(bundle)? {
+            tls.add_root_certificate(cert);
+        }
+        let tls = tls.build().map_err(|e| format!("TLS setup failed: {}", e))?;
+        let uri = request.uri();
+        let host = uri.host().unwrap_or_default().trim_start_matches('[').trim_end_matches(']').to_string();
+        let port = uri.port_u16().unwrap_or(if uri.scheme_str() == Some("wss") { 443 } else { 80 });
+        let stream = TcpStream::connect((host.as_str(), port)).map_err(|e| format!("can't connect to {}:{}: {}", host, port, e))?;
+        tungstenite::client_tls_with_config(request, stream, None, Some(tungstenite::Connector::NativeTls(tls)))
+            .map(|(ws, _)| ws)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Query string for /v1/listen, without the leading `?`
+    pub fn query(&self, keywords: &[String]) -> String {
+        let mut query = format!(
+            "encoding=linear16&sample_rate={}&channels=1&interim_results=true&model={}&language={}",
+            DEEPGRAM_SAMPLE_RATE,
+            urlencoding::encode(&self.model),
+            urlencoding::encode(&self.language)
+        );
+        match self.endpointing {
+            Some(ms) => query.push_str(&format!("&endpointing={}", ms)),
+            None => query.push_str("&endpointing=false"),
+        }
+        for (name, on) in [
+            ("punctuate", self.punctuate),
+            ("smart_format", self.smart_format),
+            ("filler_words", self.filler_words),
+            ("numerals", self.numerals),
+            ("profanity_filter", self.profanity_filter),
+            ("dictation", self.dictation),
+        ] {
+            if on {
+                query.push_str(&format!("&{}=true", name));
+            }
+        }
+        for class in &self.redact {
+            query.push_str(&format!("&redact={}", class));
+        }
+        if let Some(ms) = self.utterance_end_ms {
+            query.push_str(&format!("&utterance_end_ms={}", ms));
+        }
+        // Nova-3 takes keyterm prompts; older models boost single keywords
+        let param = if self.is_family("nova-3") { "keyterm" } else { "keywords" };
+        for kw in keywords {
+            query.push_str(&format!("&{}={}", param, urlencoding::encode(kw)));
+        }
+        query
+    }
+}
+
+/// Every certificate in a PEM file
+fn load_ca_bundle(path: &std::path::Path) -> Result<Vec<native_tls::Certificate>, String> {
+    let pem = std::fs::read_to_string(path).map_err(|e| format!("can't read CA bundle {}: {}", path.display(), e))?;
+    const END: &str = "-----END CERTIFICATE-----";
+    let certs = pem
+        .split_inclusive(END)
+        .filter(|block| block.contains(END))
+        .map(|block| native_tls::Certificate::from_pem(block.as_bytes()).map_err(|e| format!("bad certificate in {}: {}", path.display(), e)))
+        .collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(format!("no certificates in {}", path.display()));
+    }
+    Ok(certs)
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, String> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(format!("{} must be true or false, got '{}'", key, value)),
+    }
+}
+
+/// Loose BCP-47: a 2-3 letter language, then alphanumeric subtags
+fn is_language_tag(tag: &str) -> bool {
+    let mut parts = tag.split('-');
+    let primary = parts.next().unwrap_or_default();
+    (2..=3).contains(&primary.len())
+        && primary.chars().all(|c| c.is_ascii_lowercase())
+        && parts.all(|p| (2..=8).contains(&p.len()) && p.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+// ============================================================================
+// Provider
+// ============================================================================
+
+/// Deepgram (Nova-3 by default) over the streaming WebSocket API
+pub struct DeepgramTranscriber {
+    key: String,
+    keywords: Vec<String>,
+    options: DeepgramOptions,
+    pool: Option<Arc<DeepgramPool>>,
+}
+
+impl DeepgramTranscriber {
+    pub fn new(key: String, keywords: Vec<String>) -> Self {
+        DeepgramTranscriber { key, keywords, options: DeepgramOptions::default(), pool: None }
+    }
+
+    pub fn with_options(mut self, options: DeepgramOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Stream over `pool`'s warm connection when it has one
+    pub fn with_pool(mut self, pool: Arc<DeepgramPool>) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// With a [`DeepgramPool`] of its own if the options ask for `prewarm`
+    pub fn prewarmed(self) -> Self {
+        if self.options.prewarm.is_none() {
+            return self;
+        }
+        let pool = DeepgramPool::start(self.key.clone(), self.keywords.clone(), self.options.clone(), KEEPALIVE_INTERVAL);
+        self.with_pool(pool)
+    }
+}
+
+/// Live session backed by [`spawn_deepgram_thread`]
+struct DeepgramStream {
+    tx: mpsc::Sender<WsCommand>,
+    result_rx: mpsc::Receiver<DgResult>,
+    sample_rate: u32,
+    /// A result that came before Stop: the thread gave up early
+    early: Option<DgResult>,
+}
+
+impl StreamSession for DeepgramStream {
+    fn push_audio(&mut self, samples: &[f32]) {
+        let _ = self.tx.send(WsCommand::Audio(to_pcm16(samples, self.sample_rate, DEEPGRAM_SAMPLE_RATE)));
+    }
+
+    fn finish(self: Box<Self>, timeout: Duration) -> Result<Transcript, TranscribeError> {
+        let _ = self.tx.send(WsCommand::Stop);
+        let result = match self.early {
+            Some(result) => Ok(result),
+            None => self.result_rx.recv_timeout(timeout),
+        };
+        match result {
+            Ok(DgResult::Ok(transcript)) if !transcript.text.is_empty() => Ok(transcript),
+            Ok(DgResult::Ok(_)) => Err(TranscribeError::Empty),
+            Ok(DgResult::Err(e)) => Err(TranscribeError::Failed(e)),
+            Err(_) => Err(TranscribeError::Timeout),
+        }
+    }
+
+    fn failed(&mut self) -> bool {
+        if self.early.is_none() {
+            self.early = self.result_rx.try_recv().ok();
+        }
+        matches!(self.early, Some(DgResult::Err(_)))
+    }
+}
+
+impl Transcriber for DeepgramTranscriber {
+    fn name(&self) -> &str {
+        "Deepgram"
+    }
+
+    fn start_stream(&self, sample_rate: u32, segments: Option<mpsc::Sender<Segment>>) -> Option<Box<dyn StreamSession>> {
+        let (tx, rx) = mpsc::channel();
+        let (result_tx, result_rx) = mpsc::channel();
+        spawn_deepgram_thread(self.key.clone(), rx, self.keywords.clone(), self.options.clone(), result_tx, segments, self.pool.clone());
+        Some(Box::new(DeepgramStream { tx, result_rx, sample_rate, early: None }))
+    }
+
+    /// Push the whole clip through a stream as fast as Deepgram takes it
+    fn transcribe(&self, audio: &[f32], sample_rate: u32) -> Result<Transcript, TranscribeError> {
+        let (tx, rx) = mpsc::channel();
+        let (result_tx, result_rx) = mpsc::channel();
+        spawn_deepgram_thread(self.key.clone(), rx, self.keywords.clone(), self.options.clone(), result_tx, None, self.pool.clone());
+        let pcm = to_pcm16(audio, sample_rate, DEEPGRAM_SAMPLE_RATE);
+        for chunk in pcm.chunks(CLIP_CHUNK_BYTES) {
+            if tx.send(WsCommand::Audio(chunk.to_vec())).is_err() {
+                break;
+            }
+        }
+        // Deepgram runs faster than real time; allow the clip's length on top
+        // of the usual 5s so long files aren't cut off
+        let clip_secs = audio.len() as u64 / sample_rate.max(1) as u64;
+        let stream = DeepgramStream { tx, result_rx, sample_rate, early: None };
+        Box::new(stream).finish(Duration::from_secs(5 + clip_secs))
+    }
+}
+
+/// Spawn a background thread that:
+/// 1. Connects WebSocket to Deepgram (or `options.base_url`)
+/// 2. Sends audio from rx as the socket takes it
+/// 3. Reads transcripts as they arrive (forwarded to `updates` if given)
+/// 4. On Stop command: sends the tail and CloseStream, waits for the finals
+///
+/// The socket stays non-blocking; between events the thread sleeps in
+/// [`readiness::wait`] and a relay thread wakes it for each command. If the
+/// connection drops, it reconnects and replays the audio since the last
+/// final result; the stream fails only once [`MAX_RECONNECTS`] are used up.
+///
+/// With a `pool`, the stream starts on its warm connection if it has one,
+/// ends with Finalize instead of CloseStream, and hands the connection back.
+pub fn spawn_deepgram_thread(
+    key: String,
+    rx: mpsc::Receiver<WsCommand>,
+    keywords: Vec<String>,
+    options: DeepgramOptions,
+    result_tx: mpsc::Sender<DgResult>,
+    updates: Option<mpsc::Sender<Segment>>,
+    pool: Option<Arc<DeepgramPool>>,
+) {
+    thread::spawn(move || {
+        let warm = pool.as_ref().and_then(|pool| pool.take());
+        let connection = match warm {
+            Some(warm) => Ok((warm.ws, warm.socket, warm.sent)),
+            None => open(&options, &key, &keywords).map(|(ws, socket)| (ws, socket, 0)),
+        };
+        let setup = connection.and_then(|(ws, socket, sent)| {
+            let (waker, wakeups) = readiness::channel().map_err(|e| e.to_string())?;
+            Ok((ws, socket, sent, waker, wakeups))
+        });
+        let (mut ws, mut socket, sent, waker, wakeups) = match setup {
+            Ok(setup) => setup,
+            Err(e) => {
+                let msg = format!("Deepgram connect failed: {}", e);
+                log_error(&msg);
+                if let Some(ref pool) = pool {
+                    pool.put_back(None);
+                }
+                let _ = result_tx.send(DgResult::Err(msg));
+                // Drain remaining commands so senders don't block
+                for _ in rx.iter() {}
+                return;
+            }
+        };
+
+        let mut stream = StreamState::new(readiness::relay(rx, waker), updates, options.chunk_bytes(), pool.is_some(), sent);
+        let mut ws_error: Option<String> = None;
+        let mut reconnects = 0;
+        let mut gave_up = false;
+        loop {
+            match stream.run(&mut ws, &socket, &wakeups) {
+                Ended::Done => break,
+                Ended::Failed(msg) => {
+                    ws_error = Some(msg);
+                    break;
+                }
+                Ended::Dropped(msg) => match reconnect(&options, &key, &keywords, &mut reconnects) {
+                    Some((new_ws, new_socket)) => {
+                        (ws, socket) = (new_ws, new_socket);
+                        let replay = stream.rewind();
+                        log_error(&format!("Deepgram: reconnected ({}/{}), replaying {}ms", reconnects, MAX_RECONNECTS, replay / BYTES_PER_MS));
+                    }
+                    None => {
+                        ws_error = Some(format!("{} (gave up after {} reconnects)", msg, MAX_RECONNECTS));
+                        gave_up = true;
+                        break;
+                    }
+                },
+            }
+        }
+        match pool {
+            Some(ref pool) if stream.reusable => {
+                let sent = stream.connection_sent();
+                pool.put_back(Some(Warm { ws, socket, sent }));
+            }
+            _ => {
+                // Best effort; the socket is non-blocking and about to be dropped
+                let _ = ws.close(None);
+                let _ = ws.flush();
+                if let Some(ref pool) = pool {
+                    pool.put_back(None);
+                }
+            }
+        }
+        let StreamState { dg, mut queue, started, .. } = stream;
+        let stats = |queue: &SendQueue| queue.stats.summary(BYTES_PER_MS);
+        queue.drop_all();
+        if queue.stats.dropped_frames > 0 {
+            log_error(&format!("Deepgram: audio lost: {}", stats(&queue)));
+        }
+
+        // Send result back — if we got a transcript, use it even if WS errored,
+        // unless the stream was cut short and the rest of the audio is missing
+        let transcript = dg.transcript();
+        if !transcript.text.is_empty() && !gave_up {
+            let _ = result_tx.send(DgResult::Ok(transcript));
+        } else if let Some(err) = ws_error.or_else(|| dg.error().map(|e| format!("Deepgram error: {}", e))) {
+            let _ = result_tx.send(DgResult::Err(err));
+        } else {
+            let msg = format!(
+                "Deepgram: empty transcript after {}ms, {}, {} msgs recv'd{}",
+                started.elapsed().as_millis(), stats(&queue), dg.messages,
+                dg.request_id().map(|id| format!(", request {}", id)).unwrap_or_default()
+            );
+            log_error(&msg);
+            let _ = result_tx.send(DgResult::Err(msg));
+        }
+    });
+}
+
+/// How one connection of a stream ended
+enum Ended {
+    /// Finals are in (or the drain timed out)
+    Done,
+    /// Not worth retrying: a server error, a cancel, a local failure
+    Failed(String),
+    /// The connection was lost; a new one can pick up from the last final
+    Dropped(String),
+}
+
+/// A stream's progress, kept across reconnects
+struct StreamState {
+    commands: mpsc::Receiver<WsCommand>,
+    updates: Option<mpsc::Sender<Segment>>,
+    dg: DgTranscript,
+    queue: SendQueue,
+    /// Audio from the last final result on, for replay after a reconnect
+    history: Vec<u8>,
+    /// Stream offset of `history[0]`, in bytes
+    history_start: usize,
+    /// Stream offset of the next byte to hand to the queue
+    fed: usize,
+    /// Set by Stop: the final results must arrive before this
+    deadline: Option<Instant>,
+    started: Instant,
+    /// End with Finalize, leaving the connection open for the next stream
+    finalize: bool,
+    /// Ended on Finalize's answer, so the connection can be used again
+    reusable: bool,
+    /// Audio the connection carried before `connection_start`
+    connection_prior: usize,
+    /// Stream offset the current connection started at
+    connection_start: usize,
+}
+
+impl StreamState {
+    /// `prior` is the audio already sent over a reused connection
+    fn new(
+        commands: mpsc::Receiver<WsCommand>,
+        updates: Option<mpsc::Sender<Segment>>,
+        chunk_bytes: usize,
+        finalize: bool,
+        prior: usize,
+    ) -> Self {
+        let mut dg = DgTranscript::default();
+        dg.reconnected(-(prior as f64) / BYTES_PER_SEC as f64);
+        StreamState {
+            commands,
+            updates,
+            dg,
+            queue: SendQueue::new(chunk_bytes, MAX_QUEUED_BYTES),
+            history: Vec::new(),
+            history_start: 0,
+            fed: 0,
+            deadline: None,
+            started: Instant::now(),
+            finalize,
+            reusable: false,
+            connection_prior: prior,
+            connection_start: 0,
+        }
+    }
+
+    /// Audio sent over the current connection, this stream's included
+    fn connection_sent(&self) -> usize {
+        self.connection_prior + self.fed - self.connection_start
+    }
+
+    /// Final results cover everything sent
+    fn all_final(&self) -> bool {
+        self.finalized_bytes() >= self.fed
+    }
+
+    fn finalized_bytes(&self) -> usize {
+        (self.dg.finalized() * DEEPGRAM_SAMPLE_RATE as f64) as usize * 2
+    }
+
+    /// "after 1234ms, 20 chunks/31KB sent, 3 msgs recv'd", for log lines
+    fn progress(&self) -> String {
+        format!("after {}ms, {}, {} msgs recv'd", self.started.elapsed().as_millis(), self.queue.stats.summary(BYTES_PER_MS), self.dg.messages)
+    }
+
+    fn audio_end(&self) -> usize {
+        self.history_start + self.history.len()
+    }
+
+    /// Forget audio the server has finalized; it will never be replayed
+    fn trim_history(&mut self) {
+        let finalized = self.finalized_bytes().min(self.fed);
+        if finalized > self.history_start {
+            self.history.drain(..finalized - self.history_start);
+            self.history_start = finalized;
+        }
+    }
+
+    /// Start a new connection from the last final. Returns the bytes to replay.
+    fn rewind(&mut self) -> usize {
+        self.queue.clear();
+        self.fed = self.history_start;
+        self.connection_prior = 0;
+        self.connection_start = self.history_start;
+        self.dg.reconnected(self.history_start as f64 / BYTES_PER_SEC as f64);
+        if self.deadline.is_some() {
+            self.deadline = Some(Instant::now() + DRAIN_TIMEOUT);
+        }
+        self.history.len()
+    }
+
+    /// Stream over one connection until it ends
+    fn run(&mut self, ws: &mut WebSocket<MaybeTlsStream<TcpStream>>, socket: &TcpStream, wakeups: &readiness::Wakeups) -> Ended {
+        let mut close_sent = false;
+        // CloseStream is still in tungstenite's buffer
+        let mut close_blocked = false;
+        let mut readable = true;
+        loop {
+            // 1. Keep what the audio callback / event tap has sent
+            while self.deadline.is_none() {
+                match self.commands.try_recv() {
+                    Ok(WsCommand::Audio(bytes)) => self.history.extend_from_slice(&bytes),
+                    Ok(WsCommand::Stop) => self.deadline = Some(Instant::now() + DRAIN_TIMEOUT),
+                    Err(mpsc::TryRecvError::Empty) => break,
+                    Err(mpsc::TryRecvError::Disconnected) => {
+                        return Ended::Failed(format!("Deepgram: channel disconnected {}", self.progress()));
+                    }
+                }
+            }
+
+            // 2. Hand audio to the send queue while it keeps up; the rest waits
+            // in the history. After Stop, the tail goes out as a short chunk.
+            let end = self.audio_end();
+            while self.fed < end && self.queue.queued_bytes() < MAX_QUEUED_BYTES / 2 {
+                let upto = end.min(self.fed + CLIP_CHUNK_BYTES);
+                self.queue.push(&self.history[self.fed - self.history_start..upto - self.history_start]);
+                self.fed = upto;
+            }
+            let held_back = self.fed < end;
+            if self.deadline.is_some() && !held_back {
+                self.queue.flush_partial();
+            }
+
+            // 3. Send what the socket will take; the rest waits until it's writable.
+            // After Stop, CloseStream follows the last of the audio.
+            let mut sent = self.queue.pump(ws);
+            if sent.is_ok() && close_blocked {
+                sent = match ws.flush() {
+                    Err(ref e) if would_block(e) => Ok(()),
+                    result => result.map_err(|e| e.to_string()),
+                };
+                close_blocked = sent.is_err();
+            }
+            if sent.is_ok() && self.deadline.is_some() && !close_sent && !held_back && !self.queue.wants_write() {
+                if self.finalize && self.all_final() {
+                    // Nothing left for Finalize to flush
+                    self.reusable = true;
+                    return Ended::Done;
+                }
+                close_sent = true;
+                let kind = if self.finalize { "Finalize" } else { "CloseStream" };
+                let close_msg = serde_json::json!({ "type": kind });
+                sent = match ws.send(Message::Text(close_msg.to_string())) {
+                    Err(ref e) if would_block(e) => {
+                        close_blocked = true;
+                        Ok(())
+                    }
+                    result => result.map_err(|e| e.to_string()),
+                };
+            }
+            if let Err(e) = sent {
+                let msg = format!("Deepgram send error {}: {}", self.progress(), e);
+                log_error(&msg);
+                return Ended::Dropped(msg);
+            }
+
+            // 4. Read everything that has arrived, including what TLS has buffered
+            while readable {
+                match ws.read() {
+                    Ok(Message::Text(text)) => {
+                        forward_update(self.dg.handle(&text), &self.updates);
+                        if let Some(e) = self.dg.error() {
+                            let msg = format!("Deepgram error {}: {}", self.progress(), e);
+                            log_error(&msg);
+                            return Ended::Failed(msg);
+                        }
+                        self.trim_history();
+                        if close_sent && self.finalize && self.dg.flushed() {
+                            self.reusable = true;
+                            return Ended::Done;
+                        }
+                    }
+                    Ok(Message::Close(frame)) if close_sent => {
+                        if let Some(ref f) = frame {
+                            if f.code != CloseCode::Normal {
+                                log_error(&format!("Deepgram close frame: code={}, reason='{}'", f.code, f.reason));
+                            }
+                        }
+                        return Ended::Done;
+                    }
+                    Ok(Message::Close(frame)) => {
+                        // Server closed before we sent CloseStream — unexpected
+                        let reason = frame.as_ref()
+                            .map(|f| format!("code={}, reason='{}'", f.code, f.reason))
+                            .unwrap_or_else(|| "no close frame".to_string());
+                        let msg = format!("Deepgram server closed early {}: {}", self.progress(), reason);
+                        log_error(&msg);
+                        return match frame {
+                            Some(f) if !is_transient(f.code) => Ended::Failed(msg),
+                            _ => Ended::Dropped(msg),
+                        };
+                    }
+                    Ok(_) => {}
+                    Err(ref e) if would_block(e) => readable = false,
+                    // Metadata comes after the last result: nothing more to wait for
+                    Err(_) if close_sent && self.dg.request_id().is_some() => return Ended::Done,
+                    Err(tungstenite::Error::ConnectionClosed) => {
+                        let msg = format!("Deepgram connection dropped {}", self.progress());
+                        log_error(&msg);
+                        return Ended::Dropped(msg);
+                    }
+                    Err(e) => {
+                        let msg = format!("Deepgram WebSocket error {}: {}", self.progress(), e);
+                        log_error(&msg);
+                        return Ended::Dropped(msg);
+                    }
+                }
+            }
+
+            // 5. Sleep until the socket or the relay has something for us. Audio
+            // held back in the history needs no wake-up, so fetch it now.
+            if held_back && !self.queue.wants_write() {
+                continue;
+            }
+            let timeout = self.deadline.map(|d| d.saturating_duration_since(Instant::now()));
+            if timeout.is_some_and(|t| t.is_zero()) {
+                log_error(&format!("Deepgram: no close after {}s, {}", DRAIN_TIMEOUT.as_secs(), self.progress()));
+                return Ended::Done;
+            }
+            match readiness::wait(socket, wakeups, self.queue.wants_write() || close_blocked, timeout) {
+                Ok(ready) => readable = ready.readable,
+                Err(e) => {
+                    let msg = format!("Deepgram: waiting on socket failed: {}", e);
+                    log_error(&msg);
+                    return Ended::Failed(msg);
+                }
+            }
+        }
+    }
+}
+
+/// Close codes worth reconnecting after: restarts, overload, outages
+fn is_transient(code: CloseCode) -> bool {
+    matches!(code, CloseCode::Away | CloseCode::Abnormal | CloseCode::Error | CloseCode::Restart | CloseCode::Again)
+}
+
+/// Connect, with the socket non-blocking; the second half is the raw socket
+/// for [`readiness::wait`]
+fn open(options: &DeepgramOptions, key: &str, keywords: &[String]) -> Result<(WebSocket<MaybeTlsStream<TcpStream>>, TcpStream), String> {
+    let ws = options.connect(key, keywords)?;
+    let socket = tcp_stream(&ws).ok_or("unsupported stream type")?.try_clone().map_err(|e| e.to_string())?;
+    socket.set_nonblocking(true).map_err(|e| e.to_string())?;
+    Ok((ws, socket))
+}
+
+/// Open a replacement connection, backing off between attempts. None once
+/// the stream has used up its reconnects.
+fn reconnect(
+    options: &DeepgramOptions,
+    key: &str,
+    keywords: &[String],
+    attempts: &mut u32,
+) -> Option<(WebSocket<MaybeTlsStream<TcpStream>>, TcpStream)> {
+    while *attempts < MAX_RECONNECTS {
+        *attempts += 1;
+        thread::sleep(RECONNECT_BACKOFF * *attempts);
+        match open(options, key, keywords) {
+            Ok(connection) => return Some(connection),
+            Err(e) => log_error(&format!("Deepgram: reconnect {}/{} failed: {}", attempts, MAX_RECONNECTS, e)),
+        }
+    }
+    None
+}
+
+/// The TCP socket under a (possibly TLS) WebSocket
+fn tcp_stream(ws: &WebSocket<MaybeTlsStream<TcpStream>>) -> Option<&TcpStream> {
+    match ws.get_ref() {
+        MaybeTlsStream::NativeTls(s) => Some(s.get_ref()),
+        MaybeTlsStream::Plain(s) => Some(s),
+        _ => None,
+    }
+}
+
+fn forward_update(update: Option<Segment>, updates: &Option<mpsc::Sender<Segment>>) {
+    if let (Some(tx), Some(update)) = (updates, update) {
+        let _ = tx.send(update);
+    }
+}
+
+/// Transcript carried by a Deepgram `Results` message; None for other
+/// message types and for results with no text.
+pub fn parse_update(json_text: &str) -> Option<Segment> {
+    match DgMessage::parse(json_text) {
+        Ok(DgMessage::Results(results)) => results.segment(),
+        _ => None,
+    }
+}
+
+// ============================================================================
+// Connection pool
+// ============================================================================
+
+/// How often an idle connection is kept alive; Deepgram closes one that has
+/// had neither audio nor KeepAlive for 10s
+pub const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// An open connection between streams
+struct Warm {
+    ws: WebSocket<MaybeTlsStream<TcpStream>>,
+    socket: TcpStream,
+    /// Audio sent over it so far, in bytes; Deepgram's timestamps run on
+    sent: usize,
+}
+
+impl Warm {
+    /// Send KeepAlive and discard whatever arrived. False once the
+    /// connection is gone.
+    fn keep_alive(&mut self) -> bool {
+        let keep_alive = serde_json::json!({"type": "KeepAlive"});
+        match self.ws.send(Message::Text(keep_alive.to_string())) {
+            Err(ref e) if !would_block(e) => return false,
+            _ => {}
+        }
+        loop {
+            match self.ws.read() {
+                Ok(Message::Close(_)) => return false,
+                Ok(_) => {}
+                Err(ref e) if would_block(e) => return true,
+                Err(_) => return false,
+            }
+        }
+    }
+
+    fn close(mut self) {
+        let close_msg = serde_json::json!({"type": "CloseStream"});
+        let _ = self.ws.send(Message::Text(close_msg.to_string()));
+        let _ = self.ws.close(None);
+        let _ = self.ws.flush();
+    }
+}
+
+struct PoolState {
+    idle: Option<Warm>,
+    /// Streams that took from the pool and haven't finished
+    lent: usize,
+    /// Last time a stream started or finished
+    last_used: Instant,
+}
+
+impl PoolState {
+    /// Keep `warm` unless there is an idle connection already
+    fn put_idle(&mut self, warm: Warm) {
+        match self.idle {
+            None => self.idle = Some(warm),
+            Some(_) => warm.close(),
+        }
+    }
+}
+
+/// Keeps one Deepgram connection open between sessions, so a key press
+/// doesn't wait for DNS, TLS and the WebSocket handshake. Only the socket
+/// is opened ahead of time; no audio flows until a stream takes it.
+///
+/// A keeper thread opens the connection, sends KeepAlive every interval,
+/// and closes it once it has sat unused for `options.prewarm` seconds.
+/// Streams end with Finalize and hand the connection back, and the keeper
+/// opens a fresh one only when none is out. The keeper exits with the pool.
+pub struct DeepgramPool {
+    state: Arc<Mutex<PoolState>>,
+}
+
+impl DeepgramPool {
+    pub fn start(key: String, keywords: Vec<String>, options: DeepgramOptions, keepalive: Duration) -> Arc<Self> {
+        let state = Arc::new(Mutex::new(PoolState { idle: None, lent: 0, last_used: Instant::now() }));
+        let weak = Arc::downgrade(&state);
+        thread::spawn(move || keep_warm(weak, key, keywords, options, keepalive));
+        Arc::new(DeepgramPool { state })
+    }
+
+    /// The warm connection, if there is one. Every take must be matched by
+    /// a [`put_back`](Self::put_back).
+    fn take(&self) -> Option<Warm> {
+        let mut state = self.state.lock().unwrap();
+        state.lent += 1;
+        state.last_used = Instant::now();
+        state.idle.take()
+    }
+
+    /// Finish a stream, returning its connection if it can carry another
+    fn put_back(&self, warm: Option<Warm>) {
+        let mut state = self.state.lock().unwrap();
+        state.lent -= 1;
+        state.last_used = Instant::now();
+        if let Some(warm) = warm {
+            state.put_idle(warm);
+        }
+    }
+
+    /// Whether a connection is open and waiting
+    pub fn is_warm(&self) -> bool {
+        self.state.lock().unwrap().idle.is_some()
+    }
+}
+
+/// Keeper thread of a [`DeepgramPool`]
+fn keep_warm(state: Weak<Mutex<PoolState>>, key: String, keywords: Vec<String>, options: DeepgramOptions, keepalive: Duration) {
+    let idle_limit = Duration::from_secs(options.prewarm.unwrap_or(0) as u64);
+    while let Some(state) = state.upgrade() {
+        // Hold the pool (and its lock) only while tending it, not while asleep
+        {
+            let mut pool = state.lock().unwrap();
+            let wanted = pool.last_used.elapsed() < idle_limit;
+            match pool.idle.take() {
+                Some(mut warm) if wanted => {
+                    if warm.keep_alive() {
+                        pool.idle = Some(warm);
+                    } else {
+                        log_error("Deepgram: warm connection lost");
+                    }
+                }
+                Some(expired) => expired.close(),
+                None if wanted && pool.lent == 0 => {
+                    drop(pool);
+                    match open(&options, &key, &keywords) {
+                        Ok((ws, socket)) => state.lock().unwrap().put_idle(Warm { ws, socket, sent: 0 }),
+                        Err(e) => log_error(&format!("Deepgram: pre-warm failed: {}", e)),
+                    }
+                }
+                None => {}
+            }
+        }
+        drop(state);
+        thread::sleep(keepalive);
+    }
+}
@@ -0,0 +1,10 @@
+//! Groq batch fallback: Whisper large-v3 on Groq's OpenAI-compatible API
+
+use crate::openai::OpenAiTranscriber;
+
+pub const GROQ_BASE_URL: &str = "https://api.groq.com/openai/v1";
+
+/// Groq client for `key`, prompting Whisper with `keywords`
+pub fn groq_transcriber(key: &str, keywords: Vec<String>) -> OpenAiTranscriber {
+    OpenAiTranscriber::new("Groq", GROQ_BASE_URL, "whisper-large-v3").with_key(key).with_keywords(keywords)
+}
@@ -0,0 +1,47 @@
+//! fnkey-core - platform-neutral dictation pipeline
+//!
+//! Owns the recording lifecycle (key down → stream → key up → transcribe → paste)
+//! and the transcription backends ([`transcriber::Transcriber`]). Front ends implement [`Platform`] for their
+//! OS and feed hotkey edges in through [`run`] or [`handle_hotkey`]; recorded
+//! files go through [`clip::transcribe_clip`] instead.
+//!
+//! Config files (~/.config/fnkey/):
+//!   deepgram_key  - Deepgram API key (streaming, preferred)
+//!   api_key       - Groq API key (batch fallback + polish)
+//!   assemblyai_key - AssemblyAI API key (streaming, after Deepgram)
+//!   openai_key    - OpenAI API key (for `openai` in providers)
+//!   keywords      - Custom vocabulary, one term per line
+//!   auto_return   - "1" to press Return after paste
+//!   progressive   - "1" to type finished phrases while the key is held
+//!   deepgram      - Deepgram query options, e.g. `language=de endpointing=800`
+//!   providers     - Ordered fallback chain (see [`chain`])
+//!   models/       - Whisper (ggml) and Vosk models for offline transcription
+
+pub mod assemblyai;
+pub mod audio;
+pub mod chain;
+pub mod clip;
+pub mod config;
+#[cfg(unix)]
+pub mod control;
+pub mod deepgram;
+pub mod groq;
+#[cfg(feature = "cpal")]
+pub mod mic;
+pub mod openai;
+pub mod pipe;
+pub mod platform;
+pub mod readiness;
+pub mod send_queue;
+pub mod session;
+pub mod source;
+pub mod transcriber;
+#[cfg(feature = "vosk")]
+pub mod vosk;
+#[cfg(feature = "whisper")]
+pub mod whisper;
+pub mod wyoming;
+
+pub use config::{log_error, Config};
+pub use platform::Platform;
+pub use session::{cancel_recording, handle_hotkey, run, start_recording, stop_recording, AppState, SessionEvent};
@@ -0,0 +1,108 @@
+//! Microphone capture via cpal. The stream is opened on first use and only
+//! played while recording.
+
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::Stream;
+
+use crate::config::log_error;
+use crate::source::{AudioSink, AudioSource};
+
+enum MicCommand {
+    /// Start capturing; replies with the sample rate once the stream runs
+    Play(mpsc::Sender<Result<u32, String>>),
+    Pause,
+}
+
+/// Default input device of the default cpal host.
+///
+/// cpal streams are not Send on every host, so one thread owns the stream
+/// and recording can be started from the hotkey or the control socket alike.
+pub struct CpalSource {
+    commands: mpsc::Sender<MicCommand>,
+    /// Where the stream callback delivers samples; None while paused
+    sink: Arc<Mutex<Option<AudioSink>>>,
+}
+
+impl CpalSource {
+    pub fn new() -> Self {
+        let (commands, rx) = mpsc::channel();
+        let sink: Arc<Mutex<Option<AudioSink>>> = Arc::new(Mutex::new(None));
+        let callback_sink = Arc::clone(&sink);
+        thread::spawn(move || {
+            let mut stream: Option<(Stream, u32)> = None;
+            for cmd in rx {
+                match cmd {
+                    MicCommand::Play(reply) => {
+                        if stream.is_none() {
+                            stream = init_audio_stream(Arc::clone(&callback_sink));
+                        }
+                        let result = match stream {
+                            Some((ref s, rate)) => s.play().map(|_| rate).map_err(|e| e.to_string()),
+                            None => Err("no usable input device".to_string()),
+                        };
+                        let _ = reply.send(result);
+                    }
+                    MicCommand::Pause => {
+                        if let Some((ref s, _)) = stream {
+                            let _ = s.pause();
+                        }
+                    }
+                }
+            }
+        });
+        CpalSource { commands, sink }
+    }
+}
+
+impl Default for CpalSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioSource for CpalSource {
+    /// Blocks until the stream is running so the sample rate is known
+    fn start(&self, sink: AudioSink) -> Result<u32, String> {
+        *self.sink.lock().unwrap() = Some(sink);
+        let (reply_tx, reply_rx) = mpsc::channel();
+        self.commands.send(MicCommand::Play(reply_tx)).map_err(|_| "audio thread exited".to_string())?;
+        reply_rx.recv().map_err(|_| "audio thread exited".to_string())?
+    }
+
+    fn stop(&self) {
+        let _ = self.commands.send(MicCommand::Pause);
+        self.sink.lock().unwrap().take();
+    }
+}
+
+fn init_audio_stream(sink: Arc<Mutex<Option<AudioSink>>>) -> Option<(Stream, u32)> {
+    let host = cpal::default_host();
+    let device = host.default_input_device()?;
+    let supported_config = device.default_input_config().ok()?;
+    let actual_sample_rate = supported_config.sample_rate().0;
+
+    let config = cpal::StreamConfig {
+        channels: 1,
+        sample_rate: supported_config.sample_rate(),
+        buffer_size: cpal::BufferSize::Default,
+    };
+
+    let stream = device
+        .build_input_stream(
+            &config,
+            move |data: &[f32], _: &cpal::InputCallbackInfo| {
+                if let Some(ref sink) = *sink.lock().unwrap() {
+                    sink.push(data);
+                }
+            },
+            |err| {
+                log_error(&format!("Audio error: {}", err));
+            },
+            None,
+        )
+        .ok()?;
+    Some((stream, actual_sample_rate))
+}
@@ -0,0 +1,176 @@
+//! OpenAI-compatible batch transcription (`POST {base_url}/audio/transcriptions`).
+//! The same request shape is served by OpenAI, Groq, speaches/faster-whisper
+//! and whisper.cpp's server, so one client covers them all.
+
+use std::time::Duration;
+
+use crate::audio::encode_wav;
+use crate::config::{read_config_file, Config};
+use crate::transcriber::{TranscribeError, Transcriber, Transcript};
+
+pub const OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+
+pub struct OpenAiTranscriber {
+    name: String,
+    base_url: String,
+    model: String,
+    key: Option<String>,
+    /// Header carrying the raw key; None for `Authorization: Bearer <key>`
+    auth_header: Option<String>,
+    /// Extra multipart form fields, e.g. ("language", "en")
+    fields: Vec<(String, String)>,
+    /// Sent as the Whisper `prompt`
+    keywords: Vec<String>,
+}
+
+impl OpenAiTranscriber {
+    /// Unauthenticated client for `base_url` (up to and including `/v1`).
+    pub fn new(name: impl Into<String>, base_url: impl Into<String>, model: impl Into<String>) -> Self {
+        OpenAiTranscriber {
+            name: name.into(),
+            base_url: base_url.into(),
+            model: model.into(),
+            key: None,
+            auth_header: None,
+            fields: Vec::new(),
+            keywords: Vec::new(),
+        }
+    }
+
+    /// Sent as `Authorization: Bearer <key>` unless [`Self::with_auth_header`]
+    pub fn with_key(mut self, key: impl Into<String>) -> Self {
+        self.key = Some(key.into());
+        self
+    }
+
+    /// Send the key as-is in `header` (e.g. `X-Api-Key`)
+    pub fn with_auth_header(mut self, header: impl Into<String>) -> Self {
+        self.auth_header = Some(header.into());
+        self
+    }
+
+    /// Extra form field; replaces an earlier one with the same name.
+    pub fn with_field(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        let name = name.into();
+        self.fields.retain(|(n, _)| *n != name);
+        self.fields.push((name, value.into()));
+        self
+    }
+
+    pub fn with_keywords(mut self, keywords: Vec<String>) -> Self {
+        self.keywords = keywords;
+        self
+    }
+
+    /// Apply `providers` file options:
+    ///   name=Label  base_url=URL  model=NAME
+    ///   key=FILE          API key read from ~/.config/fnkey/FILE
+    ///   auth_header=NAME  send the key raw in this header instead of as a bearer token
+    ///   field.NAME=VALUE  extra form field
+    pub fn configure(mut self, options: &[(String, String)]) -> Result<Self, String> {
+        for (option, value) in options {
+            match option.as_str() {
+                "name" => self.name = value.clone(),
+                "base_url" => self.base_url = value.clone(),
+                "model" => self.model = value.clone(),
+                "key" => self.key = Some(read_config_file(value).ok_or_else(|| format!("key file '{}' is missing or empty", value))?),
+                "auth_header" => self.auth_header = Some(value.clone()),
+                _ => match option.strip_prefix("field.") {
+                    Some(field) if !field.is_empty() => self = self.with_field(field, value.clone()),
+                    _ => return Err(format!("unknown option '{}'", option)),
+                },
+            }
+        }
+        if !self.base_url.starts_with("http://") && !self.base_url.starts_with("https://") {
+            return Err(format!("base_url must be http(s), got '{}'", self.base_url));
+        }
+        if self.auth_header.is_some() && self.key.is_none() {
+            return Err("auth_header needs a key".to_string());
+        }
+        Ok(self)
+    }
+
+    /// `json` unless a `response_format` field says otherwise
+    fn response_format(&self) -> &str {
+        self.fields.iter().find(|(name, _)| name == "response_format").map_or("json", |(_, value)| value.as_str())
+    }
+
+    pub fn endpoint(&self) -> String {
+        format!("{}/audio/transcriptions", self.base_url.trim_end_matches('/'))
+    }
+}
+
+/// `openai` in the providers file: OpenAI unless `base_url` points elsewhere.
+/// The key comes from `openai_key` or OPENAI_API_KEY; local servers need none.
+pub fn openai_from_config(config: &Config, options: &[(String, String)]) -> Result<OpenAiTranscriber, String> {
+    let mut transcriber = OpenAiTranscriber::new("OpenAI", OPENAI_BASE_URL, "whisper-1").with_keywords(config.keywords.clone());
+    if let Some(key) = read_config_file("openai_key").or_else(|| std::env::var("OPENAI_API_KEY").ok()) {
+        transcriber = transcriber.with_key(key);
+    }
+    let transcriber = transcriber.configure(options)?;
+    if transcriber.key.is_none() && transcriber.base_url == OPENAI_BASE_URL {
+        return Err("no API key (openai_key)".to_string());
+    }
+    Ok(transcriber)
+}
+
+impl Transcriber for OpenAiTranscriber {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn transcribe(&self, audio: &[f32], sample_rate: u32) -> Result<Transcript, TranscribeError> {
+        let wav_data = encode_wav(audio, sample_rate).map_err(|e| TranscribeError::Failed(e.to_string()))?;
+        let mut form = reqwest::blocking::multipart::Form::new()
+            .text("model", self.model.clone())
+            .part(
+                "file",
+                reqwest::blocking::multipart::Part::bytes(wav_data)
+                    .file_name("audio.wav")
+                    .mime_str("audio/wav")
+                    .unwrap(),
+            );
+        if !self.keywords.is_empty() {
+            form = form.text("prompt", self.keywords.join(", "));
+        }
+        if !self.fields.iter().any(|(name, _)| name == "response_format") {
+            form = form.text("response_format", "json");
+        }
+        for (name, value) in &self.fields {
+            form = form.text(name.clone(), value.clone());
+        }
+        let mut request = reqwest::blocking::Client::new()
+            .post(self.endpoint())
+            .multipart(form)
+            .timeout(Duration::from_secs(30));
+        if let Some(ref key) = self.key {
+            request = match self.auth_header {
+                Some(ref header) => request.header(header.as_str(), key.as_str()),
+                None => request.bearer_auth(key),
+            };
+        }
+        let response = request.send().map_err(|e| TranscribeError::Failed(format!("{} request failed: {}", self.name, e)))?;
+        let status = response.status();
+        let body = response.text().map_err(|e| TranscribeError::Failed(e.to_string()))?;
+        if !status.is_success() {
+            return Err(TranscribeError::Failed(format!("{} returned HTTP {}: {}", self.name, status.as_u16(), body.trim())));
+        }
+        let text = parse_response(self.response_format(), &body).map_err(|e| TranscribeError::Failed(format!("{}: {}", self.name, e)))?;
+        if text.is_empty() {
+            return Err(TranscribeError::Empty);
+        }
+        Ok(Transcript::new(&self.name, text))
+    }
+}
+
+/// `{"text": …}` for json/verbose_json; the raw body for text/srt/vtt.
+/// Going by the body instead would read a transcript like "42" as JSON.
+fn parse_response(response_format: &str, body: &str) -> Result<String, String> {
+    match response_format {
+        "json" | "verbose_json" => {
+            let json: serde_json::Value = serde_json::from_str(body).map_err(|e| format!("bad JSON response: {}", e))?;
+            Ok(json["text"].as_str().unwrap_or_default().trim().to_string())
+        }
+        _ => Ok(body.trim().to_string()),
+    }
+}
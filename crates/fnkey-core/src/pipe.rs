@@ -0,0 +1,131 @@
+//! Pipe mode: raw PCM in, JSON-line transcripts out (`fnkey stream`).
+//!
+//! Output lines:
+//!   {"type":"interim","transcript":"…","start":0.0,"duration":1.2}
+//!   {"type":"final","transcript":"…","start":0.0,"duration":2.4}
+//!   {"type":"done","transcript":"<all finals joined>"}
+//!   {"type":"error","message":"…"}
+
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use serde_json::json;
+
+use crate::audio::{to_pcm16, PcmFormat};
+use crate::config::Config;
+use crate::deepgram::{spawn_deepgram_thread, DgResult, WsCommand, DEEPGRAM_SAMPLE_RATE};
+use crate::transcriber::Segment;
+
+/// How long to wait for Deepgram's last results after input ends
+const FINALIZE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Copy, Debug)]
+pub struct PipeOptions {
+    pub sample_rate: u32,
+    pub format: PcmFormat,
+    pub channels: usize,
+}
+
+/// Stream `input` to Deepgram until EOF, writing a JSON line to `output` for
+/// every interim/final result, then a `done` or `error` line. Returns the
+/// full transcript.
+pub fn run_pipe(config: &Config, opts: PipeOptions, mut input: impl Read, output: impl Write + Send) -> Result<String, String> {
+    let key = config.deepgram_auth().ok_or("stream mode needs a Deepgram key")?;
+    if opts.sample_rate == 0 || opts.channels == 0 {
+        return Err("sample rate and channels must be non-zero".to_string());
+    }
+
+    let (tx, rx) = mpsc::channel();
+    let (result_tx, result_rx) = mpsc::channel();
+    let (update_tx, update_rx) = mpsc::channel();
+    spawn_deepgram_thread(key, rx, config.keywords.clone(), config.deepgram.clone(), result_tx, Some(update_tx), None);
+
+    // Set once the final result is in (or never coming), so the printer
+    // stops even if the Deepgram thread is still winding down
+    let finished = AtomicBool::new(false);
+    thread::scope(|scope| {
+        let printer = scope.spawn(|| print_updates(update_rx, output, &finished));
+
+        // ~100ms reads; carry partial frames over to the next read
+        let frame_bytes = opts.format.bytes_per_sample() * opts.channels;
+        let mut buf = vec![0u8; frame_bytes * (opts.sample_rate as usize / 10).max(1)];
+        let mut pending: Vec<u8> = Vec::new();
+        let mut early: Option<DgResult> = None;
+        loop {
+            let n = match input.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    early = Some(DgResult::Err(format!("reading input: {}", e)));
+                    break;
+                }
+            };
+            pending.extend_from_slice(&buf[..n]);
+            let whole = pending.len() - pending.len() % frame_bytes;
+            if whole == 0 {
+                continue;
+            }
+            let samples = opts.format.to_mono(&pending[..whole], opts.channels);
+            pending.drain(..whole);
+            let bytes = to_pcm16(&samples, opts.sample_rate, DEEPGRAM_SAMPLE_RATE);
+            // The thread only answers early when the connection failed
+            if let Ok(result) = result_rx.try_recv() {
+                early = Some(result);
+                break;
+            }
+            if tx.send(WsCommand::Audio(bytes)).is_err() {
+                break;
+            }
+        }
+        let _ = tx.send(WsCommand::Stop);
+
+        let result = match early {
+            Some(result) => result,
+            None => result_rx
+                .recv_timeout(FINALIZE_TIMEOUT)
+                .unwrap_or_else(|_| DgResult::Err("timeout waiting for result".to_string())),
+        };
+        drop(tx);
+        finished.store(true, Ordering::SeqCst);
+        let output = printer.join().expect("printer thread panicked");
+        let (line, outcome) = match result {
+            DgResult::Ok(transcript) => (
+                json!({"type": "done", "transcript": transcript.text, "words": transcript.words}),
+                Ok(transcript.text),
+            ),
+            DgResult::Err(e) => (json!({"type": "error", "message": e}), Err(e)),
+        };
+        if let Some(mut out) = output {
+            let _ = writeln!(out, "{}", line).and_then(|_| out.flush());
+        }
+        outcome
+    })
+}
+
+/// Write each update as it arrives until the Deepgram thread hangs up or
+/// `finished` is set and nothing is queued. Returns the writer, or None if
+/// the reader went away (e.g. `| head`).
+fn print_updates<W: Write>(updates: mpsc::Receiver<Segment>, mut out: W, finished: &AtomicBool) -> Option<W> {
+    loop {
+        let update = match updates.recv_timeout(Duration::from_millis(50)) {
+            Ok(update) => update,
+            Err(mpsc::RecvTimeoutError::Timeout) if !finished.load(Ordering::SeqCst) => continue,
+            Err(_) => break,
+        };
+        let kind = if update.is_final { "final" } else { "interim" };
+        let line = json!({
+            "type": kind,
+            "transcript": update.transcript,
+            "start": update.start,
+            "duration": update.duration,
+        });
+        if writeln!(out, "{}", line).and_then(|_| out.flush()).is_err() {
+            return None;
+        }
+    }
+    Some(out)
+}
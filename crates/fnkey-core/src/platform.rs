@@ -0,0 +1,77 @@
+//! Host OS integration behind the dictation pipeline.
+
+use std::sync::Arc;
+
+/// Callback invoked with the hotkey state (true = held) on every change
+/// the platform observes. Repeated values are fine; edges are detected in core.
+pub type HotkeyCallback = Arc<dyn Fn(bool) + Send + Sync>;
+
+/// Run when the user clicks a notification's action button
+pub type NotificationAction = Box<dyn FnOnce() + Send>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Urgency {
+    /// Something degraded but the text still arrived (e.g. backend fallback)
+    Info,
+    /// Nothing was pasted
+    Critical,
+}
+
+/// A short user-visible message with an optional action button.
+pub struct Notification {
+    pub message: String,
+    pub urgency: Urgency,
+    /// Button label and what it does, e.g. ("Retry", ...)
+    pub action: Option<(String, NotificationAction)>,
+}
+
+impl Notification {
+    pub fn info(message: impl Into<String>) -> Self {
+        Notification { message: message.into(), urgency: Urgency::Info, action: None }
+    }
+
+    pub fn critical(message: impl Into<String>) -> Self {
+        Notification { message: message.into(), urgency: Urgency::Critical, action: None }
+    }
+
+    pub fn with_action(mut self, label: impl Into<String>, action: impl FnOnce() + Send + 'static) -> Self {
+        self.action = Some((label.into(), Box::new(action)));
+        self
+    }
+}
+
+/// Everything the pipeline needs from the host: hotkey events, the status
+/// indicator, notifications, clipboard/paste and keyboard layout.
+///
+/// Methods other than [`Platform::run`] may be called from background threads.
+pub trait Platform: Send + Sync {
+    /// Watch the hotkey and report its state to `on_key`. Blocks for the
+    /// lifetime of the app (typically runs the native event loop).
+    fn run(&self, on_key: HotkeyCallback);
+
+    /// Switch the status indicator between idle (○) and recording (●).
+    fn set_recording(&self, recording: bool);
+
+    /// Show a short user-visible message, e.g. a backend fallback. Platforms
+    /// without action buttons just drop the action.
+    fn show_notification(&self, notification: Notification);
+
+    /// Put text on the system clipboard. Returns false if that failed.
+    fn set_clipboard(&self, text: &str) -> bool;
+
+    /// Send the paste shortcut to the focused application.
+    fn paste(&self);
+
+    /// Send a Return keypress to the focused application.
+    fn press_return(&self);
+
+    /// The hotkey is a modifier (Alt, Ctrl, ...), so a paste sent while it
+    /// is held reaches the application with that modifier added.
+    fn hotkey_is_modifier(&self) -> bool {
+        false
+    }
+
+    /// Virtual keycode that types `ch` on the current keyboard layout, so
+    /// shortcuts still work on non-Latin layouts. None if it can't be found.
+    fn keycode_for_char(&self, ch: char) -> Option<u16>;
+}
@@ -0,0 +1,137 @@
+//! Sleep until a socket is readable/writable or another thread wakes us.
+//! poll(2) on unix; elsewhere a short sleep stands in for readiness.
+
+use std::io;
+use std::net::TcpStream;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// What woke [`wait`]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Ready {
+    pub readable: bool,
+    pub writable: bool,
+    /// [`Waker::wake`] was called
+    pub woken: bool,
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::io::{self, Read, Write};
+    use std::net::TcpStream;
+    use std::os::fd::AsRawFd;
+    use std::os::unix::net::UnixStream;
+    use std::time::Duration;
+
+    use super::Ready;
+
+    /// Wakes the thread waiting on the matching [`Wakeups`]
+    pub struct Waker(UnixStream);
+
+    pub struct Wakeups {
+        rx: UnixStream,
+        /// Keeps the pair open once every waker is gone; a hang-up would
+        /// otherwise make every wait return at once
+        _tx: UnixStream,
+    }
+
+    pub fn channel() -> io::Result<(Waker, Wakeups)> {
+        let (tx, rx) = UnixStream::pair()?;
+        tx.set_nonblocking(true)?;
+        rx.set_nonblocking(true)?;
+        Ok((Waker(tx.try_clone()?), Wakeups { rx, _tx: tx }))
+    }
+
+    impl Waker {
+        pub fn wake(&self) {
+            // A full pipe already means a wake-up is pending
+            let _ = (&self.0).write(&[1]);
+        }
+    }
+
+    impl Wakeups {
+        fn drain(&self) {
+            let mut buf = [0u8; 64];
+            while matches!((&self.rx).read(&mut buf), Ok(n) if n > 0) {}
+        }
+    }
+
+    pub fn wait(socket: &TcpStream, wakeups: &Wakeups, want_write: bool, timeout: Option<Duration>) -> io::Result<Ready> {
+        let events = libc::POLLIN | if want_write { libc::POLLOUT } else { 0 };
+        let mut fds = [
+            libc::pollfd { fd: socket.as_raw_fd(), events, revents: 0 },
+            libc::pollfd { fd: wakeups.rx.as_raw_fd(), events: libc::POLLIN, revents: 0 },
+        ];
+        // Round up so a sub-millisecond remainder doesn't spin
+        let ms = timeout.map_or(-1, |t| t.as_micros().div_ceil(1000).min(i32::MAX as u128) as libc::c_int);
+        if unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, ms) } < 0 {
+            let e = io::Error::last_os_error();
+            return if e.kind() == io::ErrorKind::Interrupted { Ok(Ready::default()) } else { Err(e) };
+        }
+        let woken = fds[1].revents != 0;
+        if woken {
+            wakeups.drain();
+        }
+        // Errors and hang-ups count as both, so the next read/write reports them
+        let failed = fds[0].revents & (libc::POLLERR | libc::POLLHUP) != 0;
+        Ok(Ready {
+            readable: failed || fds[0].revents & libc::POLLIN != 0,
+            writable: failed || fds[0].revents & libc::POLLOUT != 0,
+            woken,
+        })
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use std::io;
+    use std::net::TcpStream;
+    use std::time::Duration;
+
+    use super::Ready;
+
+    pub struct Waker;
+
+    pub struct Wakeups;
+
+    pub fn channel() -> io::Result<(Waker, Wakeups)> {
+        Ok((Waker, Wakeups))
+    }
+
+    impl Waker {
+        pub fn wake(&self) {}
+    }
+
+    pub fn wait(_socket: &TcpStream, _wakeups: &Wakeups, _want_write: bool, timeout: Option<Duration>) -> io::Result<Ready> {
+        let nap = Duration::from_millis(5);
+        std::thread::sleep(timeout.map_or(nap, |t| t.min(nap)));
+        Ok(Ready { readable: true, writable: true, woken: true })
+    }
+}
+
+pub use imp::{channel, Waker, Wakeups};
+
+/// Block until `socket` is readable (or writable, if `want_write`), the
+/// waker fires, or `timeout` passes. An interrupted wait returns nothing ready.
+pub fn wait(socket: &TcpStream, wakeups: &Wakeups, want_write: bool, timeout: Option<Duration>) -> io::Result<Ready> {
+    imp::wait(socket, wakeups, want_write, timeout)
+}
+
+/// Hand messages on to the thread waiting on `waker`'s [`Wakeups`], waking
+/// it for each one so it can sleep on its socket instead of polling the
+/// channel. Hanging up wakes it too.
+pub fn relay<T: Send + 'static>(rx: mpsc::Receiver<T>, waker: Waker) -> mpsc::Receiver<T> {
+    let (tx, relayed) = mpsc::channel();
+    thread::spawn(move || {
+        for message in rx {
+            if tx.send(message).is_err() {
+                return;
+            }
+            waker.wake();
+        }
+        drop(tx);
+        waker.wake();
+    });
+    relayed
+}
@@ -0,0 +1,168 @@
+//! Bounded, paced queue of audio frames for a non-blocking WebSocket.
+//! A frame that hits WouldBlock stays buffered in the socket and nothing
+//! more is written until it drains; audio is only dropped when the queue
+//! overflows, and every drop is counted.
+
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read, Write};
+
+use tungstenite::protocol::Message;
+use tungstenite::WebSocket;
+
+/// What happened to the audio handed to a [`SendQueue`]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SendStats {
+    pub frames_sent: u32,
+    pub bytes_sent: usize,
+    /// Times the socket pushed back and sending paused until it drained
+    pub stalls: u32,
+    pub dropped_frames: u32,
+    pub dropped_bytes: usize,
+    /// Most audio waiting at once
+    pub peak_queued_bytes: usize,
+}
+
+impl SendStats {
+    /// For log lines, e.g. "42 chunks/65KB sent, 3 stalls, 2 chunks (100ms) dropped"
+    pub fn summary(&self, bytes_per_ms: usize) -> String {
+        let mut summary = format!("{} chunks/{}KB sent", self.frames_sent, self.bytes_sent / 1024);
+        if self.stalls > 0 {
+            summary.push_str(&format!(", {} stalls", self.stalls));
+        }
+        if self.dropped_frames > 0 {
+            summary.push_str(&format!(
+                ", {} chunks ({}ms) dropped",
+                self.dropped_frames,
+                self.dropped_bytes / bytes_per_ms.max(1)
+            ));
+        }
+        summary
+    }
+}
+
+pub struct SendQueue {
+    frames: VecDeque<Vec<u8>>,
+    /// Audio not yet making up a whole chunk
+    partial: Vec<u8>,
+    queued: usize,
+    chunk_bytes: usize,
+    max_bytes: usize,
+    /// The last frame is still in the socket's buffer
+    blocked: bool,
+    pub stats: SendStats,
+}
+
+impl SendQueue {
+    /// Re-chunk audio into frames of `chunk_bytes`, holding at most
+    /// `max_bytes` before the oldest frames are dropped.
+    pub fn new(chunk_bytes: usize, max_bytes: usize) -> Self {
+        SendQueue {
+            frames: VecDeque::new(),
+            partial: Vec::new(),
+            queued: 0,
+            chunk_bytes: chunk_bytes.max(1),
+            max_bytes: max_bytes.max(chunk_bytes),
+            blocked: false,
+            stats: SendStats::default(),
+        }
+    }
+
+    pub fn push(&mut self, pcm: &[u8]) {
+        self.partial.extend_from_slice(pcm);
+        while self.partial.len() >= self.chunk_bytes {
+            let rest = self.partial.split_off(self.chunk_bytes);
+            let frame = std::mem::replace(&mut self.partial, rest);
+            self.enqueue(frame);
+        }
+    }
+
+    /// Queue the trailing partial chunk, at the end of the audio
+    pub fn flush_partial(&mut self) {
+        if !self.partial.is_empty() {
+            let frame = std::mem::take(&mut self.partial);
+            self.enqueue(frame);
+        }
+    }
+
+    fn enqueue(&mut self, frame: Vec<u8>) {
+        self.queued += frame.len();
+        self.frames.push_back(frame);
+        while self.queued > self.max_bytes {
+            let Some(oldest) = self.frames.pop_front() else { break };
+            self.queued -= oldest.len();
+            self.stats.dropped_frames += 1;
+            self.stats.dropped_bytes += oldest.len();
+        }
+        self.stats.peak_queued_bytes = self.stats.peak_queued_bytes.max(self.queued);
+    }
+
+    /// Nothing queued and nothing left in the socket's buffer
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty() && self.partial.is_empty() && !self.blocked
+    }
+
+    /// Whole frames are waiting, or the socket's buffer still has to drain;
+    /// the partial chunk doesn't count until [`flush_partial`](Self::flush_partial)
+    pub fn wants_write(&self) -> bool {
+        self.blocked || !self.frames.is_empty()
+    }
+
+    pub fn queued_bytes(&self) -> usize {
+        self.queued + self.partial.len()
+    }
+
+    /// Count everything still queued as dropped (the socket is going away)
+    pub fn drop_all(&mut self) {
+        self.flush_partial();
+        for frame in self.frames.drain(..) {
+            self.stats.dropped_frames += 1;
+            self.stats.dropped_bytes += frame.len();
+        }
+        self.queued = 0;
+    }
+
+    /// Forget everything queued without counting it as dropped: the caller
+    /// will send the same audio again on a new socket
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.partial.clear();
+        self.queued = 0;
+        self.blocked = false;
+    }
+
+    /// Write queued frames until the socket pushes back. Errors other than
+    /// WouldBlock / TimedOut mean the connection is gone.
+    pub fn pump<S: Read + Write>(&mut self, ws: &mut WebSocket<S>) -> Result<(), String> {
+        if self.blocked {
+            match ws.flush() {
+                Ok(()) => self.blocked = false,
+                Err(e) if would_block(&e) => return Ok(()),
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+        while let Some(frame) = self.frames.pop_front() {
+            let len = frame.len();
+            self.queued -= len;
+            // On WouldBlock the frame is already in tungstenite's buffer
+            let result = ws.send(Message::Binary(frame));
+            match result {
+                Ok(()) => {}
+                Err(ref e) if would_block(e) => {
+                    self.blocked = true;
+                    self.stats.stalls += 1;
+                }
+                Err(e) => return Err(e.to_string()),
+            }
+            self.stats.frames_sent += 1;
+            self.stats.bytes_sent += len;
+            if self.blocked {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn would_block(e: &tungstenite::Error) -> bool {
+    matches!(e, tungstenite::Error::Io(io) if matches!(io.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut))
+}
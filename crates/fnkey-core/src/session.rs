@@ -0,0 +1,555 @@
+//! Recording lifecycle — all non-blocking from the hotkey thread's perspective
+
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::config::{config_path, log_error, Config};
+use crate::platform::{Notification, Platform};
+use crate::source::{AudioSink, AudioSource};
+use crate::chain::{Chain, ChainFailure, Streamed};
+use crate::transcriber::{Segment, StreamSession, TranscribeError, Transcript};
+
+/// Pause after a paste so the target app has taken it before the next keystroke
+const PASTE_SETTLE: Duration = Duration::from_millis(50);
+
+/// The live stream's result, still on its way
+struct PendingStream {
+    /// Position in the chain of the link the stream started on
+    index: usize,
+    result: mpsc::Receiver<Streamed>,
+    /// What progressive output has typed so far, when it's on
+    typed: Option<Arc<Mutex<Typed>>>,
+}
+
+/// Progress of a session, for control socket subscribers
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum SessionEvent {
+    RecordingStarted,
+    RecordingStopped,
+    /// Recording was thrown away; nothing will be pasted
+    Cancelled,
+    /// Running text from the streaming provider (finals plus the current partial)
+    Interim { text: String },
+    /// Transcript that is being pasted
+    Final { text: String },
+    Error { message: String },
+}
+
+pub struct AppState {
+    pub platform: Arc<dyn Platform>,
+    /// Microphone or replayed file; None records silence
+    audio_source: Option<Arc<dyn AudioSource>>,
+    /// Captured samples not yet forwarded to the streaming provider
+    pub audio_buffer: Arc<Mutex<Vec<f32>>>,
+    /// Shadow buffer: keeps all audio for batch fallback if streaming fails
+    pub shadow_buffer: Arc<Mutex<Vec<f32>>>,
+    /// Sample rate of the captured audio
+    pub sample_rate: AtomicU32,
+    /// Tried in order on release; the first link gets live audio if it streams
+    chain: Chain,
+    auto_return: AtomicBool,
+    /// Type finalized segments while the key is held
+    progressive: bool,
+    /// Hotkey state seen on the previous event, for edge detection
+    was_pressed: AtomicBool,
+    /// Tells the active stream's forwarder thread to finish or cancel
+    stream_end: Mutex<Option<mpsc::Sender<Forward>>>,
+    stream_result: Mutex<Option<PendingStream>>,
+    /// Whether a session is capturing, however it was started
+    recording: AtomicBool,
+    /// Sessions stopped but not yet transcribed
+    transcribing: AtomicUsize,
+    last_transcript: Mutex<Option<String>>,
+    subscribers: Mutex<Vec<mpsc::Sender<SessionEvent>>>,
+}
+
+impl AppState {
+    /// Session recording from the default microphone (with the `cpal`
+    /// feature) and transcribing with the chain from `config`.
+    pub fn new(platform: Arc<dyn Platform>, config: Config) -> Arc<Self> {
+        #[cfg(feature = "cpal")]
+        let source: Option<Arc<dyn AudioSource>> = Some(Arc::new(crate::mic::CpalSource::new()));
+        #[cfg(not(feature = "cpal"))]
+        let source: Option<Arc<dyn AudioSource>> = None;
+        let chain = Chain::from_config(&config);
+        Self::with_parts(platform, config, source, chain)
+    }
+
+    /// Session recording from `source`, e.g. a [`crate::source::ReplaySource`].
+    pub fn with_audio_source(platform: Arc<dyn Platform>, config: Config, source: Arc<dyn AudioSource>) -> Arc<Self> {
+        let chain = Chain::from_config(&config);
+        Self::with_parts(platform, config, Some(source), chain)
+    }
+
+    /// Session with an explicit audio source and provider chain; `config` only
+    /// supplies preferences.
+    pub fn with_parts(
+        platform: Arc<dyn Platform>,
+        config: Config,
+        audio_source: Option<Arc<dyn AudioSource>>,
+        chain: Chain,
+    ) -> Arc<Self> {
+        Arc::new(AppState {
+            platform,
+            audio_source,
+            audio_buffer: Arc::new(Mutex::new(Vec::new())),
+            shadow_buffer: Arc::new(Mutex::new(Vec::new())),
+            sample_rate: AtomicU32::new(48000),
+            chain,
+            auto_return: AtomicBool::new(config.auto_return),
+            progressive: config.progressive,
+            was_pressed: AtomicBool::new(false),
+            stream_end: Mutex::new(None),
+            stream_result: Mutex::new(None),
+            recording: AtomicBool::new(false),
+            transcribing: AtomicUsize::new(0),
+            last_transcript: Mutex::new(None),
+            subscribers: Mutex::new(Vec::new()),
+        })
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.load(Ordering::SeqCst)
+    }
+
+    pub fn is_transcribing(&self) -> bool {
+        self.transcribing.load(Ordering::SeqCst) > 0
+    }
+
+    /// Text of the most recent successful transcription
+    pub fn last_transcript(&self) -> Option<String> {
+        self.last_transcript.lock().unwrap().clone()
+    }
+
+    /// Receive every [`SessionEvent`] from now on; drop the receiver to stop.
+    pub fn subscribe(&self) -> mpsc::Receiver<SessionEvent> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    fn emit(&self, event: SessionEvent) {
+        self.subscribers.lock().unwrap().retain(|tx| tx.send(event.clone()).is_ok());
+    }
+
+    /// Show a notification and report it to subscribers as an error
+    fn notify(&self, notification: Notification) {
+        self.emit(SessionEvent::Error { message: notification.message.clone() });
+        self.platform.show_notification(notification);
+    }
+
+    pub fn auto_return(&self) -> bool {
+        self.auto_return.load(Ordering::SeqCst)
+    }
+
+    /// Toggle Return-after-paste and persist it to the `auto_return` config file
+    pub fn set_auto_return(&self, enabled: bool) {
+        self.auto_return.store(enabled, Ordering::SeqCst);
+        if let Some(path) = config_path("auto_return") {
+            if enabled {
+                let _ = std::fs::write(&path, "1");
+            } else {
+                let _ = std::fs::remove_file(&path);
+            }
+        }
+    }
+}
+
+/// Start the control socket, then hand the hotkey over to the platform and
+/// block in its event loop.
+pub fn run(state: &Arc<AppState>) {
+    #[cfg(unix)]
+    match crate::control::spawn_server(state) {
+        Ok(path) => eprintln!("[fnkey] control socket at {}", path.display()),
+        Err(e) => log_error(&format!("Control socket unavailable: {}", e)),
+    }
+    let for_callback = Arc::clone(state);
+    state.platform.run(Arc::new(move |pressed| handle_hotkey(&for_callback, pressed)));
+}
+
+/// Feed the current hotkey state; starts on press, stops on release.
+pub fn handle_hotkey(state: &Arc<AppState>, key_pressed: bool) {
+    let prev_pressed = state.was_pressed.swap(key_pressed, Ordering::SeqCst);
+    if key_pressed && !prev_pressed {
+        start_recording(state);
+    } else if !key_pressed && prev_pressed {
+        stop_recording(state);
+    }
+}
+
+/// Called from the hotkey thread — must be non-blocking. No-op if a
+/// session is already recording.
+pub fn start_recording(state: &Arc<AppState>) {
+    if state.recording.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    // Clear buffers
+    {
+        let mut buffer = state.audio_buffer.lock().unwrap();
+        buffer.clear();
+    }
+    {
+        let mut shadow = state.shadow_buffer.lock().unwrap();
+        shadow.clear();
+    }
+
+    // The forwarder sleeps until the sink reports audio or the session ends;
+    // without a stream the receiver is dropped and the notices go nowhere
+    let (forward_tx, forward_rx) = mpsc::channel();
+    if let Some(ref source) = state.audio_source {
+        let notify = forward_tx.clone();
+        let sink = AudioSink::new(Arc::clone(&state.audio_buffer), Arc::clone(&state.shadow_buffer))
+            .with_notify(move || {
+                let _ = notify.send(Forward::Audio);
+            });
+        match source.start(sink) {
+            Ok(rate) => state.sample_rate.store(rate, Ordering::SeqCst),
+            Err(e) => log_error(&format!("Audio source failed: {}", e)),
+        }
+    }
+
+    state.platform.set_recording(true);
+    state.emit(SessionEvent::RecordingStarted);
+
+    // Open a stream if the first provider has one (connects in the background)
+    let sample_rate = state.sample_rate.load(Ordering::SeqCst);
+    let (segment_tx, segment_rx) = mpsc::channel();
+    let stream = state.chain.start_stream(sample_rate, segment_tx.clone());
+    if let Some(live) = stream {
+        // A paste under a held modifier hotkey would turn Ctrl+V into e.g.
+        // Ctrl+Alt+V, so those sessions wait for release like any other
+        let held_modifier = state.was_pressed.load(Ordering::SeqCst) && state.platform.hotkey_is_modifier();
+        let typed = (state.progressive && !held_modifier).then(|| Arc::new(Mutex::new(Typed::default())));
+        let relay_state = Arc::clone(state);
+        let relay_typed = typed.clone();
+        thread::spawn(move || relay_interim(&relay_state, segment_rx, relay_typed));
+
+        let (result_tx, result_rx) = mpsc::channel();
+        *state.stream_end.lock().unwrap() = Some(forward_tx);
+        let forward_typed = typed.clone();
+        *state.stream_result.lock().unwrap() = Some(PendingStream { index: live.0, result: result_rx, typed });
+        let state = Arc::clone(state);
+        thread::spawn(move || forward_audio(&state, live, segment_tx, forward_typed, forward_rx, result_tx));
+    }
+}
+
+enum Forward {
+    /// The capture buffer has new samples
+    Audio,
+    /// Flush remaining audio and wait for the transcript
+    Finish,
+    Cancel,
+}
+
+/// A stream's link, its session and how long to wait for its result
+type Live = (usize, Box<dyn StreamSession>, Duration);
+
+/// Audio forwarder: drains the capture buffer into the stream whenever the
+/// sink reports audio, until told to finish or cancel. If the stream fails
+/// while the key is held, the audio so far is replayed into a stream on the
+/// next link, when the chain allows.
+fn forward_audio(
+    state: &AppState,
+    mut live: Live,
+    segments: mpsc::Sender<Segment>,
+    typed: Option<Arc<Mutex<Typed>>>,
+    commands: mpsc::Receiver<Forward>,
+    result_tx: mpsc::Sender<Streamed>,
+) {
+    let sample_rate = state.sample_rate.load(Ordering::SeqCst);
+    let mut failures = Vec::new();
+    // Cleared once a failed stream has nowhere to hand its audio
+    let mut can_hand_over = true;
+    loop {
+        let cmd = commands.recv();
+        // One drain can cover several notices; the rest find it empty
+        let chunk: Vec<f32> = state.audio_buffer.lock().unwrap().drain(..).collect();
+        if !chunk.is_empty() {
+            live.1.push_audio(&chunk);
+        }
+        if can_hand_over && matches!(cmd, Ok(Forward::Audio)) && live.1.failed() {
+            match state.chain.hand_over(live.0, sample_rate, segments.clone()) {
+                Some(next) => {
+                    // The next stream starts over from the first word; typing
+                    // its phrases after the failed one's would repeat them
+                    if let Some(ref typed) = typed {
+                        let mut typed = typed.lock().unwrap();
+                        typed.done |= !typed.text.is_empty();
+                    }
+                    failures.push(hand_over(state, &mut live, next, sample_rate));
+                }
+                None => can_hand_over = false,
+            }
+        }
+        match cmd {
+            Ok(Forward::Audio) => {}
+            Ok(Forward::Finish) => {
+                let (index, session, timeout) = live;
+                let _ = result_tx.send(Streamed { index, result: session.finish(timeout), failures });
+                return;
+            }
+            Ok(Forward::Cancel) | Err(_) => {
+                live.1.cancel();
+                return;
+            }
+        }
+    }
+}
+
+/// Swap the failed stream in `live` for `next` and replay everything
+/// captured so far into it. Returns the failure.
+fn hand_over(state: &AppState, live: &mut Live, next: Live, sample_rate: u32) -> ChainFailure {
+    let (index, failed, _) = std::mem::replace(live, next);
+    let links = state.chain.links();
+    let (from, to) = (links[index].transcriber.name(), links[live.0].transcriber.name());
+    let error = failed.finish(Duration::ZERO).err().unwrap_or_else(|| TranscribeError::Failed("stream failed".to_string()));
+    state.notify(Notification::info(format!("{} failed, using {} fallback", from, to)));
+    log_error(&format!("Falling back to {} while recording", to));
+
+    // The shadow buffer is the forwarded audio followed by what's still
+    // waiting, so taking both at once replays each sample exactly once
+    let replay = {
+        let shadow = state.shadow_buffer.lock().unwrap();
+        state.audio_buffer.lock().unwrap().clear();
+        shadow.clone()
+    };
+    // In 1s pieces, so a provider's send queue can pace them
+    for piece in replay.chunks(sample_rate.max(1) as usize) {
+        live.1.push_audio(piece);
+    }
+    ChainFailure { provider: from.to_string(), error }
+}
+
+/// Turn stream segments into running-text events until the stream ends.
+/// With progressive output, each final segment is also typed as it lands.
+fn relay_interim(state: &AppState, updates: mpsc::Receiver<Segment>, typed: Option<Arc<Mutex<Typed>>>) {
+    let mut committed = String::new();
+    for update in updates {
+        if let (true, Some(typed)) = (update.is_final, &typed) {
+            // Held through the paste so release can't type the tail in between
+            let mut typed = typed.lock().unwrap();
+            if let Some(piece) = typed.push(&update.transcript) {
+                if state.platform.set_clipboard(&piece) {
+                    state.platform.paste();
+                    thread::sleep(PASTE_SETTLE);
+                }
+            }
+        }
+        let text = if committed.is_empty() {
+            update.transcript
+        } else {
+            format!("{} {}", committed, update.transcript)
+        };
+        if update.is_final {
+            committed = text.clone();
+        }
+        state.emit(SessionEvent::Interim { text });
+    }
+}
+
+/// Text progressive output has typed into the focused app this session
+#[derive(Default)]
+struct Typed {
+    text: String,
+    /// Nothing more is typed: the session ended, or its stream handed over
+    done: bool,
+}
+
+impl Typed {
+    /// What to type for a newly finalized segment, spaced from the ones before
+    fn push(&mut self, segment: &str) -> Option<String> {
+        let segment = segment.trim();
+        if self.done || segment.is_empty() {
+            return None;
+        }
+        let piece = if self.text.is_empty() { segment.to_string() } else { format!(" {}", segment) };
+        self.text.push_str(&piece);
+        Some(piece)
+    }
+
+    /// The part of `transcript` not typed yet, spaced to follow what was.
+    /// None if it doesn't carry on from the typed words: it came from a
+    /// provider other than the `live` one that typed them, or was reworded.
+    fn finish(&mut self, transcript: &Transcript, live: Option<&str>) -> Option<String> {
+        self.done = true;
+        if self.text.is_empty() {
+            return Some(transcript.text.clone());
+        }
+        let typed: Vec<&str> = self.text.split_whitespace().collect();
+        let words: Vec<&str> = transcript.text.split_whitespace().collect();
+        if live != Some(transcript.provider.as_str()) || !words.starts_with(&typed) {
+            return None;
+        }
+        let rest = words[typed.len()..].join(" ");
+        Some(if rest.is_empty() { rest } else { format!(" {}", rest) })
+    }
+}
+
+/// Counts a stopped session as transcribing until dropped
+struct Transcribing(Arc<AppState>);
+
+impl Transcribing {
+    fn begin(state: &Arc<AppState>) -> Self {
+        state.transcribing.fetch_add(1, Ordering::SeqCst);
+        Transcribing(Arc::clone(state))
+    }
+}
+
+impl Drop for Transcribing {
+    fn drop(&mut self) {
+        self.0.transcribing.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Called from the hotkey thread — must be non-blocking. No-op unless a
+/// session is recording.
+pub fn stop_recording(state: &Arc<AppState>) {
+    if !state.recording.swap(false, Ordering::SeqCst) {
+        return;
+    }
+
+    // Pause audio
+    if let Some(ref source) = state.audio_source {
+        source.stop();
+    }
+
+    state.platform.set_recording(false);
+    state.emit(SessionEvent::RecordingStopped);
+
+    // Keep the whole recording for batch providers
+    let shadow_audio: Vec<f32> = state.shadow_buffer.lock().unwrap().clone();
+    let sample_rate = state.sample_rate.load(Ordering::SeqCst);
+
+    if let Some(end) = state.stream_end.lock().unwrap().take() {
+        let _ = end.send(Forward::Finish);
+    }
+    let streamed = state.stream_result.lock().unwrap().take();
+    if streamed.is_none() && shadow_audio.is_empty() {
+        return;
+    }
+
+    // Wait for the stream / run batch providers in background
+    let state = Arc::clone(state);
+    let busy = Transcribing::begin(&state);
+    thread::spawn(move || {
+        let _busy = busy;
+        let typed = streamed.as_ref().and_then(|pending| pending.typed.clone());
+        transcribe_session(&state, streamed, typed, shadow_audio, sample_rate);
+    });
+}
+
+/// Walk the chain and paste its transcript. The streaming link contributes
+/// its stream's result; the others transcribe the recorded clip. If all fail,
+/// the notification offers a Retry that walks the chain again in batch mode
+/// with the same audio. `typed` is what progressive output already typed.
+fn transcribe_session(
+    state: &Arc<AppState>,
+    streamed: Option<PendingStream>,
+    typed: Option<Arc<Mutex<Typed>>>,
+    audio: Vec<f32>,
+    sample_rate: u32,
+) {
+    let streamed = streamed.map(|pending| {
+        pending.result.recv().unwrap_or_else(|_| Streamed {
+            index: pending.index,
+            result: Err(TranscribeError::Failed("stream ended without a result".to_string())),
+            failures: Vec::new(),
+        })
+    });
+    // The provider whose phrases progressive output typed
+    let live = streamed.as_ref().map(|s| state.chain.links()[s.index].transcriber.name().to_string());
+    let failures = match state.chain.run(streamed, &audio, sample_rate, |from, to| {
+        state.notify(Notification::info(format!("{} failed, using {} fallback", from, to)));
+        log_error(&format!("Falling back to {}", to));
+    }) {
+        Ok(transcript) => {
+            if !transcript.text.is_empty() {
+                paste_and_maybe_return(state, &transcript, typed.as_deref(), live.as_deref());
+            }
+            return;
+        }
+        Err(failures) => failures,
+    };
+
+    // Nothing was said (e.g. a tap of the key): don't nag
+    if failures.is_empty() || audio.is_empty() {
+        return;
+    }
+    let message = match failures.len() {
+        1 => "Transcription failed".to_string(),
+        2 => "Transcription failed (both backends)".to_string(),
+        n => format!("Transcription failed (all {} backends)", n),
+    };
+    let retry_state = Arc::clone(state);
+    let notification = Notification::critical(message).with_action("Retry", move || {
+        let busy = Transcribing::begin(&retry_state);
+        thread::spawn(move || {
+            let _busy = busy;
+            transcribe_session(&retry_state, None, typed, audio, sample_rate);
+        });
+    });
+    state.notify(notification);
+}
+
+/// Stop capturing and throw the audio away; nothing is transcribed or
+/// pasted. No-op unless a session is recording.
+pub fn cancel_recording(state: &Arc<AppState>) {
+    if !state.recording.swap(false, Ordering::SeqCst) {
+        return;
+    }
+
+    if let Some(ref source) = state.audio_source {
+        source.stop();
+    }
+
+    state.platform.set_recording(false);
+
+    if let Some(end) = state.stream_end.lock().unwrap().take() {
+        let _ = end.send(Forward::Cancel);
+    }
+    // Whatever progressive output typed stays; nothing more is added
+    if let Some(typed) = state.stream_result.lock().unwrap().take().and_then(|pending| pending.typed) {
+        typed.lock().unwrap().done = true;
+    }
+    state.audio_buffer.lock().unwrap().clear();
+    state.shadow_buffer.lock().unwrap().clear();
+
+    state.emit(SessionEvent::Cancelled);
+}
+
+/// Paste the transcript, or only its untyped tail when progressive output
+/// typed the rest. If it doesn't follow on from the typed text, nothing is
+/// pasted: the whole transcript goes on the clipboard and the user is told.
+fn paste_and_maybe_return(state: &AppState, transcript: &Transcript, typed: Option<&Mutex<Typed>>, live: Option<&str>) {
+    let text = &transcript.text;
+    *state.last_transcript.lock().unwrap() = Some(text.to_string());
+    state.emit(SessionEvent::Final { text: text.to_string() });
+    let tail = match typed {
+        Some(typed) => typed.lock().unwrap().finish(transcript, live),
+        None => Some(text.to_string()),
+    };
+    let Some(tail) = tail else {
+        log_error(&format!("{} transcript doesn't continue the typed text; not pasting it", transcript.provider));
+        state.platform.set_clipboard(text);
+        state.notify(Notification::info(format!(
+            "{} transcribed this instead of what was typed. The full text is on the clipboard.",
+            transcript.provider
+        )));
+        return;
+    };
+    if !tail.is_empty() {
+        if !state.platform.set_clipboard(&tail) {
+            return;
+        }
+        state.platform.paste();
+    }
+    if state.auto_return() {
+        // Let the paste finish processing before sending Return
+        thread::sleep(PASTE_SETTLE);
+        state.platform.press_return();
+    }
+}
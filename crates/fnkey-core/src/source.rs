@@ -0,0 +1,112 @@
+//! Where session audio comes from: the microphone (cpal) or a recorded file
+//! replayed in real time, so whole sessions can run without a microphone.
+
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::audio::decode_file;
+
+/// Receives captured samples on behalf of a session: everything goes to the
+/// streaming buffer and to the shadow buffer kept for batch fallback.
+#[derive(Clone)]
+pub struct AudioSink {
+    audio_buffer: Arc<Mutex<Vec<f32>>>,
+    shadow_buffer: Arc<Mutex<Vec<f32>>>,
+    /// Called after each push, so the consumer can sleep until audio arrives
+    on_push: Option<Arc<dyn Fn() + Send + Sync>>,
+}
+
+impl AudioSink {
+    pub fn new(audio_buffer: Arc<Mutex<Vec<f32>>>, shadow_buffer: Arc<Mutex<Vec<f32>>>) -> Self {
+        AudioSink { audio_buffer, shadow_buffer, on_push: None }
+    }
+
+    pub fn with_notify(mut self, on_push: impl Fn() + Send + Sync + 'static) -> Self {
+        self.on_push = Some(Arc::new(on_push));
+        self
+    }
+
+    /// Both buffers are locked together (shadow first), so the shadow is
+    /// always the forwarded audio followed by what's still buffered
+    pub fn push(&self, samples: &[f32]) {
+        {
+            let mut shadow = self.shadow_buffer.lock().unwrap();
+            self.audio_buffer.lock().unwrap().extend_from_slice(samples);
+            shadow.extend_from_slice(samples);
+        }
+        if let Some(ref on_push) = self.on_push {
+            on_push();
+        }
+    }
+}
+
+/// Produces mono f32 audio while a session is recording.
+pub trait AudioSource: Send + Sync {
+    /// Begin delivering samples to `sink`. Returns their sample rate.
+    fn start(&self, sink: AudioSink) -> Result<u32, String>;
+
+    /// Stop delivering samples until the next `start`.
+    fn stop(&self);
+}
+
+// ============================================================================
+// Replay
+// ============================================================================
+
+/// Feeds a decoded clip into the session in 20ms chunks, paced at `speed`×
+/// real time (0 = as fast as possible). Every `start` replays from the
+/// beginning; the clip simply runs out if recording lasts longer.
+pub struct ReplaySource {
+    samples: Arc<Vec<f32>>,
+    sample_rate: u32,
+    speed: f32,
+    /// Bumped on every start/stop so an older replay thread knows to quit
+    generation: Arc<AtomicU64>,
+}
+
+/// Replay chunk length
+const REPLAY_CHUNK: Duration = Duration::from_millis(20);
+
+impl ReplaySource {
+    pub fn new(samples: Vec<f32>, sample_rate: u32, speed: f32) -> Self {
+        ReplaySource { samples: Arc::new(samples), sample_rate, speed, generation: Arc::new(AtomicU64::new(0)) }
+    }
+
+    /// Decode a WAV/FLAC/MP3/OGG file for replay.
+    pub fn open(path: &Path, speed: f32) -> Result<Self, String> {
+        let (samples, sample_rate) = decode_file(path)?;
+        Ok(Self::new(samples, sample_rate, speed))
+    }
+}
+
+impl AudioSource for ReplaySource {
+    fn start(&self, sink: AudioSink) -> Result<u32, String> {
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let current = Arc::clone(&self.generation);
+        let samples = Arc::clone(&self.samples);
+        let chunk_len = (self.sample_rate as usize * REPLAY_CHUNK.as_millis() as usize / 1000).max(1);
+        let pace = (self.speed > 0.0).then(|| REPLAY_CHUNK.div_f32(self.speed));
+        thread::spawn(move || {
+            let started = Instant::now();
+            for (i, chunk) in samples.chunks(chunk_len).enumerate() {
+                if current.load(Ordering::SeqCst) != generation {
+                    return;
+                }
+                sink.push(chunk);
+                if let Some(pace) = pace {
+                    // Sleep to the chunk's deadline rather than a fixed step so pacing doesn't drift
+                    let deadline = started + pace * (i as u32 + 1);
+                    thread::sleep(deadline.saturating_duration_since(Instant::now()));
+                }
+            }
+        });
+        Ok(self.sample_rate)
+    }
+
+    fn stop(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+}
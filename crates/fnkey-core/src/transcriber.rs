@@ -0,0 +1,102 @@
+//! Common interface for transcription providers. A provider can stream
+//! (audio pushed while the key is held), transcribe a finished clip, or both;
+//! the session walks an ordered [`crate::chain::Chain`] of them until one
+//! produces text.
+
+use std::fmt;
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// Interim or final text as it arrives from a streaming provider
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct Segment {
+    pub transcript: String,
+    pub is_final: bool,
+    /// Offset of this segment from the start of the stream, in seconds
+    pub start: f64,
+    pub duration: f64,
+}
+
+/// One recognized word, when the provider reports them
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct Word {
+    pub word: String,
+    /// Seconds from the start of the audio
+    pub start: f64,
+    pub end: f64,
+    pub confidence: Option<f32>,
+}
+
+/// A finished transcription
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transcript {
+    pub text: String,
+    /// [`Transcriber::name`] of the provider that produced it
+    pub provider: String,
+    /// Empty unless the provider reports word timings
+    pub words: Vec<Word>,
+    pub confidence: Option<f32>,
+}
+
+impl Transcript {
+    pub fn new(provider: &str, text: impl Into<String>) -> Self {
+        Transcript { text: text.into(), provider: provider.to_string(), words: Vec::new(), confidence: None }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TranscribeError {
+    /// The provider can't do this (e.g. batch on a streaming-only backend)
+    Unsupported,
+    /// Finished without recognizing any speech
+    Empty,
+    /// No result in time
+    Timeout,
+    /// Connection, HTTP or protocol failure
+    Failed(String),
+}
+
+impl fmt::Display for TranscribeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TranscribeError::Unsupported => write!(f, "not supported by this provider"),
+            TranscribeError::Empty => write!(f, "empty transcript"),
+            TranscribeError::Timeout => write!(f, "timeout waiting for result"),
+            TranscribeError::Failed(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for TranscribeError {}
+
+/// A live streaming session opened by [`Transcriber::start_stream`].
+pub trait StreamSession: Send {
+    /// Send captured audio, at the rate given to `start_stream`. Must not block.
+    fn push_audio(&mut self, samples: &[f32]);
+
+    /// End of audio: wait up to `timeout` for the final transcript.
+    fn finish(self: Box<Self>, timeout: Duration) -> Result<Transcript, TranscribeError>;
+
+    /// The session has already failed (e.g. it couldn't connect), so live
+    /// audio is better sent elsewhere. Checked while recording; must not block.
+    fn failed(&mut self) -> bool {
+        false
+    }
+
+    /// Abandon the session without a result.
+    fn cancel(self: Box<Self>) {}
+}
+
+pub trait Transcriber: Send + Sync {
+    /// Short display name used in logs and notifications, e.g. "Deepgram"
+    fn name(&self) -> &str;
+
+    /// Open a streaming session for audio at `sample_rate`, forwarding
+    /// interim/final segments to `segments`. None if the provider is batch-only.
+    fn start_stream(&self, _sample_rate: u32, _segments: Option<mpsc::Sender<Segment>>) -> Option<Box<dyn StreamSession>> {
+        None
+    }
+
+    /// Transcribe a complete clip.
+    fn transcribe(&self, audio: &[f32], sample_rate: u32) -> Result<Transcript, TranscribeError>;
+}
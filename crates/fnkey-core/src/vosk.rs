@@ -0,0 +1,317 @@
+//! Offline streaming recognition with Vosk (Kaldi). Takes the same 16kHz
+//! 16-bit chunks as Deepgram and reports partial and final hypotheses while
+//! the key is held, with no network. Links against libvosk.
+
+use std::ffi::{c_char, c_float, c_int, CStr, CString};
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::audio::to_pcm16;
+use crate::config::{log_error, models_dir, Config};
+use crate::transcriber::{Segment, StreamSession, TranscribeError, Transcriber, Transcript, Word};
+
+/// Vosk models are trained on 16kHz audio
+pub const VOSK_SAMPLE_RATE: u32 = 16000;
+
+/// Batch mode feeds the clip in chunks of this many bytes (100ms)
+const CLIP_CHUNK_BYTES: usize = 3200;
+
+// ============================================================================
+// FFI
+// ============================================================================
+
+#[repr(C)]
+struct VoskModel {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+struct VoskRecognizer {
+    _private: [u8; 0],
+}
+
+#[link(name = "vosk")]
+extern "C" {
+    fn vosk_set_log_level(log_level: c_int);
+    fn vosk_model_new(model_path: *const c_char) -> *mut VoskModel;
+    fn vosk_model_free(model: *mut VoskModel);
+    fn vosk_recognizer_new(model: *mut VoskModel, sample_rate: c_float) -> *mut VoskRecognizer;
+    fn vosk_recognizer_set_words(recognizer: *mut VoskRecognizer, words: c_int);
+    fn vosk_recognizer_accept_waveform(recognizer: *mut VoskRecognizer, data: *const c_char, length: c_int) -> c_int;
+    fn vosk_recognizer_result(recognizer: *mut VoskRecognizer) -> *const c_char;
+    fn vosk_recognizer_partial_result(recognizer: *mut VoskRecognizer) -> *const c_char;
+    fn vosk_recognizer_final_result(recognizer: *mut VoskRecognizer) -> *const c_char;
+    fn vosk_recognizer_free(recognizer: *mut VoskRecognizer);
+}
+
+/// A loaded model; read-only once created, so it can be shared
+struct Model(*mut VoskModel);
+
+// Vosk documents models as thread-safe; recognizers each own their state
+unsafe impl Send for Model {}
+unsafe impl Sync for Model {}
+
+impl Model {
+    fn load(path: &str) -> Option<Self> {
+        let path = CString::new(path).ok()?;
+        // Kaldi logs every step of the load to stderr otherwise
+        unsafe { vosk_set_log_level(-1) };
+        let model = unsafe { vosk_model_new(path.as_ptr()) };
+        (!model.is_null()).then_some(Model(model))
+    }
+}
+
+impl Drop for Model {
+    fn drop(&mut self) {
+        unsafe { vosk_model_free(self.0) }
+    }
+}
+
+/// One utterance stream. Keeps its model alive.
+struct Recognizer {
+    raw: *mut VoskRecognizer,
+    _model: Arc<Model>,
+}
+
+unsafe impl Send for Recognizer {}
+
+impl Recognizer {
+    fn new(model: Arc<Model>) -> Option<Self> {
+        let raw = unsafe { vosk_recognizer_new(model.0, VOSK_SAMPLE_RATE as c_float) };
+        if raw.is_null() {
+            return None;
+        }
+        unsafe { vosk_recognizer_set_words(raw, 1) };
+        Some(Recognizer { raw, _model: model })
+    }
+
+    /// Feed 16-bit little-endian PCM; true at the end of an utterance
+    fn accept(&mut self, pcm: &[u8]) -> bool {
+        unsafe { vosk_recognizer_accept_waveform(self.raw, pcm.as_ptr() as *const c_char, pcm.len() as c_int) == 1 }
+    }
+
+    fn result(&mut self) -> String {
+        unsafe { json_string(vosk_recognizer_result(self.raw)) }
+    }
+
+    fn partial(&mut self) -> String {
+        unsafe { json_string(vosk_recognizer_partial_result(self.raw)) }
+    }
+
+    fn final_result(&mut self) -> String {
+        unsafe { json_string(vosk_recognizer_final_result(self.raw)) }
+    }
+}
+
+impl Drop for Recognizer {
+    fn drop(&mut self) {
+        unsafe { vosk_recognizer_free(self.raw) }
+    }
+}
+
+/// Copy out a string owned by the recognizer (valid until its next call)
+unsafe fn json_string(ptr: *const c_char) -> String {
+    if ptr.is_null() {
+        return String::new();
+    }
+    CStr::from_ptr(ptr).to_string_lossy().into_owned()
+}
+
+// ============================================================================
+// Results
+// ============================================================================
+
+#[derive(Deserialize, Default)]
+struct VoskResult {
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    partial: String,
+    #[serde(default)]
+    result: Vec<VoskWord>,
+}
+
+#[derive(Deserialize)]
+struct VoskWord {
+    word: String,
+    start: f64,
+    end: f64,
+    conf: f32,
+}
+
+fn parse_result(json: &str) -> VoskResult {
+    serde_json::from_str(json).unwrap_or_default()
+}
+
+// ============================================================================
+// Provider
+// ============================================================================
+
+pub struct VoskTranscriber {
+    model_dir: PathBuf,
+    /// Loaded by the first stream and kept for the rest of the run
+    model: Arc<Mutex<Option<Arc<Model>>>>,
+}
+
+impl VoskTranscriber {
+    pub fn new(model_dir: PathBuf) -> Self {
+        VoskTranscriber { model_dir, model: Arc::new(Mutex::new(None)) }
+    }
+
+    /// `vosk` in the providers file:
+    ///   model=DIR  unpacked model, relative to the models directory
+    ///              (default: the first vosk-model-* directory there)
+    pub fn from_config(_config: &Config, options: &[(String, String)]) -> Result<Self, String> {
+        let mut model = None;
+        for (option, value) in options {
+            match option.as_str() {
+                "model" => model = Some(value.clone()),
+                other => return Err(format!("unknown option '{}'", other)),
+            }
+        }
+        let dir = models_dir().ok_or("HOME is not set")?;
+        let model_dir = match model {
+            Some(name) => dir.join(name),
+            None => default_model(&dir).ok_or("no vosk-model-* directory in ~/.config/fnkey/models")?,
+        };
+        if !model_dir.is_dir() {
+            return Err(format!("model {} not found", model_dir.display()));
+        }
+        Ok(VoskTranscriber::new(model_dir))
+    }
+
+    fn spawn(&self, segments: Option<mpsc::Sender<Segment>>) -> (mpsc::Sender<VoskCommand>, mpsc::Receiver<Result<Transcript, TranscribeError>>) {
+        let (tx, rx) = mpsc::channel();
+        let (result_tx, result_rx) = mpsc::channel();
+        let slot = Arc::clone(&self.model);
+        let path = self.model_dir.to_string_lossy().into_owned();
+        thread::spawn(move || {
+            // Audio queues up in the channel while the model loads
+            let model = {
+                let mut slot = slot.lock().unwrap();
+                if slot.is_none() {
+                    *slot = Model::load(&path).map(Arc::new);
+                }
+                slot.clone()
+            };
+            let Some(recognizer) = model.and_then(Recognizer::new) else {
+                log_error(&format!("Vosk: can't load model {}", path));
+                let _ = result_tx.send(Err(TranscribeError::Failed(format!("can't load Vosk model {}", path))));
+                return;
+            };
+            let _ = result_tx.send(run_recognizer(recognizer, rx, segments));
+        });
+        (tx, result_rx)
+    }
+}
+
+/// First `vosk-model-*` directory, by name
+fn default_model(dir: &std::path::Path) -> Option<PathBuf> {
+    let mut models: Vec<PathBuf> = std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_dir() && path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with("vosk-model")))
+        .collect();
+    models.sort();
+    models.into_iter().next()
+}
+
+enum VoskCommand {
+    /// 16kHz 16-bit little-endian PCM
+    Audio(Vec<u8>),
+    Stop,
+}
+
+/// Recognizer loop: interim segments as the partial changes, a final one per
+/// utterance, and the whole transcript when audio ends.
+fn run_recognizer(
+    mut recognizer: Recognizer,
+    rx: mpsc::Receiver<VoskCommand>,
+    segments: Option<mpsc::Sender<Segment>>,
+) -> Result<Transcript, TranscribeError> {
+    let mut words: Vec<Word> = Vec::new();
+    let mut finals: Vec<String> = Vec::new();
+    let mut last_partial = String::new();
+    // Seconds of audio fed, and where the current utterance began
+    let (mut fed, mut utterance_start) = (0.0, 0.0);
+    let send = |segment: Segment| {
+        if let Some(ref tx) = segments {
+            let _ = tx.send(segment);
+        }
+    };
+    let mut commit = |result: VoskResult, fed: f64, utterance_start: &mut f64| {
+        let text = result.text.trim().to_string();
+        if !text.is_empty() {
+            let start = result.result.first().map_or(*utterance_start, |w| w.start);
+            let end = result.result.last().map_or(fed, |w| w.end);
+            send(Segment { transcript: text.clone(), is_final: true, start, duration: end - start });
+            finals.push(text);
+            words.extend(result.result.into_iter().map(|w| Word { word: w.word, start: w.start, end: w.end, confidence: Some(w.conf) }));
+        }
+        *utterance_start = fed;
+    };
+
+    // A dropped sender means the session was cancelled or finished
+    while let Ok(VoskCommand::Audio(pcm)) = rx.recv() {
+        fed += pcm.len() as f64 / 2.0 / VOSK_SAMPLE_RATE as f64;
+        if recognizer.accept(&pcm) {
+            commit(parse_result(&recognizer.result()), fed, &mut utterance_start);
+            last_partial.clear();
+        } else {
+            let partial = parse_result(&recognizer.partial()).partial;
+            if partial != last_partial && !partial.is_empty() {
+                send(Segment { transcript: partial.clone(), is_final: false, start: utterance_start, duration: fed - utterance_start });
+                last_partial = partial;
+            }
+        }
+    }
+    commit(parse_result(&recognizer.final_result()), fed, &mut utterance_start);
+
+    let text = finals.join(" ");
+    if text.is_empty() {
+        return Err(TranscribeError::Empty);
+    }
+    let confidence = (!words.is_empty()).then(|| words.iter().filter_map(|w| w.confidence).sum::<f32>() / words.len() as f32);
+    Ok(Transcript { text, provider: "Vosk".to_string(), words, confidence })
+}
+
+struct VoskStream {
+    tx: mpsc::Sender<VoskCommand>,
+    result_rx: mpsc::Receiver<Result<Transcript, TranscribeError>>,
+    sample_rate: u32,
+}
+
+impl StreamSession for VoskStream {
+    fn push_audio(&mut self, samples: &[f32]) {
+        let _ = self.tx.send(VoskCommand::Audio(to_pcm16(samples, self.sample_rate, VOSK_SAMPLE_RATE)));
+    }
+
+    fn finish(self: Box<Self>, timeout: Duration) -> Result<Transcript, TranscribeError> {
+        let _ = self.tx.send(VoskCommand::Stop);
+        self.result_rx.recv_timeout(timeout).unwrap_or(Err(TranscribeError::Timeout))
+    }
+}
+
+impl Transcriber for VoskTranscriber {
+    fn name(&self) -> &str {
+        "Vosk"
+    }
+
+    fn start_stream(&self, sample_rate: u32, segments: Option<mpsc::Sender<Segment>>) -> Option<Box<dyn StreamSession>> {
+        let (tx, result_rx) = self.spawn(segments);
+        Some(Box::new(VoskStream { tx, result_rx, sample_rate }))
+    }
+
+    fn transcribe(&self, audio: &[f32], sample_rate: u32) -> Result<Transcript, TranscribeError> {
+        let (tx, result_rx) = self.spawn(None);
+        for chunk in to_pcm16(audio, sample_rate, VOSK_SAMPLE_RATE).chunks(CLIP_CHUNK_BYTES) {
+            let _ = tx.send(VoskCommand::Audio(chunk.to_vec()));
+        }
+        let _ = tx.send(VoskCommand::Stop);
+        result_rx.recv().unwrap_or_else(|_| Err(TranscribeError::Failed("Vosk thread exited".to_string())))
+    }
+}
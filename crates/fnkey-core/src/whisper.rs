@@ -0,0 +1,137 @@
+//! On-device transcription with whisper.cpp. Loads a ggml model from
+//! ~/.config/fnkey/models and runs on the CPU; nothing leaves the machine.
+
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+
+use crate::audio::resample;
+use crate::config::{log_error, models_dir, Config};
+use crate::transcriber::{TranscribeError, Transcriber, Transcript};
+
+/// whisper.cpp only takes 16kHz mono
+const WHISPER_SAMPLE_RATE: u32 = 16000;
+
+/// First `ggml-*.bin` in the models directory, by name
+fn default_model() -> Option<PathBuf> {
+    let mut models: Vec<PathBuf> = std::fs::read_dir(models_dir()?)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+            name.starts_with("ggml-") && name.ends_with(".bin")
+        })
+        .collect();
+    models.sort();
+    models.into_iter().next()
+}
+
+pub struct WhisperTranscriber {
+    model: PathBuf,
+    /// Loaded on first use so a chain that never reaches us costs nothing
+    context: Mutex<Option<WhisperContext>>,
+    /// Sent as the initial prompt
+    keywords: Vec<String>,
+    /// e.g. "en"; None lets Whisper detect it
+    language: Option<String>,
+    threads: i32,
+}
+
+impl WhisperTranscriber {
+    pub fn new(model: PathBuf, keywords: Vec<String>) -> Self {
+        let threads = std::thread::available_parallelism().map_or(4, |n| n.get().min(8) as i32);
+        WhisperTranscriber { model, context: Mutex::new(None), keywords, language: None, threads }
+    }
+
+    /// `whisper` in the providers file:
+    ///   model=FILE     ggml model, relative to the models directory
+    ///                  (default: the first ggml-*.bin there)
+    ///   language=CODE  skip language detection
+    ///   threads=N      CPU threads (default: cores, at most 8)
+    pub fn from_config(config: &Config, options: &[(String, String)]) -> Result<Self, String> {
+        let mut model = None;
+        let mut language = None;
+        let mut threads = None;
+        for (option, value) in options {
+            match option.as_str() {
+                "model" => model = Some(value.clone()),
+                "language" => language = Some(value.clone()),
+                "threads" => threads = Some(value.parse::<i32>().ok().filter(|&n| n > 0).ok_or_else(|| format!("bad threads '{}'", value))?),
+                other => return Err(format!("unknown option '{}'", other)),
+            }
+        }
+        let model = match model {
+            Some(name) => models_dir().map(|dir| dir.join(&name)).ok_or("HOME is not set")?,
+            None => default_model().ok_or("no ggml-*.bin model in ~/.config/fnkey/models")?,
+        };
+        if !model.is_file() {
+            return Err(format!("model {} not found", model.display()));
+        }
+        let mut transcriber = WhisperTranscriber::new(model, config.keywords.clone());
+        transcriber.language = language;
+        if let Some(threads) = threads {
+            transcriber.threads = threads;
+        }
+        Ok(transcriber)
+    }
+}
+
+impl Transcriber for WhisperTranscriber {
+    fn name(&self) -> &str {
+        "Whisper"
+    }
+
+    fn transcribe(&self, audio: &[f32], sample_rate: u32) -> Result<Transcript, TranscribeError> {
+        let mut context = self.context.lock().unwrap();
+        if context.is_none() {
+            // Route whisper.cpp's console chatter away from our stderr
+            whisper_rs::install_logging_hooks();
+            let path = self.model.to_str().ok_or_else(|| TranscribeError::Failed("model path is not UTF-8".to_string()))?;
+            let loaded = WhisperContext::new_with_params(path, WhisperContextParameters::default()).map_err(|e| {
+                log_error(&format!("Loading {} failed: {}", self.model.display(), e));
+                TranscribeError::Failed(format!("loading model: {}", e))
+            })?;
+            *context = Some(loaded);
+        }
+        let mut state = context.as_ref().unwrap().create_state().map_err(|e| TranscribeError::Failed(e.to_string()))?;
+
+        let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+        params.set_n_threads(self.threads);
+        params.set_language(self.language.as_deref());
+        params.set_no_context(true);
+        params.set_print_special(false);
+        params.set_print_progress(false);
+        params.set_print_realtime(false);
+        params.set_print_timestamps(false);
+        let prompt = self.keywords.join(", ");
+        if !prompt.is_empty() {
+            params.set_initial_prompt(&prompt);
+        }
+
+        let samples = resample(audio, sample_rate, WHISPER_SAMPLE_RATE);
+        state.full(params, &samples).map_err(|e| TranscribeError::Failed(e.to_string()))?;
+
+        let mut text = String::new();
+        let (mut prob_sum, mut tokens) = (0.0, 0);
+        let segments = state.full_n_segments().map_err(|e| TranscribeError::Failed(e.to_string()))?;
+        for segment in 0..segments {
+            if let Ok(segment_text) = state.full_get_segment_text_lossy(segment) {
+                text.push_str(&segment_text);
+            }
+            for token in 0..state.full_n_tokens(segment).unwrap_or(0) {
+                if let Ok(prob) = state.full_get_token_prob(segment, token) {
+                    prob_sum += prob;
+                    tokens += 1;
+                }
+            }
+        }
+        let text = text.trim();
+        if text.is_empty() {
+            return Err(TranscribeError::Empty);
+        }
+        // Mean token probability, for the chain's low-confidence fall-through
+        let confidence = (tokens > 0).then(|| prob_sum / tokens as f32);
+        Ok(Transcript { confidence, ..Transcript::new("Whisper", text) })
+    }
+}
@@ -0,0 +1,371 @@
+//! Wyoming protocol client for local speech servers (wyoming-faster-whisper,
+//! wyoming-vosk, …). Events are a JSON header line, optionally followed by
+//! `data_length` bytes of JSON data and `payload_length` bytes of payload.
+
+use std::io::{self, BufRead, Read, Write};
+use std::net::{Shutdown, TcpStream, ToSocketAddrs};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Map, Value};
+
+use crate::audio::to_pcm16;
+use crate::config::{log_error, Config};
+use crate::readiness;
+use crate::transcriber::{Segment, StreamSession, TranscribeError, Transcriber, Transcript};
+
+pub const WYOMING_SAMPLE_RATE: u32 = 16000;
+
+/// Port wyoming-faster-whisper listens on by default
+const DEFAULT_HOST: &str = "localhost:10300";
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Give up on a server that never answers audio-stop
+const RESULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Batch mode sends the clip in chunks of this many bytes (100ms)
+const CLIP_CHUNK_BYTES: usize = 3200;
+
+// ============================================================================
+// Events
+// ============================================================================
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Event {
+    /// e.g. "audio-chunk", "transcript"
+    pub kind: String,
+    pub data: Map<String, Value>,
+    pub payload: Vec<u8>,
+}
+
+impl Event {
+    pub fn new(kind: &str, data: Value) -> Self {
+        let data = match data {
+            Value::Object(map) => map,
+            _ => Map::new(),
+        };
+        Event { kind: kind.to_string(), data, payload: Vec::new() }
+    }
+
+    /// `data.text`, for transcript and error events
+    pub fn text(&self) -> &str {
+        self.data.get("text").and_then(Value::as_str).unwrap_or_default()
+    }
+}
+
+pub fn write_event<W: Write>(w: &mut W, event: &Event) -> io::Result<()> {
+    let data = serde_json::to_vec(&event.data)?;
+    let mut header = json!({ "type": event.kind, "data_length": data.len() });
+    if !event.payload.is_empty() {
+        header["payload_length"] = json!(event.payload.len());
+    }
+    let mut buf = serde_json::to_vec(&header)?;
+    buf.push(b'\n');
+    buf.extend_from_slice(&data);
+    buf.extend_from_slice(&event.payload);
+    w.write_all(&buf)?;
+    w.flush()
+}
+
+/// Read one event. Data may be inline in the header (older servers) or follow
+/// it; both are merged. EOF before a header is `UnexpectedEof`.
+pub fn read_event<R: BufRead>(r: &mut R) -> io::Result<Event> {
+    let mut line = String::new();
+    if r.read_line(&mut line)? == 0 {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    let header: Value = serde_json::from_str(&line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let kind = header["type"].as_str().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "event without type"))?;
+    let mut event = Event::new(kind, header.get("data").cloned().unwrap_or(Value::Null));
+
+    let data_length = header["data_length"].as_u64().unwrap_or(0) as usize;
+    if data_length > 0 {
+        let mut data = vec![0; data_length];
+        r.read_exact(&mut data)?;
+        if let Value::Object(extra) = serde_json::from_slice(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))? {
+            event.data.extend(extra);
+        }
+    }
+    let payload_length = header["payload_length"].as_u64().unwrap_or(0) as usize;
+    if payload_length > 0 {
+        event.payload = vec![0; payload_length];
+        r.read_exact(&mut event.payload)?;
+    }
+    Ok(event)
+}
+
+// ============================================================================
+// Provider
+// ============================================================================
+
+pub struct WyomingTranscriber {
+    name: String,
+    /// host:port
+    host: String,
+    language: Option<String>,
+    /// Model name for servers that host several
+    model: Option<String>,
+}
+
+impl WyomingTranscriber {
+    pub fn new(host: impl Into<String>) -> Self {
+        WyomingTranscriber { name: "Wyoming".to_string(), host: host.into(), language: None, model: None }
+    }
+
+    /// `wyoming` in the providers file:
+    ///   uri=tcp://HOST:PORT  server (default: tcp://localhost:10300)
+    ///   language=CODE  model=NAME  name=Label
+    pub fn from_config(_config: &Config, options: &[(String, String)]) -> Result<Self, String> {
+        let mut transcriber = WyomingTranscriber::new(DEFAULT_HOST);
+        for (option, value) in options {
+            match option.as_str() {
+                "uri" => {
+                    let host = value.strip_prefix("tcp://").unwrap_or(value).trim_end_matches('/');
+                    if host.contains("://") || !host.contains(':') {
+                        return Err(format!("uri must be tcp://HOST:PORT, got '{}'", value));
+                    }
+                    transcriber.host = host.to_string();
+                }
+                "language" => transcriber.language = Some(value.clone()),
+                "model" => transcriber.model = Some(value.clone()),
+                "name" => transcriber.name = value.clone(),
+                other => return Err(format!("unknown option '{}'", other)),
+            }
+        }
+        Ok(transcriber)
+    }
+
+    /// Connect and run one transcription on a background thread
+    fn spawn(&self, segments: Option<mpsc::Sender<Segment>>) -> WyomingStream {
+        let (tx, rx) = mpsc::channel();
+        let (result_tx, result_rx) = mpsc::channel();
+        let host = self.host.clone();
+        let name = self.name.clone();
+        let mut transcribe = Map::new();
+        if let Some(ref language) = self.language {
+            transcribe.insert("language".to_string(), json!(language));
+        }
+        if let Some(ref model) = self.model {
+            transcribe.insert("name".to_string(), json!(model));
+        }
+        thread::spawn(move || {
+            // Audio queues up in the channel while we connect
+            let result = match connect(&host) {
+                Ok(stream) => run_session(stream, Value::Object(transcribe), rx, segments),
+                Err(e) => Err(format!("can't connect to {}: {}", host, e)),
+            };
+            let _ = result_tx.send(result.map(|text| Transcript::new(&name, text)).map_err(|e| {
+                log_error(&format!("{}: {}", name, e));
+                TranscribeError::Failed(e)
+            }));
+        });
+        WyomingStream { tx, result_rx, sample_rate: WYOMING_SAMPLE_RATE }
+    }
+}
+
+fn connect(host: &str) -> io::Result<TcpStream> {
+    let mut last_err = io::Error::new(io::ErrorKind::NotFound, format!("can't resolve {}", host));
+    for addr in host.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+            Ok(stream) => {
+                let _ = stream.set_nodelay(true);
+                return Ok(stream);
+            }
+            Err(e) => last_err = e,
+        }
+    }
+    Err(last_err)
+}
+
+enum WyomingCommand {
+    /// 16kHz 16-bit little-endian PCM
+    Audio(Vec<u8>),
+    Stop,
+}
+
+/// Send transcribe + audio-start, an audio-chunk per command, audio-stop on
+/// Stop; meanwhile read events until the transcript. Streaming servers'
+/// transcript-chunk events become interim segments. The socket is
+/// non-blocking; between events the thread sleeps in [`readiness::wait`].
+fn run_session(
+    stream: TcpStream,
+    transcribe: Value,
+    rx: mpsc::Receiver<WyomingCommand>,
+    segments: Option<mpsc::Sender<Segment>>,
+) -> Result<String, String> {
+    let result = exchange(&stream, transcribe, rx, segments);
+    let _ = stream.shutdown(Shutdown::Both);
+    result
+}
+
+fn exchange(
+    stream: &TcpStream,
+    transcribe: Value,
+    rx: mpsc::Receiver<WyomingCommand>,
+    segments: Option<mpsc::Sender<Segment>>,
+) -> Result<String, String> {
+    // The handshake goes out before anything else, even a cancel
+    let format = json!({ "rate": WYOMING_SAMPLE_RATE, "width": 2, "channels": 1 });
+    let mut start = format.clone();
+    start["timestamp"] = json!(0);
+    let mut writer = stream;
+    for event in [Event::new("transcribe", transcribe), Event::new("audio-start", start)] {
+        write_event(&mut writer, &event).map_err(|e| format!("send failed: {}", e))?;
+    }
+
+    stream.set_nonblocking(true).map_err(|e| e.to_string())?;
+    let (waker, wakeups) = readiness::channel().map_err(|e| e.to_string())?;
+    let commands = readiness::relay(rx, waker);
+    // Events are queued here and written as the socket takes them
+    let mut outgoing = Vec::new();
+    let mut incoming = Vec::new();
+    let queue = |out: &mut Vec<u8>, event: Event| write_event(out, &event).map_err(|e| e.to_string());
+
+    let mut sent_ms = 0u64;
+    // Set by Stop: the transcript must arrive before this
+    let mut deadline: Option<Instant> = None;
+    let mut interim = String::new();
+    let mut readable = true;
+    loop {
+        // 1. Turn commands into events
+        while deadline.is_none() {
+            match commands.try_recv() {
+                Ok(WyomingCommand::Audio(pcm)) => {
+                    let mut chunk = Event::new("audio-chunk", format.clone());
+                    chunk.data.insert("timestamp".to_string(), json!(sent_ms));
+                    sent_ms += pcm.len() as u64 * 1000 / 2 / WYOMING_SAMPLE_RATE as u64;
+                    chunk.payload = pcm;
+                    queue(&mut outgoing, chunk)?;
+                }
+                Ok(WyomingCommand::Stop) => {
+                    queue(&mut outgoing, Event::new("audio-stop", json!({ "timestamp": sent_ms })))?;
+                    deadline = Some(Instant::now() + RESULT_TIMEOUT);
+                }
+                Err(mpsc::TryRecvError::Empty) => break,
+                // Cancelled
+                Err(mpsc::TryRecvError::Disconnected) => return Err("cancelled".to_string()),
+            }
+        }
+
+        // 2. Write what the socket will take
+        while !outgoing.is_empty() {
+            match (&*stream).write(&outgoing) {
+                Ok(0) => return Err("send failed: connection closed".to_string()),
+                Ok(n) => {
+                    outgoing.drain(..n);
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(format!("send failed: {}", e)),
+            }
+        }
+
+        // 3. Read everything that has arrived, then handle each whole event
+        let mut closed = false;
+        let mut buf = [0u8; 4096];
+        while readable {
+            match (&*stream).read(&mut buf) {
+                Ok(0) => {
+                    closed = true;
+                    break;
+                }
+                Ok(n) => incoming.extend_from_slice(&buf[..n]),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => readable = false,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(format!("read failed: {}", e)),
+            }
+        }
+        while let Some(event) = take_event(&mut incoming).map_err(|e| format!("bad event: {}", e))? {
+            match event.kind.as_str() {
+                "transcript" => return Ok(event.text().trim().to_string()),
+                "transcript-chunk" => {
+                    interim.push_str(event.text());
+                    if let Some(ref tx) = segments {
+                        let duration = sent_ms as f64 / 1000.0;
+                        let _ = tx.send(Segment { transcript: interim.trim().to_string(), is_final: false, start: 0.0, duration });
+                    }
+                }
+                "error" => return Err(format!("server error: {}", event.text())),
+                // transcript-start/stop, info, …
+                _ => {}
+            }
+        }
+        if closed {
+            return Err("server closed the connection".to_string());
+        }
+
+        // 4. Sleep until the socket or the relay has something for us
+        let timeout = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+        if timeout.is_some_and(|t| t.is_zero()) {
+            return Err("no transcript from server".to_string());
+        }
+        let ready = readiness::wait(stream, &wakeups, !outgoing.is_empty(), timeout)
+            .map_err(|e| format!("waiting on socket failed: {}", e))?;
+        readable = ready.readable;
+    }
+}
+
+/// Take one whole event off the front of `buf`; None until all of it is there
+fn take_event(buf: &mut Vec<u8>) -> io::Result<Option<Event>> {
+    if !buf.contains(&b'\n') {
+        return Ok(None);
+    }
+    let mut reader = io::Cursor::new(&buf[..]);
+    match read_event(&mut reader) {
+        Ok(event) => {
+            let used = reader.position() as usize;
+            buf.drain(..used);
+            Ok(Some(event))
+        }
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+struct WyomingStream {
+    tx: mpsc::Sender<WyomingCommand>,
+    result_rx: mpsc::Receiver<Result<Transcript, TranscribeError>>,
+    sample_rate: u32,
+}
+
+impl StreamSession for WyomingStream {
+    fn push_audio(&mut self, samples: &[f32]) {
+        let _ = self.tx.send(WyomingCommand::Audio(to_pcm16(samples, self.sample_rate, WYOMING_SAMPLE_RATE)));
+    }
+
+    fn finish(self: Box<Self>, timeout: Duration) -> Result<Transcript, TranscribeError> {
+        let _ = self.tx.send(WyomingCommand::Stop);
+        match self.result_rx.recv_timeout(timeout) {
+            Ok(Ok(transcript)) if transcript.text.is_empty() => Err(TranscribeError::Empty),
+            Ok(result) => result,
+            Err(_) => Err(TranscribeError::Timeout),
+        }
+    }
+}
+
+impl Transcriber for WyomingTranscriber {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn start_stream(&self, sample_rate: u32, segments: Option<mpsc::Sender<Segment>>) -> Option<Box<dyn StreamSession>> {
+        let mut stream = self.spawn(segments);
+        stream.sample_rate = sample_rate;
+        Some(Box::new(stream))
+    }
+
+    fn transcribe(&self, audio: &[f32], sample_rate: u32) -> Result<Transcript, TranscribeError> {
+        let stream = self.spawn(None);
+        for chunk in to_pcm16(audio, sample_rate, WYOMING_SAMPLE_RATE).chunks(CLIP_CHUNK_BYTES) {
+            let _ = stream.tx.send(WyomingCommand::Audio(chunk.to_vec()));
+        }
+        let _ = stream.tx.send(WyomingCommand::Stop);
+        match stream.result_rx.recv() {
+            Ok(Ok(transcript)) if transcript.text.is_empty() => Err(TranscribeError::Empty),
+            Ok(result) => result,
+            Err(_) => Err(TranscribeError::Failed("Wyoming thread exited".to_string())),
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use std::net::TcpListener;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use fnkey_core::assemblyai::AssemblyAiTranscriber;
+use fnkey_core::transcriber::{TranscribeError, Transcriber};
+use fnkey_core::Config;
+use serde_json::{json, Value};
+use tungstenite::handshake::server::{Request, Response};
+use tungstenite::protocol::frame::coding::CloseCode;
+use tungstenite::protocol::CloseFrame;
+use tungstenite::Message;
+
+/// What the mock server saw
+#[derive(Default)]
+struct Seen {
+    uri: String,
+    authorization: Option<String>,
+    /// Sizes of the binary frames, in order
+    chunks: Vec<usize>,
+    terminated: bool,
+}
+
+enum Script {
+    /// Wait for Terminate, then send these
+    Reply(Vec<Value>),
+    /// Close with this code and reason right after the handshake
+    Close(u16, &'static str),
+}
+
+/// Accept one WebSocket connection and play `script`. Returns a ws:// URL
+/// and where the session will be reported.
+// The handshake callback's error type is tungstenite's
+#[allow(clippy::result_large_err)]
+fn mock_server(script: Script) -> (String, mpsc::Receiver<Seen>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("ws://{}/v3/ws", listener.local_addr().unwrap());
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut seen = Seen::default();
+        let mut ws = tungstenite::accept_hdr(stream, |request: &Request, response: Response| {
+            seen.uri = request.uri().to_string();
+            seen.authorization = request.headers().get("authorization").map(|v| v.to_str().unwrap().to_string());
+            Ok(response)
+        })
+        .unwrap();
+        ws.send(Message::Text(json!({ "type": "Begin", "id": "abc", "expires_at": 1 }).to_string())).unwrap();
+        match script {
+            Script::Close(code, reason) => {
+                let _ = ws.close(Some(CloseFrame { code: CloseCode::from(code), reason: reason.into() }));
+                while ws.read().is_ok() {}
+            }
+            Script::Reply(replies) => {
+                while let Ok(message) = ws.read() {
+                    match message {
+                        Message::Binary(chunk) => seen.chunks.push(chunk.len()),
+                        Message::Text(text) if text.contains("Terminate") => {
+                            seen.terminated = true;
+                            break;
+                        }
+                        _ => {}
+                    }
+                }
+                for reply in replies {
+                    ws.send(Message::Text(reply.to_string())).unwrap();
+                }
+                let _ = ws.close(None);
+                while ws.read().is_ok() {}
+            }
+        }
+        let _ = tx.send(seen);
+    });
+    (url, rx)
+}
+
+fn word(text: &str, start: u64, end: u64, confidence: f32) -> Value {
+    json!({ "text": text, "start": start, "end": end, "confidence": confidence, "word_is_final": true })
+}
+
+fn turn(order: u32, transcript: &str, end_of_turn: bool, formatted: bool, words: Vec<Value>) -> Value {
+    json!({
+        "type": "Turn", "turn_order": order, "transcript": transcript,
+        "end_of_turn": end_of_turn, "turn_is_formatted": formatted,
+        "end_of_turn_confidence": 0.9, "words": words,
+    })
+}
+
+fn termination() -> Value {
+    json!({ "type": "Termination", "audio_duration_seconds": 1, "session_duration_seconds": 1 })
+}
+
+#[test]
+fn streams_turns_with_words_and_keyterms() {
+    let replies = vec![
+        turn(0, "hello", false, false, vec![word("hello", 100, 400, 0.9)]),
+        turn(0, "hello world", true, false, vec![word("hello", 100, 400, 0.9), word("world", 450, 800, 0.7)]),
+        turn(0, "Hello world.", true, true, vec![word("Hello", 100, 400, 0.9), word("world.", 450, 800, 0.7)]),
+        turn(1, "again", true, true, vec![word("again", 1000, 1200, 0.8)]),
+        termination(),
+    ];
+    let (url, seen) = mock_server(Script::Reply(replies));
+    let config = Config { assemblyai_key: Some("aai-key".into()), keywords: vec!["FnKey".into(), "Nova 3".into()], ..Config::default() };
+    let options = [("url".to_string(), url)];
+    let transcriber = AssemblyAiTranscriber::from_config(&config, &options).unwrap();
+
+    let (segments_tx, segments) = mpsc::channel();
+    let mut stream = transcriber.start_stream(48000, Some(segments_tx)).unwrap();
+    // 20ms pushes, like the recorder: 250ms in all
+    for _ in 0..12 {
+        stream.push_audio(&[0.1; 960]);
+    }
+    stream.push_audio(&[0.1; 480]);
+    let transcript = stream.finish(Duration::from_secs(5)).unwrap();
+    assert_eq!(transcript.text, "Hello world. again");
+    assert_eq!(transcript.provider, "AssemblyAI");
+    let words: Vec<(&str, f64, f64)> = transcript.words.iter().map(|w| (w.word.as_str(), w.start, w.end)).collect();
+    assert_eq!(words, [("Hello", 0.1, 0.4), ("world.", 0.45, 0.8), ("again", 1.0, 1.2)]);
+    assert!((transcript.confidence.unwrap() - 0.8).abs() < 1e-6);
+
+    let segments: Vec<(String, bool)> = segments.try_iter().map(|s| (s.transcript, s.is_final)).collect();
+    assert_eq!(segments, [("hello".to_string(), false), ("Hello world.".to_string(), true), ("again".to_string(), true)]);
+
+    let seen = seen.recv().unwrap();
+    assert!(seen.terminated);
+    assert_eq!(seen.authorization.as_deref(), Some("aai-key"));
+    assert!(seen.uri.starts_with("/v3/ws?sample_rate=16000&encoding=pcm_s16le&format_turns=true"), "{}", seen.uri);
+    let keyterms = seen.uri.split("keyterms_prompt=").nth(1).unwrap();
+    assert_eq!(urlencoding::decode(keyterms).unwrap(), r#"["FnKey","Nova 3"]"#);
+    // 250ms of 16kHz i16 = 8000 bytes, sent in frames of at least 50ms
+    assert_eq!(seen.chunks.iter().sum::<usize>(), 8000);
+    assert!(seen.chunks.iter().all(|&len| len >= 1600), "{:?}", seen.chunks);
+}
+
+#[test]
+fn batch_clip_and_empty_result() {
+    let (url, seen) = mock_server(Script::Reply(vec![turn(0, "from a file", true, true, vec![]), termination()]));
+    let transcriber = AssemblyAiTranscriber::new("k", Vec::new()).with_url(url);
+    assert_eq!(transcriber.transcribe(&[0.1; 40000], 16000).unwrap().text, "from a file");
+    let seen = seen.recv().unwrap();
+    // 2.5s of audio in 1s frames
+    assert_eq!(seen.chunks, [32000, 32000, 16000]);
+    assert!(!seen.uri.contains("keyterms_prompt"));
+
+    let (url, _seen) = mock_server(Script::Reply(vec![termination()]));
+    let transcriber = AssemblyAiTranscriber::new("k", Vec::new()).with_url(url);
+    assert_eq!(transcriber.transcribe(&[0.1; 1600], 16000).unwrap_err(), TranscribeError::Empty);
+}
+
+#[test]
+fn server_close_is_an_error() {
+    let (url, _seen) = mock_server(Script::Close(1008, "Unauthorized connection"));
+    let transcriber = AssemblyAiTranscriber::new("bad", Vec::new()).with_url(url);
+    match transcriber.transcribe(&[0.1; 16000], 16000) {
+        Err(TranscribeError::Failed(msg)) => assert!(msg.contains("Unauthorized"), "{}", msg),
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn connect_failure_shows_before_finish() {
+    let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let transcriber = AssemblyAiTranscriber::new("k", Vec::new()).with_url(format!("ws://127.0.0.1:{}/v3/ws", port));
+    let mut stream = transcriber.start_stream(16000, None).unwrap();
+    stream.push_audio(&[0.1; 320]);
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !stream.failed() {
+        assert!(Instant::now() < deadline, "timed out");
+        thread::sleep(Duration::from_millis(5));
+    }
+    assert!(matches!(stream.finish(Duration::ZERO), Err(TranscribeError::Failed(msg)) if msg.contains("connect failed")));
+}
+
+#[test]
+fn config_needs_key_and_ws_url() {
+    let with = |options: &[(&str, &str)], key: Option<&str>| {
+        let config = Config { assemblyai_key: key.map(String::from), ..Config::default() };
+        let options: Vec<(String, String)> = options.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        AssemblyAiTranscriber::from_config(&config, &options).err()
+    };
+    assert!(with(&[], None).unwrap().contains("assemblyai_key"));
+    assert!(with(&[("url", "https://example.com")], Some("k")).unwrap().contains("ws://"));
+    assert!(with(&[("language", "en")], Some("k")).unwrap().contains("unknown option"));
+    assert!(with(&[("model", "universal-streaming-multilingual")], Some("k")).is_none());
+
+    let long = "x".repeat(51);
+    let transcriber = AssemblyAiTranscriber::new("k", vec![long, "ok".to_string()]);
+    assert!(transcriber.request_url().ends_with(&format!("keyterms_prompt={}", urlencoding::encode(r#"["ok"]"#))));
+}
@@ -0,0 +1,75 @@
+use std::io::Cursor;
+
+use fnkey_core::audio::{decode_file, encode_wav, resample, to_pcm16, PcmFormat};
+
+#[test]
+fn resample_48k_to_16k_keeps_duration() {
+    let samples = vec![0.25_f32; 48000];
+    let out = resample(&samples, 48000, 16000);
+    assert_eq!(out.len(), 16000);
+    assert!(out.iter().all(|&s| (s - 0.25).abs() < 1e-6));
+}
+
+#[test]
+fn pcm16_is_little_endian_and_clamped() {
+    let bytes = to_pcm16(&[1.0, -2.0, 0.0], 16000, 16000);
+    assert_eq!(bytes, vec![0xff, 0x7f, 0x00, 0x80, 0x00, 0x00]);
+}
+
+#[test]
+fn encode_wav_round_trips_through_hound() {
+    let tone: Vec<f32> = (0..16000)
+        .map(|i| (i as f32 * 440.0 * std::f32::consts::TAU / 16000.0).sin() * 0.1)
+        .collect();
+    let wav = encode_wav(&tone, 16000).unwrap();
+    let reader = hound::WavReader::new(Cursor::new(wav)).unwrap();
+    assert_eq!(reader.spec().sample_rate, 16000);
+    assert_eq!(reader.spec().channels, 1);
+    assert_eq!(reader.len(), 16000);
+}
+
+#[test]
+fn decode_file_downmixes_stereo_wav() {
+    let path = std::env::temp_dir().join(format!("fnkey-decode-{}.wav", std::process::id()));
+    let spec = hound::WavSpec { channels: 2, sample_rate: 44100, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+    let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+    for _ in 0..4410 {
+        writer.write_sample(16384_i16).unwrap();
+        writer.write_sample(0_i16).unwrap();
+    }
+    writer.finalize().unwrap();
+
+    let decoded = decode_file(&path);
+    let _ = std::fs::remove_file(&path);
+    let (samples, rate) = decoded.unwrap();
+    assert_eq!(rate, 44100);
+    assert_eq!(samples.len(), 4410);
+    assert!(samples.iter().all(|&s| (s - 0.25).abs() < 1e-3));
+}
+
+#[test]
+fn decode_file_rejects_non_audio() {
+    let path = std::env::temp_dir().join(format!("fnkey-decode-{}.txt", std::process::id()));
+    std::fs::write(&path, "not audio").unwrap();
+    let decoded = decode_file(&path);
+    let _ = std::fs::remove_file(&path);
+    assert!(decoded.is_err());
+}
+
+#[test]
+fn pcm_formats_decode_to_mono() {
+    assert_eq!(PcmFormat::parse("F32LE"), Some(PcmFormat::F32Le));
+    assert_eq!(PcmFormat::parse("s16_le"), Some(PcmFormat::S16Le));
+    assert_eq!(PcmFormat::parse("u8"), None);
+
+    let mut f32_bytes = Vec::new();
+    for s in [0.5_f32, -0.5, 1.0, 0.0] {
+        f32_bytes.extend_from_slice(&s.to_le_bytes());
+    }
+    assert_eq!(PcmFormat::F32Le.to_mono(&f32_bytes, 1), vec![0.5, -0.5, 1.0, 0.0]);
+    assert_eq!(PcmFormat::F32Le.to_mono(&f32_bytes, 2), vec![0.0, 0.5]);
+
+    // Trailing odd byte is an incomplete frame and is dropped
+    let s16_bytes = [0x00, 0x40, 0x00, 0xc0, 0x7f];
+    assert_eq!(PcmFormat::S16Le.to_mono(&s16_bytes, 1), vec![0.5, -0.5]);
+}
@@ -0,0 +1,224 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use fnkey_core::chain::{build_provider, parse_providers, Chain, FallThrough, Link, Streamed};
+use fnkey_core::transcriber::{Segment, StreamSession, TranscribeError, Transcriber, Transcript};
+use fnkey_core::Config;
+
+/// Batch provider with a canned answer, optional delay and call counter
+struct Fake {
+    name: &'static str,
+    result: Result<(&'static str, Option<f32>), TranscribeError>,
+    delay: Duration,
+    calls: AtomicUsize,
+}
+
+impl Fake {
+    fn new(name: &'static str, result: Result<(&'static str, Option<f32>), TranscribeError>) -> Arc<Self> {
+        Arc::new(Fake { name, result, delay: Duration::ZERO, calls: AtomicUsize::new(0) })
+    }
+
+    fn slow(name: &'static str, delay: Duration) -> Arc<Self> {
+        Arc::new(Fake { name, result: Ok(("too late", None)), delay, calls: AtomicUsize::new(0) })
+    }
+}
+
+impl Transcriber for Fake {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn transcribe(&self, _audio: &[f32], _sample_rate: u32) -> Result<Transcript, TranscribeError> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        thread::sleep(self.delay);
+        self.result.clone().map(|(text, confidence)| Transcript { confidence, ..Transcript::new(self.name, text) })
+    }
+}
+
+/// Streaming-only provider whose streams say "live"
+struct Live;
+
+struct LiveStream;
+
+impl StreamSession for LiveStream {
+    fn push_audio(&mut self, _samples: &[f32]) {}
+
+    fn finish(self: Box<Self>, _timeout: Duration) -> Result<Transcript, TranscribeError> {
+        Ok(Transcript::new("Live", "live"))
+    }
+}
+
+impl Transcriber for Live {
+    fn name(&self) -> &str {
+        "Live"
+    }
+
+    fn start_stream(&self, _sample_rate: u32, _segments: Option<mpsc::Sender<Segment>>) -> Option<Box<dyn StreamSession>> {
+        Some(Box::new(LiveStream))
+    }
+
+    fn transcribe(&self, _audio: &[f32], _sample_rate: u32) -> Result<Transcript, TranscribeError> {
+        Err(TranscribeError::Unsupported)
+    }
+}
+
+fn link(transcriber: Arc<Fake>, timeout: Option<Duration>, fall_through: FallThrough) -> Link {
+    Link { transcriber, timeout, fall_through }
+}
+
+/// Chain result (failed provider names on error) and the fallbacks taken
+type Outcome = (Result<Transcript, Vec<String>>, Vec<(String, String)>);
+
+fn run(chain: &Chain) -> Outcome {
+    let mut fallbacks = Vec::new();
+    let result = chain.run(None, &[0.1; 160], 16000, |from, to| fallbacks.push((from.to_string(), to.to_string())));
+    (result.map_err(|failures| failures.into_iter().map(|f| f.provider).collect()), fallbacks)
+}
+
+#[test]
+fn parses_providers_file() {
+    let specs = parse_providers(
+        "# local first\n\
+         deepgram timeout=2.5\n\
+         \n\
+         Groq fallthrough=error min_confidence=0.6 fallthrough=error,low-confidence\n\
+         groq min_confidence=0.3\n",
+    )
+    .unwrap();
+    assert_eq!(specs.len(), 3);
+    assert_eq!(specs[0].name, "deepgram");
+    assert_eq!(specs[0].timeout, Some(Duration::from_millis(2500)));
+    assert_eq!(specs[0].fall_through, FallThrough::default());
+    assert_eq!(specs[1].name, "groq");
+    assert_eq!(specs[1].fall_through, FallThrough { on_error: true, on_empty: false, min_confidence: Some(0.6) });
+    assert_eq!(specs[2].fall_through, FallThrough { min_confidence: Some(0.3), ..FallThrough::default() });
+}
+
+#[test]
+fn rejects_bad_lines() {
+    for (content, expected) in [
+        ("groq timeout=0", "line 1: bad timeout"),
+        ("groq timeout=1e20", "bad timeout"),
+        ("groq timeout=-1", "bad timeout"),
+        ("groq timeout=inf", "bad timeout"),
+        ("deepgram\ngroq fallthrough", "line 2: expected key=value"),
+        ("groq fallthrough=error,sometimes", "unknown fallthrough condition"),
+        ("groq fallthrough=low-confidence", "needs min_confidence"),
+        ("groq fallthrough=error min_confidence=0.5", "needs low-confidence"),
+        ("groq min_confidence=2", "between 0 and 1"),
+        ("groq 5", "expected key=value"),
+    ] {
+        let err = parse_providers(content).unwrap_err();
+        assert!(err.contains(expected), "{:?}: {}", content, err);
+    }
+}
+
+#[test]
+fn default_chain_follows_keys() {
+    let names = |config: &Config| Chain::from_config(config).links().iter().map(|l| l.transcriber.name().to_string()).collect::<Vec<_>>();
+    let both = Config { deepgram_key: Some("d".into()), groq_key: Some("g".into()), ..Config::default() };
+    assert_eq!(names(&both), ["Deepgram", "Groq"]);
+    assert!(names(&Config::default()).is_empty());
+
+    // Explicit order wins; entries without a key are skipped
+    let providers = parse_providers("groq\ndeepgram\nwhisper").unwrap();
+    assert_eq!(names(&Config { providers: providers.clone(), ..both }), ["Groq", "Deepgram"]);
+    assert_eq!(names(&Config { providers, groq_key: Some("g".into()), ..Config::default() }), ["Groq"]);
+}
+
+#[test]
+fn invalid_providers_file_builds_no_chain() {
+    let providers_error = parse_providers("whisper fallthrough=eror").err();
+    assert!(providers_error.is_some());
+    let config = Config { deepgram_key: Some("d".into()), groq_key: Some("g".into()), providers_error, ..Config::default() };
+    assert!(Chain::from_config(&config).is_empty());
+}
+
+#[test]
+fn provider_options_are_checked_on_build() {
+    let config = Config { deepgram_key: Some("d".into()), groq_key: Some("g".into()), ..Config::default() };
+    let build = |line: &str| build_provider(&parse_providers(line).unwrap()[0], &config).map(|p| p.name().to_string());
+    assert_eq!(build("groq model=distil-whisper-large-v3-en name=GroqFast").unwrap(), "GroqFast");
+    assert_eq!(build("openai base_url=http://127.0.0.1:9/v1").unwrap(), "OpenAI");
+    assert_eq!(build("deepgram model=nova-2 language=de endpointing=800").unwrap(), "Deepgram");
+    assert!(build("deepgram model=whisper-large").unwrap_err().contains("unknown streaming model"));
+    assert!(build("deepgram retries=2").unwrap_err().contains("unknown option 'retries'"));
+    assert!(build("groq retries=2").unwrap_err().contains("unknown option 'retries'"));
+    assert!(build("openai base_url=localhost:8000").unwrap_err().contains("http(s)"));
+    assert!(build("nonsense").unwrap_err().contains("unknown provider"));
+}
+
+#[test]
+fn slow_provider_times_out_and_falls_through() {
+    let slow = Fake::slow("Slow", Duration::from_secs(2));
+    let fast = Fake::new("Fast", Ok(("hello", None)));
+    let chain = Chain::new(vec![
+        link(slow, Some(Duration::from_millis(100)), FallThrough::default()),
+        link(fast, None, FallThrough::default()),
+    ]);
+    let started = Instant::now();
+    let (result, fallbacks) = run(&chain);
+    assert_eq!(result.unwrap().text, "hello");
+    assert!(started.elapsed() < Duration::from_secs(1));
+    assert_eq!(fallbacks, [("Slow".to_string(), "Fast".to_string())]);
+}
+
+#[test]
+fn error_without_fallthrough_stops_the_chain() {
+    let first = Fake::new("First", Err(TranscribeError::Failed("HTTP 401".into())));
+    let second = Fake::new("Second", Ok(("unused", None)));
+    let no_error = FallThrough { on_error: false, ..FallThrough::default() };
+    let chain = Chain::new(vec![link(first, None, no_error), link(second.clone(), None, FallThrough::default())]);
+    assert_eq!(run(&chain).0.unwrap_err(), ["First"]);
+    assert_eq!(second.calls.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn empty_is_accepted_or_passed_on() {
+    let silent = Fake::new("Silent", Err(TranscribeError::Empty));
+    let other = Fake::new("Other", Ok(("something", None)));
+    let accept_empty = FallThrough { on_empty: false, ..FallThrough::default() };
+    let chain = Chain::new(vec![link(silent.clone(), None, accept_empty), link(other.clone(), None, FallThrough::default())]);
+    assert_eq!(run(&chain).0.unwrap().text, "");
+    assert_eq!(other.calls.load(Ordering::SeqCst), 0);
+
+    let chain: Chain = vec![silent as Arc<dyn Transcriber>, other].into();
+    assert_eq!(run(&chain).0.unwrap().text, "something");
+}
+
+#[test]
+fn low_confidence_falls_through_but_is_kept() {
+    let unsure = Fake::new("Unsure", Ok(("maybe", Some(0.4))));
+    let broken = Fake::new("Broken", Err(TranscribeError::Timeout));
+    let sure = Fake::new("Sure", Ok(("certainly", Some(0.95))));
+    let picky = FallThrough { min_confidence: Some(0.8), ..FallThrough::default() };
+
+    let chain = Chain::new(vec![link(unsure.clone(), None, picky.clone()), link(sure, None, FallThrough::default())]);
+    assert_eq!(run(&chain).0.unwrap().text, "certainly");
+
+    // Nothing better later on: the unsure text beats failing outright
+    let chain = Chain::new(vec![link(unsure, None, picky), link(broken, None, FallThrough::default())]);
+    let (result, fallbacks) = run(&chain);
+    assert_eq!(result.unwrap().text, "maybe");
+    assert_eq!(fallbacks.len(), 1);
+}
+
+#[test]
+fn stream_only_opens_on_the_first_link() {
+    let local = Fake::new("Local", Ok(("offline", None)));
+    let (segments, _) = mpsc::channel();
+
+    // A batch link ahead keeps the streaming link waiting for its turn
+    let chain: Chain = vec![local.clone() as Arc<dyn Transcriber>, Arc::new(Live)].into();
+    assert!(chain.start_stream(16000, segments.clone()).is_none());
+    assert_eq!(run(&chain).0.unwrap().text, "offline");
+
+    let chain: Chain = vec![Arc::new(Live) as Arc<dyn Transcriber>, local.clone()].into();
+    let (index, stream, _) = chain.start_stream(16000, segments).unwrap();
+    assert_eq!(index, 0);
+    let streamed = Some(Streamed { index, result: stream.finish(Duration::from_secs(1)), failures: Vec::new() });
+    assert_eq!(chain.run(streamed, &[0.1; 160], 16000, |_, _| {}).unwrap().text, "live");
+    assert_eq!(local.calls.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,14 @@
+use fnkey_core::clip::transcribe_clip;
+use fnkey_core::Config;
+
+#[test]
+fn no_backend_is_an_error() {
+    let err = transcribe_clip(&Config::default(), &[0.0; 1600], 16000).unwrap_err();
+    assert!(err.contains("no API key"));
+}
+
+#[test]
+fn empty_clip_is_an_error() {
+    let config = Config { groq_key: Some("unused".to_string()), ..Config::default() };
+    assert!(transcribe_clip(&config, &[], 16000).is_err());
+}
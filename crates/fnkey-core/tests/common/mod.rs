@@ -0,0 +1,46 @@
+//! Shared by the integration tests and the latency bench; each uses only
+//! some of it.
+#![allow(dead_code)]
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use fnkey_core::platform::{HotkeyCallback, Notification};
+use fnkey_core::Platform;
+
+/// Records what the session asks of the desktop instead of touching a real one
+#[derive(Default)]
+pub struct FakePlatform {
+    /// Every indicator change
+    pub indicator: Mutex<Vec<bool>>,
+    /// Text put on the clipboard, whether or not it was pasted
+    pub pasted: Mutex<Vec<String>>,
+    /// Paste keystrokes sent
+    pub pastes: AtomicUsize,
+    pub notifications: Mutex<Vec<Notification>>,
+    pub modifier_hotkey: bool,
+}
+
+impl Platform for FakePlatform {
+    fn run(&self, _on_key: HotkeyCallback) {}
+    fn set_recording(&self, recording: bool) {
+        self.indicator.lock().unwrap().push(recording);
+    }
+    fn show_notification(&self, notification: Notification) {
+        self.notifications.lock().unwrap().push(notification);
+    }
+    fn set_clipboard(&self, text: &str) -> bool {
+        self.pasted.lock().unwrap().push(text.to_string());
+        true
+    }
+    fn paste(&self) {
+        self.pastes.fetch_add(1, Ordering::SeqCst);
+    }
+    fn press_return(&self) {}
+    fn keycode_for_char(&self, _ch: char) -> Option<u16> {
+        None
+    }
+    fn hotkey_is_modifier(&self) -> bool {
+        self.modifier_hotkey
+    }
+}
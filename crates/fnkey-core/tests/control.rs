@@ -0,0 +1,112 @@
+mod common;
+
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use common::FakePlatform;
+use fnkey_core::control::spawn_server_at;
+use fnkey_core::{handle_hotkey, AppState, Config};
+use serde_json::{json, Value};
+
+struct Client {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+impl Client {
+    fn connect(path: &PathBuf) -> Self {
+        let stream = UnixStream::connect(path).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        Client { reader: BufReader::new(stream.try_clone().unwrap()), writer: stream }
+    }
+
+    fn send(&mut self, line: &str) {
+        writeln!(self.writer, "{}", line).unwrap();
+    }
+
+    fn recv(&mut self) -> Value {
+        let mut line = String::new();
+        self.reader.read_line(&mut line).unwrap();
+        serde_json::from_str(&line).unwrap()
+    }
+
+    fn call(&mut self, cmd: &str) -> Value {
+        self.send(&json!({ "cmd": cmd }).to_string());
+        self.recv()
+    }
+}
+
+fn socket(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("fnkey-ctl-{}-{}.sock", std::process::id(), name))
+}
+
+#[test]
+fn commands_drive_the_session() {
+    let platform = Arc::new(FakePlatform::default());
+    let state = AppState::new(platform.clone(), Config::default());
+    let path = socket("commands");
+    spawn_server_at(&state, &path).unwrap();
+    let mut client = Client::connect(&path);
+
+    assert_eq!(client.call("status"), json!({"ok": true, "recording": false, "transcribing": false}));
+    assert_eq!(client.call("start"), json!({"ok": true}));
+    assert!(state.is_recording());
+    // A second start, or the hotkey, doesn't restart a running session
+    client.call("start");
+    handle_hotkey(&state, true);
+    handle_hotkey(&state, false);
+    assert!(!state.is_recording());
+    assert_eq!(client.call("stop"), json!({"ok": true}));
+    assert_eq!(*platform.indicator.lock().unwrap(), vec![true, false]);
+
+    assert_eq!(client.call("last-transcript"), json!({"ok": true, "transcript": null}));
+    assert_eq!(client.call("bogus")["ok"], json!(false));
+    client.send("not json");
+    assert_eq!(client.recv()["ok"], json!(false));
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn subscribers_see_session_events() {
+    let state = AppState::new(Arc::new(FakePlatform::default()), Config::default());
+    let path = socket("events");
+    spawn_server_at(&state, &path).unwrap();
+
+    let mut events = Client::connect(&path);
+    assert_eq!(events.call("subscribe"), json!({"ok": true}));
+
+    let mut control = Client::connect(&path);
+    control.call("start");
+    control.call("cancel");
+    control.call("start");
+    control.call("stop");
+    // Cancel when idle is a no-op
+    control.call("cancel");
+
+    assert_eq!(events.recv(), json!({"event": "recording-started"}));
+    assert_eq!(events.recv(), json!({"event": "cancelled"}));
+    assert_eq!(events.recv(), json!({"event": "recording-started"}));
+    assert_eq!(events.recv(), json!({"event": "recording-stopped"}));
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn second_server_on_same_path_is_refused() {
+    let state = AppState::new(Arc::new(FakePlatform::default()), Config::default());
+    let path = socket("busy");
+    spawn_server_at(&state, &path).unwrap();
+    assert!(spawn_server_at(&state, &path).is_err());
+
+    // A stale socket file with nobody listening is replaced
+    let stale = socket("stale");
+    drop(std::os::unix::net::UnixListener::bind(&stale).unwrap());
+    spawn_server_at(&state, &stale).unwrap();
+
+    let _ = std::fs::remove_file(&path);
+    let _ = std::fs::remove_file(&stale);
+}
@@ -0,0 +1,480 @@
+use std::net::{Shutdown, TcpListener};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use fnkey_core::audio::PcmFormat;
+use fnkey_core::deepgram::{
+    parse_update, DeepgramOptions, DeepgramTranscriber, DgMessage, DgSpeechStarted, DgTranscript, DgUtteranceEnd,
+    DeepgramPool, MAX_RECONNECTS,
+};
+use fnkey_core::pipe::{run_pipe, PipeOptions};
+use fnkey_core::transcriber::{Segment, TranscribeError, Transcriber};
+use fnkey_core::Config;
+use tungstenite::handshake::server::{Request, Response};
+use tungstenite::Message;
+
+const INTERIM: &str = r#"{"type":"Results","start":1.5,"duration":0.8,"is_final":false,
+    "channel":{"alternatives":[{"transcript":"hello wor","confidence":0.9}]}}"#;
+const FINAL: &str = r#"{"type":"Results","start":1.5,"duration":1.1,"is_final":true,
+    "channel":{"alternatives":[{"transcript":"hello world","confidence":0.98}]}}"#;
+
+#[test]
+fn results_become_updates() {
+    assert_eq!(
+        parse_update(INTERIM),
+        Some(Segment { transcript: "hello wor".to_string(), is_final: false, start: 1.5, duration: 0.8 })
+    );
+    assert!(parse_update(FINAL).unwrap().is_final);
+    assert_eq!(parse_update(r#"{"type":"Metadata","request_id":"x"}"#), None);
+    assert_eq!(parse_update(r#"{"is_final":true,"channel":{"alternatives":[{"transcript":""}]}}"#), None);
+}
+
+#[test]
+fn only_finals_are_accumulated() {
+    let mut dg = DgTranscript::default();
+    for msg in [INTERIM, FINAL, FINAL] {
+        dg.handle(msg);
+    }
+    let transcript = dg.transcript();
+    assert_eq!(transcript.text, "hello world hello world");
+    assert_eq!(transcript.provider, "Deepgram");
+    assert_eq!(transcript.confidence, Some(0.98));
+    assert_eq!(dg.messages, 3);
+}
+
+const WORDS: &str = r#"{"type":"Results","start":0.0,"duration":1.2,"is_final":true,"speech_final":true,"from_finalize":false,
+    "channel":{"alternatives":[{"transcript":"Hi, FnKey.","confidence":0.9,"words":[
+        {"word":"hi","start":0.1,"end":0.3,"confidence":0.95,"punctuated_word":"Hi,"},
+        {"word":"fnkey","start":0.4,"end":0.9,"confidence":0.75,"punctuated_word":"FnKey."}]}]},
+    "metadata":{"request_id":"r1","model_info":{"name":"general-nova-3"}}}"#;
+
+#[test]
+fn every_message_type_parses() {
+    let DgMessage::Results(results) = DgMessage::parse(WORDS).unwrap() else { panic!() };
+    assert!(results.is_final && results.speech_final && !results.from_finalize);
+    assert_eq!(results.channel.alternatives[0].words[1].punctuated_word.as_deref(), Some("FnKey."));
+
+    let metadata = r#"{"type":"Metadata","transaction_key":"deprecated","request_id":"r1","sha256":"x","created":"2025-01-01T00:00:00Z","duration":3.5,"channels":1,"models":[],"model_info":{}}"#;
+    assert!(matches!(DgMessage::parse(metadata).unwrap(), DgMessage::Metadata(m) if m.request_id == "r1" && m.duration == 3.5));
+    let end = r#"{"type":"UtteranceEnd","channel":[0,1],"last_word_end":2.4}"#;
+    assert_eq!(DgMessage::parse(end).unwrap(), DgMessage::UtteranceEnd(DgUtteranceEnd { last_word_end: 2.4 }));
+    let started = r#"{"type":"SpeechStarted","channel":[0],"timestamp":0.6}"#;
+    assert_eq!(DgMessage::parse(started).unwrap(), DgMessage::SpeechStarted(DgSpeechStarted { timestamp: 0.6 }));
+
+    let error = r#"{"type":"Error","description":"Failed to decode audio","message":"","variant":"DATA-0000"}"#;
+    let DgMessage::Error(error) = DgMessage::parse(error).unwrap() else { panic!() };
+    assert_eq!(error.to_string(), "Failed to decode audio (DATA-0000)");
+    let legacy = r#"{"err_code":"INVALID_AUTH","err_msg":"Invalid credentials.","request_id":"r2"}"#;
+    let DgMessage::Error(legacy) = DgMessage::parse(legacy).unwrap() else { panic!() };
+    assert_eq!(legacy.to_string(), "Invalid credentials. (INVALID_AUTH)");
+
+    assert_eq!(DgMessage::parse(r#"{"type":"Warning","description":"x"}"#).unwrap(), DgMessage::Unknown("Warning".to_string()));
+    assert!(DgMessage::parse(r#"{"type":"Results"}"#).unwrap_err().contains("bad Results message"));
+    assert!(DgMessage::parse("not json").is_err());
+}
+
+#[test]
+fn words_and_errors_are_kept() {
+    let mut dg = DgTranscript::default();
+    assert!(dg.handle(WORDS).unwrap().is_final);
+    for _ in 0..3 {
+        assert_eq!(dg.handle(r#"{"type":"Warning"}"#), None);
+    }
+    dg.handle(r#"{"type":"Metadata","request_id":"r1"}"#);
+    assert_eq!(dg.request_id(), Some("r1"));
+    assert_eq!(dg.error(), None);
+    dg.handle(r#"{"type":"Error","description":"Too much audio","variant":"NET-0001"}"#);
+    assert_eq!(dg.error(), Some("Too much audio (NET-0001)"));
+
+    let transcript = dg.transcript();
+    assert_eq!(transcript.text, "Hi, FnKey.");
+    let words: Vec<(&str, f64, f64, Option<f32>)> = transcript.words.iter().map(|w| (w.word.as_str(), w.start, w.end, w.confidence)).collect();
+    assert_eq!(words, [("Hi,", 0.1, 0.3, Some(0.95)), ("FnKey.", 0.4, 0.9, Some(0.75))]);
+    assert!((transcript.confidence.unwrap() - 0.85).abs() < 1e-6);
+}
+
+#[test]
+fn default_query_matches_the_old_url() {
+    let query = DeepgramOptions::default().query(&["FnKey".to_string(), "Claude Code".to_string()]);
+    assert_eq!(
+        query,
+        "encoding=linear16&sample_rate=16000&channels=1&interim_results=true&model=nova-3&language=multi\
+         &endpointing=300&punctuate=true&smart_format=true&keyterm=FnKey&keyterm=Claude%20Code"
+    );
+}
+
+#[test]
+fn options_file_sets_query_parameters() {
+    let options = DeepgramOptions::parse(
+        "# slow speakers\nmodel=nova-2-meeting language=de-CH endpointing=1200\n\
+         filler_words=true numerals=true profanity_filter=true\nredact=pci,ssn dictation=true utterance_end_ms=1500\n",
+    )
+    .unwrap();
+    let query = options.query(&["Zürich".to_string()]);
+    for param in [
+        "model=nova-2-meeting", "language=de-CH", "endpointing=1200", "filler_words=true", "numerals=true",
+        "profanity_filter=true", "redact=pci&redact=ssn", "dictation=true", "utterance_end_ms=1500",
+        // Older models boost keywords rather than keyterms
+        "keywords=Z%C3%BCrich",
+    ] {
+        assert!(query.contains(param), "{} missing from {}", param, query);
+    }
+    assert!(!query.contains("keyterm"));
+
+    let mut off = DeepgramOptions::default();
+    off.configure(&[("endpointing".to_string(), "false".to_string()), ("smart_format".to_string(), "false".to_string())]).unwrap();
+    let query = off.query(&[]);
+    assert!(query.contains("endpointing=false") && !query.contains("smart_format"), "{}", query);
+}
+
+#[test]
+fn options_are_validated() {
+    let err = |content: &str| DeepgramOptions::parse(content).unwrap_err();
+    assert!(err("model=whisper-medium").contains("unknown streaming model"));
+    assert!(err("model=enhanced").contains("language=multi needs"));
+    assert!(err("language=English").contains("bad language"));
+    assert!(err("endpointing=soon").contains("bad endpointing"));
+    assert!(err("utterance_end_ms=500").contains("at least 1000"));
+    assert!(err("smart_format=yes").contains("true or false"));
+    assert!(err("redact=names").contains("unknown redact"));
+    assert!(err("dictation=true punctuate=false").contains("needs punctuate"));
+    assert!(err("tier=fast").contains("unknown option"));
+    assert!(err("language").contains("line 1"));
+    assert!(err("prewarm=0").contains("bad prewarm"));
+    assert_eq!(DeepgramOptions::parse("model=enhanced-general language=en").unwrap().model, "enhanced-general");
+}
+
+/// What the mock server saw
+struct Seen {
+    uri: String,
+    headers: Vec<(String, String)>,
+    /// Sizes of the audio frames, in order
+    frames: Vec<usize>,
+}
+
+impl Seen {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+    }
+}
+
+/// Accept one Listen-API connection, take audio until CloseStream, answer
+/// with `replies` and close. Returns a ws:// base URL.
+// The handshake callback's error type is tungstenite's
+#[allow(clippy::result_large_err)]
+fn mock_deepgram(replies: Vec<&'static str>) -> (String, mpsc::Receiver<Seen>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = format!("ws://{}", listener.local_addr().unwrap());
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut seen = Seen { uri: String::new(), headers: Vec::new(), frames: Vec::new() };
+        let mut ws = tungstenite::accept_hdr(stream, |request: &Request, response: Response| {
+            seen.uri = request.uri().to_string();
+            seen.headers = request.headers().iter().map(|(n, v)| (n.to_string(), v.to_str().unwrap().to_string())).collect();
+            Ok(response)
+        })
+        .unwrap();
+        while let Ok(message) = ws.read() {
+            match message {
+                Message::Binary(audio) => seen.frames.push(audio.len()),
+                Message::Text(text) if text.contains("CloseStream") => break,
+                _ => {}
+            }
+        }
+        for reply in replies {
+            ws.send(Message::Text(reply.to_string())).unwrap();
+        }
+        let _ = ws.close(None);
+        while ws.read().is_ok() {}
+        let _ = tx.send(seen);
+    });
+    (base_url, rx)
+}
+
+/// Listen-API server whose first `drops` connections take `drop_after` bytes
+/// of audio, send `partial` (if any) and vanish without a close handshake;
+/// later ones answer CloseStream with `last`. Reports the audio bytes each
+/// connection received, as it finishes.
+fn mock_flaky(drops: usize, drop_after: usize, partial: Option<&'static str>, last: &'static str) -> (String, mpsc::Receiver<usize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = format!("ws://{}", listener.local_addr().unwrap());
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for (n, stream) in listener.incoming().enumerate() {
+            let Ok(stream) = stream else { return };
+            let tx = tx.clone();
+            thread::spawn(move || {
+                let mut ws = tungstenite::accept(stream.try_clone().unwrap()).unwrap();
+                let dropping = n < drops;
+                let mut received = 0;
+                while !(dropping && received >= drop_after) {
+                    match ws.read() {
+                        Ok(Message::Binary(audio)) => received += audio.len(),
+                        Ok(Message::Text(text)) if text.contains("CloseStream") => break,
+                        Ok(_) => {}
+                        Err(_) => break,
+                    }
+                }
+                if dropping {
+                    if let Some(partial) = partial {
+                        ws.send(Message::Text(partial.to_string())).unwrap();
+                    }
+                    let _ = stream.shutdown(Shutdown::Write);
+                } else {
+                    ws.send(Message::Text(last.to_string())).unwrap();
+                    let _ = ws.close(None);
+                }
+                while ws.read().is_ok() {}
+                let _ = tx.send(received);
+            });
+        }
+    });
+    (base_url, rx)
+}
+
+fn options(settings: &[(&str, &str)]) -> DeepgramOptions {
+    let mut options = DeepgramOptions::default();
+    let settings: Vec<(String, String)> = settings.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+    options.configure(&settings).unwrap();
+    options
+}
+
+#[test]
+fn streams_to_a_configured_endpoint() {
+    let (base_url, seen) = mock_deepgram(vec![INTERIM, FINAL]);
+    let options = options(&[("base_url", &base_url), ("header.X-Team", "dictation"), ("language", "en")]);
+    let deepgram = DeepgramTranscriber::new("dg-key".to_string(), vec!["FnKey".to_string()]).with_options(options);
+
+    let (segments_tx, segments) = mpsc::channel();
+    let mut stream = deepgram.start_stream(16000, Some(segments_tx)).unwrap();
+    // 110ms in 20ms pushes, sent as 50ms frames and the remainder
+    for _ in 0..5 {
+        stream.push_audio(&[0.2; 320]);
+    }
+    stream.push_audio(&[0.2; 160]);
+    let transcript = stream.finish(Duration::from_secs(5)).unwrap();
+    assert_eq!((transcript.text.as_str(), transcript.provider.as_str()), ("hello world", "Deepgram"));
+    assert_eq!(segments.try_iter().count(), 2);
+
+    let seen = seen.recv().unwrap();
+    assert!(seen.uri.starts_with("/v1/listen?encoding=linear16&sample_rate=16000"), "{}", seen.uri);
+    assert!(seen.uri.contains("&language=en&") && seen.uri.ends_with("&keyterm=FnKey"), "{}", seen.uri);
+    assert_eq!(seen.header("authorization"), Some("Token dg-key"));
+    assert_eq!(seen.header("x-team"), Some("dictation"));
+    assert_eq!(seen.frames, [1600, 1600, 320]);
+}
+
+#[test]
+fn self_hosted_server_needs_no_key() {
+    let (base_url, seen) = mock_deepgram(vec![FINAL]);
+    let config = Config { deepgram: options(&[("base_url", &format!("{}/", base_url))]), ..Config::default() };
+    assert!(config.has_backend());
+    let key = config.deepgram_auth().unwrap();
+    let deepgram = DeepgramTranscriber::new(key, Vec::new()).with_options(config.deepgram.clone());
+    assert_eq!(deepgram.transcribe(&[0.2; 16000], 16000).unwrap().text, "hello world");
+    assert_eq!(seen.recv().unwrap().header("authorization"), None);
+
+    // Nothing listening
+    let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let deepgram = DeepgramTranscriber::new(String::new(), Vec::new()).with_options(options(&[("base_url", &format!("ws://127.0.0.1:{}", port))]));
+    assert!(matches!(deepgram.transcribe(&[0.2; 1600], 16000), Err(TranscribeError::Failed(msg)) if msg.contains("connect failed")));
+
+    // A live stream says so while the key is still held
+    let mut stream = deepgram.start_stream(16000, None).unwrap();
+    stream.push_audio(&[0.2; 320]);
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !stream.failed() {
+        assert!(Instant::now() < deadline, "timed out");
+        thread::sleep(Duration::from_millis(5));
+    }
+    assert!(matches!(stream.finish(Duration::ZERO), Err(TranscribeError::Failed(msg)) if msg.contains("connect failed")));
+}
+
+#[test]
+fn endpoint_options_are_validated() {
+    let err = |key: &str, value: &str| {
+        let mut options = DeepgramOptions::default();
+        options.configure(&[(key.to_string(), value.to_string())]).unwrap_err()
+    };
+    assert!(err("base_url", "https://deepgram.internal").contains("ws://"));
+    assert!(err("header.Bad Name", "x").contains("bad header name"));
+    assert!(err("ca_bundle", "/nonexistent/ca.pem").contains("can't read CA bundle"));
+
+    let not_pem = std::env::temp_dir().join(format!("fnkey-ca-{}.pem", std::process::id()));
+    std::fs::write(&not_pem, "not a certificate\n").unwrap();
+    assert!(err("ca_bundle", not_pem.to_str().unwrap()).contains("no certificates"));
+    let _ = std::fs::remove_file(&not_pem);
+    assert!(!DeepgramOptions::default().is_self_hosted());
+}
+
+#[test]
+fn server_errors_fail_the_stream() {
+    let (base_url, _seen) = mock_deepgram(vec![r#"{"type":"Error","description":"Failed to decode audio","variant":"DATA-0000"}"#]);
+    let deepgram = DeepgramTranscriber::new("k".to_string(), Vec::new()).with_options(options(&[("base_url", &base_url)]));
+    match deepgram.transcribe(&[0.2; 1600], 16000) {
+        Err(TranscribeError::Failed(msg)) => assert!(msg.contains("Failed to decode audio (DATA-0000)"), "{}", msg),
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn pipe_done_line_carries_words() {
+    let (base_url, _seen) = mock_deepgram(vec![WORDS]);
+    let config = Config { deepgram: options(&[("base_url", &base_url)]), ..Config::default() };
+    let opts = PipeOptions { sample_rate: 16000, format: PcmFormat::S16Le, channels: 1 };
+    let mut out = Vec::new();
+    assert_eq!(run_pipe(&config, opts, &[0u8; 3200][..], &mut out).unwrap(), "Hi, FnKey.");
+    let out = String::from_utf8(out).unwrap();
+    let done: serde_json::Value = serde_json::from_str(out.lines().last().unwrap()).unwrap();
+    assert_eq!(done["type"], "done");
+    assert_eq!(done["words"][1]["word"], "FnKey.");
+    assert_eq!(done["words"][1]["start"], 0.4);
+}
+
+#[test]
+fn long_clips_are_sent_whole_in_paced_frames() {
+    let (base_url, seen) = mock_deepgram(vec![FINAL]);
+    let deepgram = DeepgramTranscriber::new("k".to_string(), Vec::new()).with_options(options(&[("base_url", &base_url), ("chunk_ms", "100")]));
+    // 30s, more than the send queue holds at once
+    assert_eq!(deepgram.transcribe(&vec![0.2; 16000 * 30], 16000).unwrap().text, "hello world");
+    let frames = seen.recv().unwrap().frames;
+    assert_eq!(frames.len(), 300);
+    assert!(frames.iter().all(|&len| len == 3200));
+
+    let mut options = DeepgramOptions::default();
+    assert!(options.configure(&[("chunk_ms".to_string(), "10".to_string())]).unwrap_err().contains("20-100"));
+}
+
+const FIRST_SECOND: &str = r#"{"type":"Results","start":0.0,"duration":1.0,"is_final":true,
+    "channel":{"alternatives":[{"transcript":"first part","confidence":0.9,
+    "words":[{"word":"first","start":0.2,"end":0.5,"confidence":0.9}]}]}}"#;
+const AFTER_RECONNECT: &str = r#"{"type":"Results","start":0.0,"duration":2.0,"is_final":true,
+    "channel":{"alternatives":[{"transcript":"second part","confidence":0.9,
+    "words":[{"word":"second","start":0.3,"end":0.6,"confidence":0.9}]}]}}"#;
+
+#[test]
+fn dropped_stream_reconnects_and_replays_from_last_final() {
+    // The first connection finalizes 1s, then drops once 2s are in
+    let (base_url, received) = mock_flaky(1, 64000, Some(FIRST_SECOND), AFTER_RECONNECT);
+    let deepgram = DeepgramTranscriber::new("k".to_string(), Vec::new()).with_options(options(&[("base_url", &base_url)]));
+    let transcript = deepgram.transcribe(&[0.2; 16000 * 3], 16000).unwrap();
+    assert_eq!(transcript.text, "first part second part");
+    // The second connection's clock starts at the replay point
+    let words: Vec<(&str, f64)> = transcript.words.iter().map(|w| (w.word.as_str(), w.start)).collect();
+    assert_eq!(words, [("first", 0.2), ("second", 1.3)]);
+
+    // The dropped connection is let go once its replacement is up. Everything
+    // after the first final is sent again, and nothing before it.
+    let received = || received.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert!(received() >= 64000);
+    assert_eq!(received(), 64000);
+}
+
+#[test]
+fn stream_fails_once_reconnects_run_out() {
+    let (base_url, received) = mock_flaky(usize::MAX, 0, None, FINAL);
+    let deepgram = DeepgramTranscriber::new("k".to_string(), Vec::new()).with_options(options(&[("base_url", &base_url)]));
+    match deepgram.transcribe(&[0.2; 16000], 16000) {
+        Err(TranscribeError::Failed(msg)) => assert!(msg.contains("gave up after 3 reconnects"), "{}", msg),
+        other => panic!("unexpected {:?}", other),
+    }
+    for _ in 0..=MAX_RECONNECTS {
+        received.recv_timeout(Duration::from_secs(5)).unwrap();
+    }
+}
+
+/// Listen-API server that answers Finalize the way Deepgram does: a final
+/// from the previous one to the end of the audio, on the connection's own
+/// clock. Logs "connect" and the type of every control message.
+fn mock_pooled() -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = format!("ws://{}", listener.local_addr().unwrap());
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let log = Arc::clone(&events);
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let log = Arc::clone(&log);
+            thread::spawn(move || {
+                let mut ws = tungstenite::accept(stream).unwrap();
+                log.lock().unwrap().push("connect".to_string());
+                let (mut received, mut finalized, mut answered) = (0, 0.0, 0);
+                while let Ok(message) = ws.read() {
+                    let text = match message {
+                        Message::Binary(audio) => {
+                            received += audio.len();
+                            continue;
+                        }
+                        Message::Text(text) => text,
+                        _ => continue,
+                    };
+                    let kind = serde_json::from_str::<serde_json::Value>(&text).unwrap()["type"].as_str().unwrap().to_string();
+                    log.lock().unwrap().push(kind.clone());
+                    match kind.as_str() {
+                        "Finalize" => {
+                            answered += 1;
+                            let end = received as f64 / 32000.0;
+                            let result = serde_json::json!({
+                                "type": "Results", "start": finalized, "duration": end - finalized,
+                                "is_final": true, "from_finalize": true,
+                                "channel": { "alternatives": [{
+                                    "transcript": format!("session {}", answered), "confidence": 0.9,
+                                    "words": [{ "word": "session", "start": finalized + 0.1, "end": finalized + 0.4, "confidence": 0.9 }],
+                                }]},
+                            });
+                            finalized = end;
+                            ws.send(Message::Text(result.to_string())).unwrap();
+                        }
+                        "CloseStream" => break,
+                        _ => {}
+                    }
+                }
+                let _ = ws.close(None);
+                while ws.read().is_ok() {}
+            });
+        }
+    });
+    (base_url, events)
+}
+
+fn wait_until(done: impl Fn() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !done() {
+        assert!(Instant::now() < deadline, "timed out");
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[test]
+fn prewarmed_connection_is_kept_alive_and_reused() {
+    let (base_url, events) = mock_pooled();
+    let seen = |kind: &str| events.lock().unwrap().iter().filter(|e| *e == kind).count();
+    let options = options(&[("base_url", &base_url), ("prewarm", "60")]);
+    let pool = DeepgramPool::start("k".to_string(), Vec::new(), options.clone(), Duration::from_millis(20));
+    wait_until(|| seen("KeepAlive") >= 2);
+    assert!(pool.is_warm());
+
+    let deepgram = DeepgramTranscriber::new("k".to_string(), Vec::new()).with_options(options).with_pool(Arc::clone(&pool));
+    for n in 1..=2 {
+        let transcript = deepgram.transcribe(&[0.2; 16000], 16000).unwrap();
+        assert_eq!(transcript.text, format!("session {}", n));
+        // Times are the session's own, though the connection's clock runs on
+        assert!((transcript.words[0].start - 0.1).abs() < 1e-9, "{:?}", transcript.words);
+    }
+    assert_eq!((seen("connect"), seen("Finalize"), seen("CloseStream")), (1, 2, 0));
+    assert!(pool.is_warm());
+}
+
+#[test]
+fn idle_prewarmed_connection_is_closed() {
+    let (base_url, events) = mock_pooled();
+    let seen = |kind: &str| events.lock().unwrap().iter().filter(|e| *e == kind).count();
+    let options = options(&[("base_url", &base_url), ("prewarm", "1")]);
+    let pool = DeepgramPool::start("k".to_string(), Vec::new(), options, Duration::from_millis(20));
+    wait_until(|| seen("CloseStream") == 1);
+    assert!(!pool.is_warm());
+    // Not reopened until a session uses the pool again
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(seen("connect"), 1);
+}
@@ -0,0 +1,149 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::mpsc;
+use std::thread;
+
+use fnkey_core::openai::OpenAiTranscriber;
+use fnkey_core::transcriber::{TranscribeError, Transcriber};
+
+/// What the mock server saw
+struct Request {
+    request_line: String,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+    }
+
+    /// Value of a multipart text field
+    fn field(&self, name: &str) -> Option<&str> {
+        let marker = format!("name=\"{}\"\r\n\r\n", name);
+        let start = self.body.find(&marker)? + marker.len();
+        let len = self.body[start..].find("\r\n")?;
+        Some(&self.body[start..start + len])
+    }
+}
+
+/// Answer one HTTP request with `status` and `body`; returns the server's
+/// base URL and where the request will be reported.
+fn mock_server(status: u16, body: &'static str) -> (String, mpsc::Receiver<Request>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = format!("http://{}/v1/", listener.local_addr().unwrap());
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).unwrap();
+        let mut headers = Vec::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            let (name, value) = line.split_once(':').unwrap();
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+        let len: usize = headers.iter().find(|(n, _)| n.eq_ignore_ascii_case("content-length")).map_or(0, |(_, v)| v.parse().unwrap());
+        let mut received = vec![0; len];
+        reader.read_exact(&mut received).unwrap();
+
+        let mut stream = stream;
+        write!(stream, "HTTP/1.1 {} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, body.len(), body).unwrap();
+        let body = String::from_utf8_lossy(&received).into_owned();
+        let _ = tx.send(Request { request_line: request_line.trim_end().to_string(), headers, body });
+    });
+    (base_url, rx)
+}
+
+fn clip() -> Vec<f32> {
+    (0..16000).map(|i| (i as f32 * 0.05).sin() * 0.3).collect()
+}
+
+#[test]
+fn posts_openai_style_multipart() {
+    let (base_url, requests) = mock_server(200, r#"{"text":" hello from the mock "}"#);
+    let transcriber = OpenAiTranscriber::new("Local", base_url, "Systran/faster-whisper-small")
+        .with_key("sk-test")
+        .with_keywords(vec!["FnKey".to_string(), "Deepgram".to_string()])
+        .with_field("language", "en");
+
+    let transcript = transcriber.transcribe(&clip(), 16000).unwrap();
+    assert_eq!(transcript.text, "hello from the mock");
+    assert_eq!(transcript.provider, "Local");
+
+    let request = requests.recv().unwrap();
+    assert_eq!(request.request_line, "POST /v1/audio/transcriptions HTTP/1.1");
+    assert_eq!(request.header("authorization"), Some("Bearer sk-test"));
+    assert!(request.header("content-type").unwrap().starts_with("multipart/form-data"));
+    assert_eq!(request.field("model"), Some("Systran/faster-whisper-small"));
+    assert_eq!(request.field("response_format"), Some("json"));
+    assert_eq!(request.field("prompt"), Some("FnKey, Deepgram"));
+    assert_eq!(request.field("language"), Some("en"));
+    assert!(request.body.contains("filename=\"audio.wav\""));
+    assert!(request.body.contains("RIFF"));
+}
+
+#[test]
+fn custom_auth_header_and_plain_text_response() {
+    let (base_url, requests) = mock_server(200, "plain words\n");
+    let options = [
+        ("auth_header".to_string(), "X-Api-Key".to_string()),
+        ("field.response_format".to_string(), "text".to_string()),
+    ];
+    let transcriber = OpenAiTranscriber::new("Local", base_url, "whisper-1").with_key("secret").configure(&options).unwrap();
+
+    assert_eq!(transcriber.transcribe(&clip(), 16000).unwrap().text, "plain words");
+    let request = requests.recv().unwrap();
+    assert_eq!(request.header("x-api-key"), Some("secret"));
+    assert_eq!(request.header("authorization"), None);
+    assert_eq!(request.field("response_format"), Some("text"));
+    assert_eq!(request.body.matches("name=\"response_format\"").count(), 1);
+}
+
+#[test]
+fn text_response_is_never_read_as_json() {
+    let (base_url, _requests) = mock_server(200, "42");
+    let transcriber = OpenAiTranscriber::new("Local", base_url, "whisper-1").with_field("response_format", "text");
+    assert_eq!(transcriber.transcribe(&clip(), 16000).unwrap().text, "42");
+
+    let (base_url, _requests) = mock_server(200, "plain words");
+    match OpenAiTranscriber::new("Local", base_url, "whisper-1").transcribe(&clip(), 16000) {
+        Err(TranscribeError::Failed(msg)) => assert!(msg.contains("bad JSON"), "{}", msg),
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn no_key_means_no_auth() {
+    let (base_url, requests) = mock_server(200, r#"{"text":"local"}"#);
+    let transcriber = OpenAiTranscriber::new("whisper.cpp", base_url, "whisper-1");
+    assert_eq!(transcriber.transcribe(&clip(), 16000).unwrap().text, "local");
+    assert_eq!(requests.recv().unwrap().header("authorization"), None);
+}
+
+#[test]
+fn http_errors_and_empty_text() {
+    let (base_url, _requests) = mock_server(401, r#"{"error":{"message":"Invalid API key"}}"#);
+    match OpenAiTranscriber::new("OpenAI", base_url, "whisper-1").transcribe(&clip(), 16000) {
+        Err(TranscribeError::Failed(msg)) => assert!(msg.contains("HTTP 401") && msg.contains("Invalid API key"), "{}", msg),
+        other => panic!("unexpected {:?}", other),
+    }
+
+    let (base_url, _requests) = mock_server(200, r#"{"text":"  "}"#);
+    let result = OpenAiTranscriber::new("OpenAI", base_url, "whisper-1").transcribe(&clip(), 16000);
+    assert_eq!(result.unwrap_err(), TranscribeError::Empty);
+}
+
+#[test]
+fn unreachable_server_fails() {
+    // Grab a free port and close it again so nothing is listening
+    let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let transcriber = OpenAiTranscriber::new("Local", format!("http://127.0.0.1:{}/v1", port), "whisper-1");
+    assert!(matches!(transcriber.transcribe(&clip(), 16000), Err(TranscribeError::Failed(_))));
+}
@@ -0,0 +1,12 @@
+use fnkey_core::audio::PcmFormat;
+use fnkey_core::pipe::{run_pipe, PipeOptions};
+use fnkey_core::Config;
+
+#[test]
+fn stream_needs_a_deepgram_key() {
+    let opts = PipeOptions { sample_rate: 16000, format: PcmFormat::S16Le, channels: 1 };
+    let mut out = Vec::new();
+    let err = run_pipe(&Config::default(), opts, &[0u8; 320][..], &mut out).unwrap_err();
+    assert!(err.contains("Deepgram key"));
+    assert!(out.is_empty());
+}
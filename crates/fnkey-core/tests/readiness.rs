@@ -0,0 +1,41 @@
+use std::io::Write;
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use fnkey_core::readiness::{self, Ready};
+
+fn socket_pair() -> (TcpStream, TcpStream) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (server, _) = listener.accept().unwrap();
+    (client, server)
+}
+
+#[test]
+fn wakes_for_data_waker_and_timeout() {
+    let (client, mut server) = socket_pair();
+    let (waker, wakeups) = readiness::channel().unwrap();
+
+    // Nothing to read and no room asked for: the timeout ends the wait
+    let started = Instant::now();
+    let ready = readiness::wait(&client, &wakeups, false, Some(Duration::from_millis(50))).unwrap();
+    assert_eq!(ready, Ready::default());
+    assert!(started.elapsed() >= Duration::from_millis(50));
+
+    // An idle socket is writable at once
+    assert!(readiness::wait(&client, &wakeups, true, None).unwrap().writable);
+
+    // Another thread's wake-up ends an unbounded wait, and is consumed by it
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(20));
+        waker.wake();
+    });
+    let ready = readiness::wait(&client, &wakeups, false, None).unwrap();
+    assert!(ready.woken && !ready.readable);
+    let ready = readiness::wait(&client, &wakeups, false, Some(Duration::ZERO)).unwrap();
+    assert!(!ready.woken);
+
+    server.write_all(b"x").unwrap();
+    assert!(readiness::wait(&client, &wakeups, false, None).unwrap().readable);
+}
@@ -0,0 +1,101 @@
+use std::io::{self, Read, Write};
+
+use fnkey_core::send_queue::SendQueue;
+use tungstenite::protocol::Role;
+use tungstenite::WebSocket;
+
+/// Socket that takes `budget` bytes and then reports WouldBlock
+struct Throttled {
+    written: Vec<u8>,
+    budget: usize,
+}
+
+impl Read for Throttled {
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        Err(io::ErrorKind::WouldBlock.into())
+    }
+}
+
+impl Write for Throttled {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.budget == 0 {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        let n = buf.len().min(self.budget);
+        self.budget -= n;
+        self.written.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn socket(budget: usize) -> WebSocket<Throttled> {
+    WebSocket::from_raw_socket(Throttled { written: Vec::new(), budget }, Role::Client, None)
+}
+
+#[test]
+fn stalls_resume_without_losing_audio() {
+    // 50ms chunks; a client frame adds 8 bytes (header, length, mask)
+    let mut queue = SendQueue::new(1600, 320_000);
+    let mut ws = socket(4000);
+    // 1s of audio in 20ms pieces
+    for _ in 0..50 {
+        queue.push(&[7; 640]);
+    }
+    assert_eq!(queue.queued_bytes(), 32000);
+
+    queue.pump(&mut ws).unwrap();
+    assert_eq!(queue.stats.stalls, 1);
+    assert!(!queue.is_empty());
+
+    // Still backed up: nothing more goes out
+    let sent = queue.stats.frames_sent;
+    queue.pump(&mut ws).unwrap();
+    assert_eq!(queue.stats.frames_sent, sent);
+
+    ws.get_mut().budget = usize::MAX;
+    queue.pump(&mut ws).unwrap();
+    assert!(queue.is_empty());
+    assert_eq!(queue.stats.frames_sent, 20);
+    assert_eq!(queue.stats.bytes_sent, 32000);
+    assert_eq!(queue.stats.dropped_frames, 0);
+    assert_eq!(ws.get_ref().written.len(), 20 * 1608);
+    assert_eq!(queue.stats.summary(32), "20 chunks/31KB sent, 1 stalls");
+}
+
+#[test]
+fn partial_chunk_waits_for_flush() {
+    let mut queue = SendQueue::new(1600, 320_000);
+    let mut ws = socket(usize::MAX);
+    queue.push(&[1; 2000]);
+    queue.pump(&mut ws).unwrap();
+    assert_eq!((queue.stats.frames_sent, queue.queued_bytes()), (1, 400));
+    queue.flush_partial();
+    queue.pump(&mut ws).unwrap();
+    assert_eq!((queue.stats.frames_sent, queue.stats.bytes_sent), (2, 2000));
+    assert!(queue.is_empty());
+
+    // Audio that will be sent again elsewhere isn't lost
+    queue.push(&[1; 5000]);
+    queue.clear();
+    assert!(queue.is_empty());
+    assert_eq!(queue.stats.dropped_frames, 0);
+}
+
+#[test]
+fn overflow_and_shutdown_are_counted() {
+    // Room for three chunks
+    let mut queue = SendQueue::new(1600, 4800);
+    queue.push(&[0; 1600 * 5]);
+    assert_eq!((queue.stats.dropped_frames, queue.stats.dropped_bytes), (2, 3200));
+    assert_eq!(queue.stats.peak_queued_bytes, 4800);
+
+    queue.push(&[0; 100]);
+    queue.drop_all();
+    assert!(queue.is_empty());
+    assert_eq!((queue.stats.dropped_frames, queue.stats.dropped_bytes), (6, 8100));
+    assert_eq!(queue.stats.summary(32), "0 chunks/0KB sent, 6 chunks (253ms) dropped");
+}
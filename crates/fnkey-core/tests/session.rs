@@ -0,0 +1,34 @@
+mod common;
+
+use std::sync::Arc;
+
+use common::FakePlatform;
+use fnkey_core::{handle_hotkey, AppState, Config};
+
+#[test]
+fn hotkey_edges_start_and_stop_once() {
+    let platform = Arc::new(FakePlatform::default());
+    let state = AppState::new(platform.clone(), Config::default());
+
+    // Key-repeat style duplicates must not restart the session
+    handle_hotkey(&state, true);
+    handle_hotkey(&state, true);
+    handle_hotkey(&state, false);
+    handle_hotkey(&state, false);
+
+    assert_eq!(*platform.indicator.lock().unwrap(), vec![true, false]);
+}
+
+#[test]
+fn press_clears_buffers_from_previous_session() {
+    let platform = Arc::new(FakePlatform::default());
+    let state = AppState::new(platform, Config::default());
+    state.shadow_buffer.lock().unwrap().extend_from_slice(&[0.5; 480]);
+    state.audio_buffer.lock().unwrap().extend_from_slice(&[0.5; 480]);
+
+    handle_hotkey(&state, true);
+
+    assert!(state.shadow_buffer.lock().unwrap().is_empty());
+    assert!(state.audio_buffer.lock().unwrap().is_empty());
+    handle_hotkey(&state, false);
+}
@@ -0,0 +1,69 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use common::FakePlatform;
+use fnkey_core::source::{AudioSink, AudioSource, ReplaySource};
+use fnkey_core::{handle_hotkey, AppState, Config};
+
+fn sine(len: usize, rate: u32) -> Vec<f32> {
+    (0..len).map(|i| (i as f32 * 440.0 * std::f32::consts::TAU / rate as f32).sin() * 0.5).collect()
+}
+
+fn wait_for(mut done: impl FnMut() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !done() {
+        assert!(Instant::now() < deadline, "timed out");
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[test]
+fn replay_fills_session_buffers() {
+    let clip = sine(22050, 44100);
+    let source = Arc::new(ReplaySource::new(clip.clone(), 44100, 0.0));
+    let state = AppState::with_audio_source(Arc::new(FakePlatform::default()), Config::default(), source);
+
+    handle_hotkey(&state, true);
+    wait_for(|| state.shadow_buffer.lock().unwrap().len() == clip.len());
+    assert_eq!(state.sample_rate.load(std::sync::atomic::Ordering::SeqCst), 44100);
+    assert_eq!(*state.shadow_buffer.lock().unwrap(), clip);
+    handle_hotkey(&state, false);
+}
+
+#[test]
+fn replay_is_paced_and_stops() {
+    let audio = Arc::new(Mutex::new(Vec::new()));
+    let shadow = Arc::new(Mutex::new(Vec::new()));
+    // One second of audio at 2x: roughly half of it after 250ms
+    let source = ReplaySource::new(vec![0.1; 16000], 16000, 2.0);
+    assert_eq!(source.start(AudioSink::new(audio.clone(), shadow.clone())).unwrap(), 16000);
+    thread::sleep(Duration::from_millis(250));
+    source.stop();
+    let fed = shadow.lock().unwrap().len();
+    assert!((4000..=12000).contains(&fed), "fed {} samples", fed);
+
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(shadow.lock().unwrap().len(), fed, "replay kept going after stop");
+    assert_eq!(audio.lock().unwrap().len(), fed);
+}
+
+#[test]
+fn replay_opens_wav() {
+    let path = std::env::temp_dir().join(format!("fnkey-replay-{}.wav", std::process::id()));
+    let spec = hound::WavSpec { channels: 1, sample_rate: 16000, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+    let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+    for _ in 0..1600 {
+        writer.write_sample(8192_i16).unwrap();
+    }
+    writer.finalize().unwrap();
+
+    let source = ReplaySource::open(&path, 0.0);
+    let _ = std::fs::remove_file(&path);
+    let source = source.unwrap();
+    let shadow = Arc::new(Mutex::new(Vec::new()));
+    source.start(AudioSink::new(Arc::new(Mutex::new(Vec::new())), shadow.clone())).unwrap();
+    wait_for(|| shadow.lock().unwrap().len() == 1600);
+}
@@ -0,0 +1,338 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use common::FakePlatform;
+use fnkey_core::chain::Chain;
+use fnkey_core::clip::transcribe_with;
+use fnkey_core::platform::Urgency;
+use fnkey_core::source::ReplaySource;
+use fnkey_core::transcriber::{Segment, StreamSession, TranscribeError, Transcriber, Transcript};
+use fnkey_core::{handle_hotkey, AppState, Config};
+
+/// Batch provider with a canned answer that counts its calls
+struct Batch {
+    name: &'static str,
+    result: Result<&'static str, TranscribeError>,
+    calls: AtomicUsize,
+}
+
+impl Batch {
+    fn new(name: &'static str, result: Result<&'static str, TranscribeError>) -> Arc<Self> {
+        Arc::new(Batch { name, result, calls: AtomicUsize::new(0) })
+    }
+}
+
+impl Transcriber for Batch {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn transcribe(&self, audio: &[f32], _sample_rate: u32) -> Result<Transcript, TranscribeError> {
+        assert!(!audio.is_empty());
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        self.result.clone().map(|text| Transcript::new(self.name, text))
+    }
+}
+
+/// Streaming provider whose result says how many samples it was pushed
+struct Streaming {
+    segments_sent: AtomicUsize,
+}
+
+struct CountingStream {
+    samples: usize,
+}
+
+impl StreamSession for CountingStream {
+    fn push_audio(&mut self, samples: &[f32]) {
+        self.samples += samples.len();
+    }
+
+    fn finish(self: Box<Self>, _timeout: Duration) -> Result<Transcript, TranscribeError> {
+        Ok(Transcript::new("Streaming", format!("{} samples", self.samples)))
+    }
+}
+
+impl Transcriber for Streaming {
+    fn name(&self) -> &str {
+        "Streaming"
+    }
+
+    fn start_stream(&self, _sample_rate: u32, segments: Option<mpsc::Sender<Segment>>) -> Option<Box<dyn StreamSession>> {
+        if let Some(tx) = segments {
+            let segment = Segment { transcript: "partial".to_string(), is_final: false, start: 0.0, duration: 0.1 };
+            let _ = tx.send(segment);
+            self.segments_sent.fetch_add(1, Ordering::SeqCst);
+        }
+        Some(Box::new(CountingStream { samples: 0 }))
+    }
+
+    fn transcribe(&self, _audio: &[f32], _sample_rate: u32) -> Result<Transcript, TranscribeError> {
+        Err(TranscribeError::Unsupported)
+    }
+}
+
+/// Streaming provider that can't connect; says so as soon as it's pushed audio
+struct Unreachable {
+    calls: AtomicUsize,
+}
+
+struct UnreachableStream {
+    pushed: bool,
+}
+
+impl StreamSession for UnreachableStream {
+    fn push_audio(&mut self, _samples: &[f32]) {
+        self.pushed = true;
+    }
+
+    fn finish(self: Box<Self>, _timeout: Duration) -> Result<Transcript, TranscribeError> {
+        Err(TranscribeError::Failed("connect failed".to_string()))
+    }
+
+    fn failed(&mut self) -> bool {
+        self.pushed
+    }
+}
+
+impl Transcriber for Unreachable {
+    fn name(&self) -> &str {
+        "Unreachable"
+    }
+
+    fn start_stream(&self, _sample_rate: u32, _segments: Option<mpsc::Sender<Segment>>) -> Option<Box<dyn StreamSession>> {
+        Some(Box::new(UnreachableStream { pushed: false }))
+    }
+
+    fn transcribe(&self, _audio: &[f32], _sample_rate: u32) -> Result<Transcript, TranscribeError> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Err(TranscribeError::Failed("connect failed".to_string()))
+    }
+}
+
+/// Streaming provider that finalizes a phrase for every 4000 samples it is
+/// pushed, then adds a last word at the end (or fails, if `fails`)
+struct Dictating {
+    fails: bool,
+}
+
+struct DictatingStream {
+    fails: bool,
+    segments: Option<mpsc::Sender<Segment>>,
+    samples: usize,
+    phrases: Vec<&'static str>,
+}
+
+const PHRASES: [&str; 2] = ["Hello there.", "General Kenobi."];
+
+impl StreamSession for DictatingStream {
+    fn push_audio(&mut self, samples: &[f32]) {
+        self.samples += samples.len();
+        while self.phrases.len() < (self.samples / 4000).min(PHRASES.len()) {
+            let phrase = PHRASES[self.phrases.len()];
+            if let Some(ref tx) = self.segments {
+                let _ = tx.send(Segment { transcript: phrase.to_string(), is_final: true, start: 0.0, duration: 1.0 });
+            }
+            self.phrases.push(phrase);
+        }
+    }
+
+    fn finish(self: Box<Self>, _timeout: Duration) -> Result<Transcript, TranscribeError> {
+        if self.fails {
+            return Err(TranscribeError::Timeout);
+        }
+        let mut text = self.phrases.join(" ");
+        text.push_str(" Bye.");
+        Ok(Transcript::new("Dictating", text.trim_start()))
+    }
+}
+
+impl Transcriber for Dictating {
+    fn name(&self) -> &str {
+        "Dictating"
+    }
+
+    fn start_stream(&self, _sample_rate: u32, segments: Option<mpsc::Sender<Segment>>) -> Option<Box<dyn StreamSession>> {
+        Some(Box::new(DictatingStream { fails: self.fails, segments, samples: 0, phrases: Vec::new() }))
+    }
+
+    fn transcribe(&self, _audio: &[f32], _sample_rate: u32) -> Result<Transcript, TranscribeError> {
+        Err(TranscribeError::Unsupported)
+    }
+}
+
+fn session(platform: &Arc<FakePlatform>, samples: usize, providers: Vec<Arc<dyn Transcriber>>) -> Arc<AppState> {
+    let source = Arc::new(ReplaySource::new(vec![0.1; samples], 16000, 0.0));
+    AppState::with_parts(platform.clone(), Config::default(), Some(source), providers.into())
+}
+
+/// Press and hold until the replay has been fully captured, then release
+fn record(state: &Arc<AppState>, samples: usize) {
+    handle_hotkey(state, true);
+    wait_for(|| state.shadow_buffer.lock().unwrap().len() == samples);
+    handle_hotkey(state, false);
+    wait_for(|| !state.is_transcribing());
+}
+
+fn wait_for(mut done: impl FnMut() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !done() {
+        assert!(Instant::now() < deadline, "timed out");
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[test]
+fn streaming_provider_gets_live_audio() {
+    let platform = Arc::new(FakePlatform::default());
+    let streaming = Arc::new(Streaming { segments_sent: AtomicUsize::new(0) });
+    let batch = Batch::new("Batch", Ok("from batch"));
+    let state = session(&platform, 8000, vec![streaming.clone(), batch.clone()]);
+    let events = state.subscribe();
+
+    record(&state, 8000);
+    assert_eq!(*platform.pasted.lock().unwrap(), ["8000 samples"]);
+    assert_eq!(batch.calls.load(Ordering::SeqCst), 0);
+    assert_eq!(streaming.segments_sent.load(Ordering::SeqCst), 1);
+    let mut events = (0..10).map_while(|_| events.recv_timeout(Duration::from_secs(1)).ok());
+    assert!(events.any(|e| serde_json::to_value(&e).unwrap()["text"] == "partial"));
+}
+
+#[test]
+fn failed_stream_hands_live_audio_to_the_next() {
+    let platform = Arc::new(FakePlatform::default());
+    let unreachable = Arc::new(Unreachable { calls: AtomicUsize::new(0) });
+    let streaming = Arc::new(Streaming { segments_sent: AtomicUsize::new(0) });
+    let state = session(&platform, 8000, vec![unreachable.clone(), streaming.clone()]);
+
+    record(&state, 8000);
+    // Every sample reaches the second stream once, replayed or live
+    assert_eq!(*platform.pasted.lock().unwrap(), ["8000 samples"]);
+    assert_eq!(streaming.segments_sent.load(Ordering::SeqCst), 1);
+    // Its failure counts; it isn't tried again in batch
+    assert_eq!(unreachable.calls.load(Ordering::SeqCst), 0);
+    let notes = platform.notifications.lock().unwrap();
+    assert_eq!(notes.len(), 1);
+    assert_eq!(notes[0].message, "Unreachable failed, using Streaming fallback");
+}
+
+#[test]
+fn providers_are_tried_in_order() {
+    let platform = Arc::new(FakePlatform::default());
+    let first = Batch::new("First", Err(TranscribeError::Failed("HTTP 500".to_string())));
+    let second = Batch::new("Second", Ok("hello"));
+    let third = Batch::new("Third", Ok("unused"));
+    let state = session(&platform, 1600, vec![first.clone(), second.clone(), third.clone()]);
+
+    record(&state, 1600);
+    assert_eq!(*platform.pasted.lock().unwrap(), ["hello"]);
+    assert_eq!((first.calls.load(Ordering::SeqCst), third.calls.load(Ordering::SeqCst)), (1, 0));
+    let notes = platform.notifications.lock().unwrap();
+    assert_eq!(notes.len(), 1);
+    assert_eq!(notes[0].message, "First failed, using Second fallback");
+    assert_eq!(notes[0].urgency, Urgency::Info);
+}
+
+#[test]
+fn total_failure_offers_retry() {
+    let platform = Arc::new(FakePlatform::default());
+    let first = Batch::new("First", Err(TranscribeError::Timeout));
+    let second = Batch::new("Second", Err(TranscribeError::Empty));
+    let state = session(&platform, 1600, vec![first.clone(), second.clone()]);
+
+    record(&state, 1600);
+    assert!(platform.pasted.lock().unwrap().is_empty());
+    let failure = platform.notifications.lock().unwrap().pop().unwrap();
+    assert_eq!(failure.message, "Transcription failed (both backends)");
+    assert_eq!(failure.urgency, Urgency::Critical);
+
+    let (label, retry) = failure.action.unwrap();
+    assert_eq!(label, "Retry");
+    retry();
+    wait_for(|| second.calls.load(Ordering::SeqCst) == 2);
+    wait_for(|| !state.is_transcribing());
+    assert_eq!(first.calls.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn clip_skips_failed_providers() {
+    let providers: Vec<Arc<dyn Transcriber>> =
+        vec![Arc::new(Streaming { segments_sent: AtomicUsize::new(0) }), Batch::new("Batch", Ok("clip text"))];
+    let streaming_only = Chain::from(providers[..1].to_vec());
+    let chain = Chain::from(providers);
+    assert_eq!(transcribe_with(&chain, &[0.1; 160], 16000).unwrap(), "clip text");
+    assert!(transcribe_with(&streaming_only, &[0.1; 160], 16000).is_err());
+    assert_eq!(transcribe_with(&chain, &[], 16000).unwrap_err(), "no audio");
+}
+
+#[test]
+fn progressive_output_types_finals_while_held() {
+    for progressive in [true, false] {
+        let platform = Arc::new(FakePlatform::default());
+        let source = Arc::new(ReplaySource::new(vec![0.1; 8000], 16000, 0.0));
+        let config = Config { progressive, ..Config::default() };
+        let state = AppState::with_parts(platform.clone(), config, Some(source), vec![Arc::new(Dictating { fails: false }) as _].into());
+
+        handle_hotkey(&state, true);
+        wait_for(|| state.shadow_buffer.lock().unwrap().len() == 8000);
+        if progressive {
+            // Both phrases land before release, spaced as one text
+            wait_for(|| platform.pasted.lock().unwrap().len() == 2);
+            assert_eq!(*platform.pasted.lock().unwrap(), ["Hello there.", " General Kenobi."]);
+        }
+        handle_hotkey(&state, false);
+        wait_for(|| !state.is_transcribing());
+
+        let pasted = platform.pasted.lock().unwrap();
+        let full = "Hello there. General Kenobi. Bye.";
+        if progressive {
+            assert_eq!(*pasted, ["Hello there.", " General Kenobi.", " Bye."]);
+        } else {
+            assert_eq!(*pasted, [full]);
+        }
+        assert_eq!(pasted.concat(), full);
+        assert_eq!(state.last_transcript().as_deref(), Some(full));
+    }
+}
+
+#[test]
+fn progressive_output_waits_for_a_modifier_hotkey() {
+    let platform = Arc::new(FakePlatform { modifier_hotkey: true, ..FakePlatform::default() });
+    let source = Arc::new(ReplaySource::new(vec![0.1; 8000], 16000, 0.0));
+    let config = Config { progressive: true, ..Config::default() };
+    let state = AppState::with_parts(platform.clone(), config, Some(source), vec![Arc::new(Dictating { fails: false }) as _].into());
+
+    // Ctrl+V under a held Alt would arrive as Ctrl+Alt+V: everything waits for release
+    record(&state, 8000);
+    assert_eq!(*platform.pasted.lock().unwrap(), ["Hello there. General Kenobi. Bye."]);
+}
+
+#[test]
+fn progressive_output_leaves_another_providers_text_on_the_clipboard() {
+    let platform = Arc::new(FakePlatform::default());
+    let batch = Batch::new("Batch", Ok("Hello there, General Kenobi. Bye."));
+    let source = Arc::new(ReplaySource::new(vec![0.1; 8000], 16000, 0.0));
+    let config = Config { progressive: true, ..Config::default() };
+    let providers: Vec<Arc<dyn Transcriber>> = vec![Arc::new(Dictating { fails: true }), batch];
+    let state = AppState::with_parts(platform.clone(), config, Some(source), providers.into());
+
+    handle_hotkey(&state, true);
+    wait_for(|| platform.pasted.lock().unwrap().len() == 2);
+    wait_for(|| state.shadow_buffer.lock().unwrap().len() == 8000);
+    handle_hotkey(&state, false);
+    wait_for(|| !state.is_transcribing());
+
+    // The stream timed out at release; the batch wording can't be spliced onto what was typed
+    assert_eq!(
+        *platform.pasted.lock().unwrap(),
+        ["Hello there.", " General Kenobi.", "Hello there, General Kenobi. Bye."]
+    );
+    assert_eq!(platform.pastes.load(Ordering::SeqCst), 2);
+    let notes = platform.notifications.lock().unwrap();
+    assert_eq!(notes.last().unwrap().message, "Batch transcribed this instead of what was typed. The full text is on the clipboard.");
+    assert_eq!(state.last_transcript().as_deref(), Some("Hello there, General Kenobi. Bye."));
+}
@@ -0,0 +1,50 @@
+//! Needs `--features vosk` and libvosk. Point FNKEY_VOSK_MODEL at an unpacked
+//! model directory to run recognition; skipped otherwise.
+#![cfg(feature = "vosk")]
+
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use fnkey_core::transcriber::{TranscribeError, Transcriber};
+use fnkey_core::vosk::VoskTranscriber;
+use fnkey_core::Config;
+
+fn model() -> Option<PathBuf> {
+    let path = PathBuf::from(std::env::var_os("FNKEY_VOSK_MODEL")?);
+    if path.is_dir() {
+        Some(path)
+    } else {
+        eprintln!("skipping: {} not found", path.display());
+        None
+    }
+}
+
+#[test]
+fn unknown_options_are_rejected() {
+    let options = [("grammar".to_string(), "yes no".to_string())];
+    let err = VoskTranscriber::from_config(&Config::default(), &options).err().unwrap();
+    assert!(err.contains("unknown option"), "{}", err);
+}
+
+#[test]
+fn streams_from_a_local_model() {
+    let Some(model) = model() else { return };
+    let vosk = VoskTranscriber::new(model);
+    let (segments_tx, segments) = mpsc::channel();
+    let mut stream = vosk.start_stream(48000, Some(segments_tx)).unwrap();
+    for _ in 0..50 {
+        stream.push_audio(&[0.0; 960]);
+    }
+    match stream.finish(Duration::from_secs(30)) {
+        Ok(_) | Err(TranscribeError::Empty) => {}
+        Err(e) => panic!("{}", e),
+    }
+    // Silence gives no finals; anything that did arrive must be well-formed
+    for segment in segments.try_iter() {
+        assert!(segment.duration >= 0.0);
+    }
+
+    // Batch mode reuses the already-loaded model
+    assert!(!matches!(vosk.transcribe(&[0.0; 16000], 16000), Err(TranscribeError::Failed(_))));
+}
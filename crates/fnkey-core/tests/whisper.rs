@@ -0,0 +1,44 @@
+//! Needs `--features whisper`. Point FNKEY_WHISPER_MODEL at a ggml model
+//! (e.g. ggml-tiny.en.bin) to run the model itself; skipped otherwise.
+#![cfg(feature = "whisper")]
+
+use std::path::PathBuf;
+
+use fnkey_core::transcriber::{TranscribeError, Transcriber};
+use fnkey_core::whisper::WhisperTranscriber;
+use fnkey_core::Config;
+
+fn model() -> Option<PathBuf> {
+    let path = PathBuf::from(std::env::var_os("FNKEY_WHISPER_MODEL")?);
+    if path.is_file() {
+        Some(path)
+    } else {
+        eprintln!("skipping: {} not found", path.display());
+        None
+    }
+}
+
+#[test]
+fn missing_model_is_a_config_error() {
+    let options = [("model".to_string(), "ggml-does-not-exist.bin".to_string())];
+    let err = WhisperTranscriber::from_config(&Config::default(), &options).err().unwrap();
+    assert!(err.contains("not found") || err.contains("HOME"), "{}", err);
+
+    let options = [("threads".to_string(), "0".to_string())];
+    assert!(WhisperTranscriber::from_config(&Config::default(), &options).err().unwrap().contains("threads"));
+}
+
+#[test]
+fn transcribes_offline() {
+    let Some(model) = model() else { return };
+    let whisper = WhisperTranscriber::new(model, vec!["FnKey".to_string()]);
+
+    // Silence: nothing to say, but the model loads and runs
+    match whisper.transcribe(&[0.0; 48000], 48000) {
+        Ok(_) | Err(TranscribeError::Empty) => {}
+        Err(e) => panic!("{}", e),
+    }
+    // A second clip reuses the loaded context
+    let tone: Vec<f32> = (0..16000).map(|i| (i as f32 * 0.1).sin() * 0.2).collect();
+    assert!(!matches!(whisper.transcribe(&tone, 16000), Err(TranscribeError::Failed(_))));
+}
@@ -0,0 +1,168 @@
+use std::io::{BufReader, Cursor, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use fnkey_core::transcriber::{TranscribeError, Transcriber};
+use fnkey_core::wyoming::{read_event, write_event, Event, WyomingTranscriber};
+use fnkey_core::Config;
+use serde_json::json;
+
+#[test]
+fn events_round_trip() {
+    let mut chunk = Event::new("audio-chunk", json!({ "rate": 16000, "width": 2, "channels": 1, "timestamp": 20 }));
+    chunk.payload = vec![1, 2, 3, 4];
+    let mut buf = Vec::new();
+    write_event(&mut buf, &chunk).unwrap();
+    write_event(&mut buf, &Event::new("audio-stop", json!({ "timestamp": 40 }))).unwrap();
+
+    let mut reader = Cursor::new(buf);
+    assert_eq!(read_event(&mut reader).unwrap(), chunk);
+    assert_eq!(read_event(&mut reader).unwrap().data["timestamp"], 40);
+    assert!(read_event(&mut reader).is_err());
+}
+
+#[test]
+fn reads_inline_data_from_older_servers() {
+    let mut reader = Cursor::new(b"{\"type\":\"transcript\",\"data\":{\"text\":\"hi there\"}}\n".to_vec());
+    let event = read_event(&mut reader).unwrap();
+    assert_eq!((event.kind.as_str(), event.text()), ("transcript", "hi there"));
+}
+
+/// Stand-in server: record the client's events until audio-stop, then send
+/// `replies`. Returns host:port and the recorded events.
+fn stand_in(replies: Vec<Event>) -> (String, mpsc::Receiver<Vec<Event>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let host = listener.local_addr().unwrap().to_string();
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut seen = Vec::new();
+        loop {
+            let event = read_event(&mut reader).unwrap();
+            let done = event.kind == "audio-stop";
+            seen.push(event);
+            if done {
+                break;
+            }
+        }
+        for reply in &replies {
+            write_event(&mut stream, reply).unwrap();
+        }
+        let _ = tx.send(seen);
+        // Hold the connection until the client hangs up
+        let _ = read_event(&mut reader);
+    });
+    (host, rx)
+}
+
+fn transcriber(host: &str, extra: &[(&str, &str)]) -> WyomingTranscriber {
+    let mut options = vec![("uri".to_string(), format!("tcp://{}", host))];
+    options.extend(extra.iter().map(|(k, v)| (k.to_string(), v.to_string())));
+    WyomingTranscriber::from_config(&Config::default(), &options).unwrap()
+}
+
+#[test]
+fn streams_pcm_and_returns_transcript() {
+    let replies = vec![
+        Event::new("transcript-start", json!({})),
+        Event::new("transcript-chunk", json!({ "text": "hello" })),
+        Event::new("transcript-chunk", json!({ "text": " world" })),
+        Event::new("transcript-stop", json!({})),
+        Event::new("transcript", json!({ "text": " hello world " })),
+    ];
+    let (host, seen) = stand_in(replies);
+    let wyoming = transcriber(&host, &[("language", "en"), ("name", "Local")]);
+
+    let (segments_tx, segments) = mpsc::channel();
+    let mut stream = wyoming.start_stream(48000, Some(segments_tx)).unwrap();
+    // 100ms at 48kHz, twice
+    stream.push_audio(&[0.25; 4800]);
+    stream.push_audio(&[-0.25; 4800]);
+    let transcript = stream.finish(Duration::from_secs(5)).unwrap();
+    assert_eq!((transcript.text.as_str(), transcript.provider.as_str()), ("hello world", "Local"));
+
+    let events = seen.recv().unwrap();
+    let kinds: Vec<&str> = events.iter().map(|e| e.kind.as_str()).collect();
+    assert_eq!(kinds, ["transcribe", "audio-start", "audio-chunk", "audio-chunk", "audio-stop"]);
+    assert_eq!(events[0].data["language"], "en");
+    assert_eq!(events[1].data["rate"], 16000);
+    assert_eq!(events[1].data["width"], 2);
+    // Resampled to 16kHz 16-bit mono: 1600 samples per chunk
+    assert_eq!(events[2].payload.len(), 3200);
+    assert_eq!(i16::from_le_bytes([events[2].payload[0], events[2].payload[1]]), 8191);
+    assert_eq!((events[2].data["timestamp"].as_u64(), events[3].data["timestamp"].as_u64()), (Some(0), Some(100)));
+    assert_eq!(events[4].data["timestamp"], 200);
+
+    let interim: Vec<String> = segments.try_iter().map(|s| s.transcript).collect();
+    assert_eq!(interim, ["hello", "hello world"]);
+}
+
+#[test]
+fn batch_clip_and_server_errors() {
+    let (host, seen) = stand_in(vec![Event::new("transcript", json!({ "text": "from a file" }))]);
+    let wyoming = transcriber(&host, &[]);
+    assert_eq!(wyoming.transcribe(&[0.1; 16000], 16000).unwrap().text, "from a file");
+    let chunks = seen.recv().unwrap().iter().filter(|e| e.kind == "audio-chunk").count();
+    assert_eq!(chunks, 10);
+
+    let (host, _seen) = stand_in(vec![Event::new("error", json!({ "text": "model not loaded", "code": "x" }))]);
+    match transcriber(&host, &[]).transcribe(&[0.1; 1600], 16000) {
+        Err(TranscribeError::Failed(msg)) => assert!(msg.contains("model not loaded"), "{}", msg),
+        other => panic!("unexpected {:?}", other),
+    }
+
+    let (host, _seen) = stand_in(vec![Event::new("transcript", json!({ "text": "" }))]);
+    assert_eq!(transcriber(&host, &[]).transcribe(&[0.1; 1600], 16000).unwrap_err(), TranscribeError::Empty);
+}
+
+#[test]
+fn event_split_across_reads() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let host = listener.local_addr().unwrap().to_string();
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        while read_event(&mut reader).unwrap().kind != "audio-stop" {}
+        let mut reply = Vec::new();
+        write_event(&mut reply, &Event::new("transcript", json!({ "text": "in two parts" }))).unwrap();
+        let (head, tail) = reply.split_at(reply.len() / 2);
+        stream.write_all(head).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        stream.write_all(tail).unwrap();
+        let _ = read_event(&mut reader);
+    });
+    assert_eq!(transcriber(&host, &[]).transcribe(&[0.1; 1600], 16000).unwrap().text, "in two parts");
+}
+
+#[test]
+fn unreachable_server_and_bad_options() {
+    let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let wyoming = transcriber(&format!("127.0.0.1:{}", port), &[]);
+    assert!(matches!(wyoming.transcribe(&[0.1; 1600], 16000), Err(TranscribeError::Failed(_))));
+
+    let bad = |k: &str, v: &str| WyomingTranscriber::from_config(&Config::default(), &[(k.to_string(), v.to_string())]).err().unwrap();
+    assert!(bad("uri", "http://localhost:10300").contains("tcp://"));
+    assert!(bad("uri", "localhost").contains("tcp://"));
+    assert!(bad("voice", "x").contains("unknown option"));
+}
+
+#[test]
+fn cancel_hangs_up() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let host = listener.local_addr().unwrap().to_string();
+    let stream = transcriber(&host, &[]).start_stream(16000, None).unwrap();
+    let (conn, _): (TcpStream, _) = listener.accept().unwrap();
+    conn.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    stream.cancel();
+
+    // The server sees the handshake and then EOF
+    let mut reader = BufReader::new(conn);
+    let mut kinds = Vec::new();
+    while let Ok(event) = read_event(&mut reader) {
+        kinds.push(event.kind);
+    }
+    assert_eq!(kinds, ["transcribe", "audio-start"]);
+}
@@ -0,0 +1,144 @@
+//! Hotkey source reading key down/up events from /dev/input (evdev).
+//!
+//! Config files (~/.config/fnkey/):
+//!   hotkey_device - event device to watch, e.g. /dev/input/by-id/...-event-kbd
+//!   hotkey_key    - key name or code, e.g. KEY_RIGHTCTRL or 97
+//!
+//! Without `hotkey_key`, KEY_FN is used, falling back to Right Alt (the
+//! closest thing to macOS Option) if the device (or, without `hotkey_device`,
+//! every device) lacks Fn.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use evdev::{Device, EventSummary, KeyCode};
+use fnkey_core::config::read_config_file;
+use fnkey_core::platform::HotkeyCallback;
+
+/// Key used when `hotkey_key` isn't configured
+pub const DEFAULT_KEY: KeyCode = KeyCode::KEY_FN;
+/// Used instead of Fn when no device reports it (most laptops handle Fn in firmware)
+pub const FALLBACK_KEY: KeyCode = KeyCode::KEY_RIGHTALT;
+
+/// evdev key values
+const KEY_UP: i32 = 0;
+const KEY_DOWN: i32 = 1;
+
+pub struct EvdevHotkey {
+    path: PathBuf,
+    key: KeyCode,
+    device: Mutex<Device>,
+}
+
+impl EvdevHotkey {
+    /// Open `path` and watch `key` on it.
+    pub fn open(path: impl AsRef<Path>, key: KeyCode) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let device = Device::open(&path)?;
+        if !device.supported_keys().is_some_and(|keys| keys.contains(key)) {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("{} does not report {:?}", path.display(), key),
+            ));
+        }
+        Ok(EvdevHotkey { path, key, device: Mutex::new(device) })
+    }
+
+    /// Open the device and key from `hotkey_device` / `hotkey_key`,
+    /// auto-detecting whichever is missing.
+    pub fn from_config() -> io::Result<Self> {
+        let key = match read_config_file("hotkey_key") {
+            Some(name) => Some(parse_key(&name).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, format!("unknown hotkey_key '{}'", name))
+            })?),
+            None => None,
+        };
+        let candidates = match key {
+            Some(k) => vec![k],
+            None => vec![DEFAULT_KEY, FALLBACK_KEY],
+        };
+        if let Some(path) = read_config_file("hotkey_device") {
+            // The first candidate the device reports; the error names the last
+            let mut result = Err(io::Error::new(io::ErrorKind::InvalidInput, "no hotkey candidates"));
+            for key in candidates {
+                result = Self::open(&path, key);
+                if result.is_ok() {
+                    break;
+                }
+            }
+            return result;
+        }
+        for key in candidates {
+            if let Some(path) = find_device(key) {
+                return Self::open(path, key);
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "no readable input device reports the hotkey (is the user in the 'input' group?)",
+        ))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn key(&self) -> KeyCode {
+        self.key
+    }
+
+    /// Block reading events, reporting press (true) and release (false) of
+    /// the hotkey. Autorepeat is ignored. Returns when the device goes away.
+    pub fn run(&self, on_key: HotkeyCallback) -> io::Result<()> {
+        let mut device = self.device.lock().unwrap();
+        loop {
+            for event in device.fetch_events()? {
+                if let EventSummary::Key(_, code, value) = event.destructure() {
+                    if code != self.key {
+                        continue;
+                    }
+                    match value {
+                        KEY_DOWN => on_key(true),
+                        KEY_UP => on_key(false),
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Alt, Ctrl, Shift and Super, which change any key injected while held
+pub fn is_modifier(key: KeyCode) -> bool {
+    matches!(
+        key,
+        KeyCode::KEY_LEFTALT
+            | KeyCode::KEY_RIGHTALT
+            | KeyCode::KEY_LEFTCTRL
+            | KeyCode::KEY_RIGHTCTRL
+            | KeyCode::KEY_LEFTSHIFT
+            | KeyCode::KEY_RIGHTSHIFT
+            | KeyCode::KEY_LEFTMETA
+            | KeyCode::KEY_RIGHTMETA
+    )
+}
+
+/// First event device under /dev/input that reports `key`.
+pub fn find_device(key: KeyCode) -> Option<PathBuf> {
+    evdev::enumerate()
+        .find(|(_, device)| device.supported_keys().is_some_and(|keys| keys.contains(key)))
+        .map(|(path, _)| path)
+}
+
+/// Accepts `KEY_RIGHTCTRL`, `rightctrl` or a numeric code like `97`.
+pub fn parse_key(name: &str) -> Option<KeyCode> {
+    let name = name.trim();
+    if let Ok(code) = name.parse::<u16>() {
+        return Some(KeyCode::new(code));
+    }
+    let upper = name.to_ascii_uppercase();
+    let full = if upper.starts_with("KEY_") { upper } else { format!("KEY_{}", upper) };
+    KeyCode::from_str(&full).ok()
+}
@@ -0,0 +1,11 @@
+//! Linux backends for FnKey: evdev hotkey source, Wayland/XTest/uinput paste,
+//! StatusNotifierItem tray icon, desktop notifications.
+//!
+//! Each backend is usable on its own; the `fnkey` binary combines them into
+//! a `fnkey_core::Platform`.
+
+pub mod hotkey;
+pub mod notify;
+pub mod paste;
+pub mod tray;
+pub mod wayland;
@@ -0,0 +1,104 @@
+//! Desktop notifications over org.freedesktop.Notifications, with urgency
+//! hints and an action button when the server supports them.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use fnkey_core::log_error;
+use fnkey_core::platform::{Notification, NotificationAction, Urgency};
+use zbus::blocking::{Connection, Proxy};
+use zbus::zvariant::Value;
+
+const DEST: &str = "org.freedesktop.Notifications";
+const PATH: &str = "/org/freedesktop/Notifications";
+
+/// Action key sent in Notify; the label is what the user sees
+const ACTION_KEY: &str = "default-action";
+
+/// freedesktop urgency hint values
+const URGENCY_NORMAL: u8 = 1;
+const URGENCY_CRITICAL: u8 = 2;
+
+pub struct Notifier {
+    proxy: Proxy<'static>,
+    supports_actions: bool,
+    /// Pending actions by notification id, consumed on ActionInvoked
+    pending: Arc<Mutex<HashMap<u32, NotificationAction>>>,
+}
+
+impl Notifier {
+    /// Connect to the notification server on the session bus.
+    pub fn connect() -> zbus::Result<Self> {
+        Self::connect_on(Connection::session()?)
+    }
+
+    /// Use an existing connection (e.g. a private bus in tests).
+    pub fn connect_on(conn: Connection) -> zbus::Result<Self> {
+        let proxy = Proxy::new(&conn, DEST, PATH, DEST)?;
+        let capabilities: Vec<String> = proxy.call("GetCapabilities", &())?;
+        let supports_actions = capabilities.iter().any(|c| c == "actions");
+
+        let pending: Arc<Mutex<HashMap<u32, NotificationAction>>> = Arc::new(Mutex::new(HashMap::new()));
+        if supports_actions {
+            let signals = proxy.receive_all_signals()?;
+            let pending = Arc::clone(&pending);
+            thread::spawn(move || {
+                for msg in signals {
+                    let header = msg.header();
+                    match header.member().map(|m| m.as_str()) {
+                        Some("ActionInvoked") => {
+                            let Ok((id, key)) = msg.body().deserialize::<(u32, String)>() else { continue };
+                            let action = pending.lock().unwrap().remove(&id);
+                            if let (Some(action), ACTION_KEY) = (action, key.as_str()) {
+                                action();
+                            }
+                        }
+                        Some("NotificationClosed") => {
+                            if let Ok((id, _reason)) = msg.body().deserialize::<(u32, u32)>() {
+                                pending.lock().unwrap().remove(&id);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            });
+        }
+        Ok(Notifier { proxy, supports_actions, pending })
+    }
+
+    /// Show `notification`; returns the server's id for it.
+    pub fn show(&self, notification: Notification) -> zbus::Result<u32> {
+        let urgency = match notification.urgency {
+            Urgency::Info => URGENCY_NORMAL,
+            Urgency::Critical => URGENCY_CRITICAL,
+        };
+        let mut hints: HashMap<&str, Value> = HashMap::new();
+        hints.insert("urgency", Value::U8(urgency));
+
+        let action = notification.action.filter(|_| self.supports_actions);
+        let actions: Vec<&str> = match &action {
+            Some((label, _)) => vec![ACTION_KEY, label.as_str()],
+            None => Vec::new(),
+        };
+        // Critical notifications stay until dismissed; -1 is the server default
+        let timeout: i32 = if notification.urgency == Urgency::Critical { 0 } else { -1 };
+
+        let id: u32 = self.proxy.call(
+            "Notify",
+            &("FnKey", 0u32, "audio-input-microphone", "FnKey", notification.message.as_str(), actions, hints, timeout),
+        )?;
+        if let Some((_, run)) = action {
+            self.pending.lock().unwrap().insert(id, run);
+        }
+        Ok(id)
+    }
+
+    /// Show, falling back to stderr if the server is gone.
+    pub fn show_or_log(&self, notification: Notification) {
+        let message = notification.message.clone();
+        if let Err(e) = self.show(notification) {
+            log_error(&format!("Notification failed ({}): {}", e, message));
+        }
+    }
+}
@@ -0,0 +1,486 @@
+//! Clipboard + synthetic paste: Wayland virtual keyboard, XTest on X11, and a
+//! uinput virtual keyboard as a compositor-independent fallback.
+//!
+//! Config files (~/.config/fnkey/):
+//!   paste_backend - auto (default), wayland, xtest or uinput
+//!   paste_chord   - auto (default), ctrl-v or ctrl-shift-v
+//!   paste_mode    - paste (default) or type; type sends the text as
+//!                   keystrokes and needs the wayland backend
+//!   paste_v_key   - key the uinput backend presses for V, e.g. KEY_K or 37;
+//!                   by default it's looked up in the X server's layout,
+//!                   falling back to the QWERTY position without X
+//!
+//! In auto mode Ctrl+Shift+V is sent when the focused X11 window belongs to a
+//! known terminal emulator; without X11 there is no focus information, so
+//! Ctrl+V is used unless `paste_chord` says otherwise.
+
+use std::collections::HashMap;
+use std::env;
+use std::io;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use arboard::Clipboard;
+use evdev::uinput::VirtualDevice;
+use evdev::{AttributeSet, EventType, InputEvent, KeyCode};
+use fnkey_core::config::read_config_file;
+use fnkey_core::log_error;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{AtomEnum, ConnectionExt as _, KEY_PRESS_EVENT, KEY_RELEASE_EVENT};
+use x11rb::protocol::xtest::ConnectionExt as _;
+use x11rb::rust_connection::RustConnection;
+
+use crate::hotkey::parse_key;
+use crate::wayland::WaylandOutput;
+
+/// Keys the paste path needs to synthesize
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Key {
+    Ctrl,
+    Shift,
+    V,
+    Return,
+}
+
+/// Window classes (lowercase) that paste with Ctrl+Shift+V
+const TERMINAL_CLASSES: &[&str] = &[
+    "alacritty", "foot", "gnome-terminal", "gnome-terminal-server", "kitty", "konsole",
+    "ptyxis", "st", "st-256color", "terminator", "tilix", "urxvt", "wezterm",
+    "org.wezfurlong.wezterm", "xfce4-terminal", "xterm", "com.mitchellh.ghostty",
+];
+
+pub fn is_terminal(window_class: &str) -> bool {
+    TERMINAL_CLASSES.contains(&window_class.to_ascii_lowercase().as_str())
+}
+
+/// Something that can press and release keys in the focused application.
+pub trait Keyboard: Send {
+    /// Press `modifiers` in order, tap `key`, release modifiers in reverse.
+    fn chord(&mut self, modifiers: &[Key], key: Key) -> io::Result<()>;
+
+    /// WM_CLASS of the focused window, where the backend can see it.
+    fn focused_class(&self) -> Option<String> {
+        None
+    }
+
+    /// Keycode (in this backend's numbering) that types `ch`.
+    fn keycode_for_char(&self, _ch: char) -> Option<u16> {
+        None
+    }
+
+    /// Type arbitrary text as keystrokes.
+    fn type_text(&mut self, _text: &str) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "this backend can't type text"))
+    }
+}
+
+/// Somewhere to put the transcript for the paste chord to pick up.
+pub trait ClipboardSink: Send {
+    fn set_text(&mut self, text: &str) -> io::Result<()>;
+}
+
+/// arboard clipboard (X11, or XWayland when data-control is missing)
+pub struct ArboardClipboard(Clipboard);
+
+impl ArboardClipboard {
+    pub fn new() -> io::Result<Self> {
+        Clipboard::new().map(ArboardClipboard).map_err(io::Error::other)
+    }
+}
+
+impl ClipboardSink for ArboardClipboard {
+    fn set_text(&mut self, text: &str) -> io::Result<()> {
+        self.0.set_text(text).map_err(io::Error::other)
+    }
+}
+
+// ============================================================================
+// XTest (X11)
+// ============================================================================
+
+const XK_CONTROL_L: u32 = 0xffe3;
+const XK_SHIFT_L: u32 = 0xffe1;
+const XK_RETURN: u32 = 0xff0d;
+const XK_V: u32 = 0x0076;
+
+pub struct XTestKeyboard {
+    conn: RustConnection,
+    root: u32,
+    /// keysym → keycode for the current layout (all groups)
+    keycodes: HashMap<u32, u8>,
+}
+
+fn x11_error(e: impl std::fmt::Display) -> io::Error {
+    io::Error::other(format!("X11: {}", e))
+}
+
+/// keysym → keycode for the X server's current layout (all groups)
+fn x11_keycodes(conn: &RustConnection) -> io::Result<HashMap<u32, u8>> {
+    let setup = conn.setup();
+    let (min, max) = (setup.min_keycode, setup.max_keycode);
+    let mapping = conn
+        .get_keyboard_mapping(min, max - min + 1)
+        .map_err(x11_error)?
+        .reply()
+        .map_err(x11_error)?;
+    let per = mapping.keysyms_per_keycode.max(1) as usize;
+    let mut keycodes = HashMap::new();
+    for (i, syms) in mapping.keysyms.chunks(per).enumerate() {
+        for &sym in syms {
+            if sym != 0 {
+                keycodes.entry(sym).or_insert(min + i as u8);
+            }
+        }
+    }
+    Ok(keycodes)
+}
+
+impl XTestKeyboard {
+    /// Connect to $DISPLAY and check the XTEST extension is present.
+    pub fn connect() -> io::Result<Self> {
+        let (conn, screen_num) = x11rb::connect(None).map_err(x11_error)?;
+        conn.xtest_get_version(2, 2).map_err(x11_error)?.reply().map_err(x11_error)?;
+        let root = conn.setup().roots[screen_num].root;
+        let keycodes = x11_keycodes(&conn)?;
+        Ok(XTestKeyboard { conn, root, keycodes })
+    }
+
+    fn keycode(&self, key: Key) -> io::Result<u8> {
+        let sym = match key {
+            Key::Ctrl => XK_CONTROL_L,
+            Key::Shift => XK_SHIFT_L,
+            Key::V => XK_V,
+            Key::Return => XK_RETURN,
+        };
+        self.keycodes
+            .get(&sym)
+            .copied()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no keycode for {:?}", key)))
+    }
+
+    fn fake(&self, event: u8, keycode: u8) -> io::Result<()> {
+        self.conn
+            .xtest_fake_input(event, keycode, x11rb::CURRENT_TIME, self.root, 0, 0, 0)
+            .map_err(x11_error)?;
+        Ok(())
+    }
+}
+
+impl Keyboard for XTestKeyboard {
+    fn chord(&mut self, modifiers: &[Key], key: Key) -> io::Result<()> {
+        let mods = modifiers.iter().map(|&m| self.keycode(m)).collect::<io::Result<Vec<_>>>()?;
+        let code = self.keycode(key)?;
+        for &m in &mods {
+            self.fake(KEY_PRESS_EVENT, m)?;
+        }
+        self.fake(KEY_PRESS_EVENT, code)?;
+        self.fake(KEY_RELEASE_EVENT, code)?;
+        for &m in mods.iter().rev() {
+            self.fake(KEY_RELEASE_EVENT, m)?;
+        }
+        self.conn.flush().map_err(x11_error)
+    }
+
+    fn focused_class(&self) -> Option<String> {
+        let active = self.conn.intern_atom(false, b"_NET_ACTIVE_WINDOW").ok()?.reply().ok()?.atom;
+        let window = self
+            .conn
+            .get_property(false, self.root, active, AtomEnum::WINDOW, 0, 1)
+            .ok()?
+            .reply()
+            .ok()?
+            .value32()?
+            .next()?;
+        let class = self
+            .conn
+            .get_property(false, window, AtomEnum::WM_CLASS, AtomEnum::STRING, 0, 256)
+            .ok()?
+            .reply()
+            .ok()?;
+        // WM_CLASS is "instance\0class\0"
+        let class = class.value.split(|&b| b == 0).nth(1)?;
+        Some(String::from_utf8_lossy(class).into_owned())
+    }
+
+    fn keycode_for_char(&self, ch: char) -> Option<u16> {
+        // Latin-1 keysyms equal their code points
+        self.keycodes.get(&(ch as u32)).map(|&k| u16::from(k))
+    }
+}
+
+// ============================================================================
+// uinput virtual keyboard (X11, Wayland, console)
+// ============================================================================
+
+/// Time for the compositor / X server to pick up a new input device
+const UINPUT_SETTLE: Duration = Duration::from_millis(300);
+
+/// X keycodes are evdev codes plus 8
+const X_KEYCODE_OFFSET: u8 = 8;
+
+/// keysym → evdev code for the X server's layout (XWayland mirrors the
+/// compositor's). None without X, where only QWERTY positions are known.
+fn x11_layout() -> Option<HashMap<u32, u16>> {
+    env::var_os("DISPLAY")?;
+    let (conn, _) = x11rb::connect(None).ok()?;
+    let keycodes = x11_keycodes(&conn).ok()?;
+    Some(keycodes.into_iter().filter_map(|(sym, code)| Some((sym, u16::from(code.checked_sub(X_KEYCODE_OFFSET)?)))).collect())
+}
+
+/// Injects evdev keycodes, which the compositor then runs through the active
+/// layout: the key for V is the one that types `v` there, not the QWERTY V.
+pub struct UinputKeyboard {
+    device: VirtualDevice,
+    /// keysym → evdev code; empty when the layout is unknown
+    layout: HashMap<u32, u16>,
+    /// Pressed for [`Key::V`]
+    v: KeyCode,
+}
+
+impl UinputKeyboard {
+    /// `v_key` overrides the key pressed for V; otherwise it comes from the
+    /// X server's layout, or is the QWERTY V when there's no X server.
+    pub fn new(v_key: Option<KeyCode>) -> io::Result<Self> {
+        let layout = x11_layout().unwrap_or_default();
+        let v = v_key
+            .or_else(|| layout.get(&XK_V).map(|&code| KeyCode::new(code)))
+            .unwrap_or(KeyCode::KEY_V);
+        let mut keys = AttributeSet::<KeyCode>::new();
+        for key in [KeyCode::KEY_LEFTCTRL, KeyCode::KEY_LEFTSHIFT, KeyCode::KEY_ENTER, v] {
+            keys.insert(key);
+        }
+        let device = VirtualDevice::builder()?
+            .name("fnkey virtual keyboard")
+            .with_keys(&keys)?
+            .build()?;
+        thread::sleep(UINPUT_SETTLE);
+        Ok(UinputKeyboard { device, layout, v })
+    }
+
+    /// The underlying device, e.g. to find its /dev/input node
+    pub fn device_mut(&mut self) -> &mut VirtualDevice {
+        &mut self.device
+    }
+
+    fn keycode(&self, key: Key) -> KeyCode {
+        match key {
+            Key::Ctrl => KeyCode::KEY_LEFTCTRL,
+            Key::Shift => KeyCode::KEY_LEFTSHIFT,
+            Key::V => self.v,
+            Key::Return => KeyCode::KEY_ENTER,
+        }
+    }
+
+    fn send(&mut self, key: Key, value: i32) -> io::Result<()> {
+        let code = self.keycode(key);
+        self.device.emit(&[InputEvent::new(EventType::KEY.0, code.0, value)])
+    }
+}
+
+impl Keyboard for UinputKeyboard {
+    fn chord(&mut self, modifiers: &[Key], key: Key) -> io::Result<()> {
+        for &m in modifiers {
+            self.send(m, 1)?;
+        }
+        self.send(key, 1)?;
+        self.send(key, 0)?;
+        for &m in modifiers.iter().rev() {
+            self.send(m, 0)?;
+        }
+        Ok(())
+    }
+
+    fn keycode_for_char(&self, ch: char) -> Option<u16> {
+        // Latin-1 keysyms equal their code points
+        self.layout.get(&(ch as u32)).copied()
+    }
+}
+
+// ============================================================================
+// Paster: clipboard + chord selection
+// ============================================================================
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PasteChord {
+    /// Ctrl+Shift+V in terminals, Ctrl+V elsewhere
+    Auto,
+    CtrlV,
+    CtrlShiftV,
+}
+
+impl PasteChord {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim() {
+            "auto" => Some(PasteChord::Auto),
+            "ctrl-v" => Some(PasteChord::CtrlV),
+            "ctrl-shift-v" => Some(PasteChord::CtrlShiftV),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PasteMode {
+    /// Send the paste chord; the app reads the clipboard
+    Paste,
+    /// Type the text directly as keystrokes
+    Type,
+}
+
+impl PasteMode {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim() {
+            "paste" => Some(PasteMode::Paste),
+            "type" => Some(PasteMode::Type),
+            _ => None,
+        }
+    }
+}
+
+pub struct Paster {
+    /// None if no backend could be opened; the text then stays on the clipboard
+    keyboard: Mutex<Option<Box<dyn Keyboard>>>,
+    /// Kept alive: on X11 the selection is served only while a Clipboard exists
+    clipboard: Mutex<Option<Box<dyn ClipboardSink>>>,
+    chord: PasteChord,
+    mode: PasteMode,
+    /// Text most recently put on the clipboard, for type mode
+    last_text: Mutex<String>,
+}
+
+fn invalid(what: &str, value: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, format!("unknown {} '{}'", what, value))
+}
+
+fn open_keyboard(backend: &str, wayland: Option<&WaylandOutput>, v_key: Option<KeyCode>) -> io::Result<Box<dyn Keyboard>> {
+    match backend {
+        "wayland" => match wayland {
+            Some(w) => Ok(Box::new(w.clone())),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "no Wayland virtual keyboard")),
+        },
+        "xtest" => Ok(Box::new(XTestKeyboard::connect()?)),
+        "uinput" => Ok(Box::new(UinputKeyboard::new(v_key)?)),
+        _ => {
+            if let Some(w) = wayland {
+                return Ok(Box::new(w.clone()));
+            }
+            let x11_only = env::var_os("DISPLAY").is_some() && env::var_os("WAYLAND_DISPLAY").is_none();
+            match x11_only.then(XTestKeyboard::connect) {
+                Some(Ok(xtest)) => Ok(Box::new(xtest)),
+                _ => Ok(Box::new(UinputKeyboard::new(v_key)?)),
+            }
+        }
+    }
+}
+
+impl Paster {
+    pub fn new(
+        keyboard: Option<Box<dyn Keyboard>>,
+        clipboard: Option<Box<dyn ClipboardSink>>,
+        chord: PasteChord,
+        mode: PasteMode,
+    ) -> Self {
+        Paster {
+            keyboard: Mutex::new(keyboard),
+            clipboard: Mutex::new(clipboard),
+            chord,
+            mode,
+            last_text: Mutex::new(String::new()),
+        }
+    }
+
+    /// Pick backends from config. In a Wayland session the data-control
+    /// clipboard and virtual keyboard are preferred; auto otherwise uses
+    /// XTest on plain X11 and uinput everywhere else. Only invalid config is
+    /// an error — an unavailable backend is logged and that step is skipped.
+    pub fn from_config() -> io::Result<Self> {
+        let chord = match read_config_file("paste_chord") {
+            Some(s) => PasteChord::parse(&s).ok_or_else(|| invalid("paste_chord", &s))?,
+            None => PasteChord::Auto,
+        };
+        let mode = match read_config_file("paste_mode") {
+            Some(s) => PasteMode::parse(&s).ok_or_else(|| invalid("paste_mode", &s))?,
+            None => PasteMode::Paste,
+        };
+        let v_key = match read_config_file("paste_v_key") {
+            Some(s) => Some(parse_key(&s).ok_or_else(|| invalid("paste_v_key", &s))?),
+            None => None,
+        };
+        let backend = read_config_file("paste_backend").unwrap_or_else(|| "auto".to_string());
+        if !matches!(backend.as_str(), "auto" | "wayland" | "xtest" | "uinput") {
+            return Err(invalid("paste_backend", &backend));
+        }
+
+        let wayland = if env::var_os("WAYLAND_DISPLAY").is_some() {
+            WaylandOutput::connect()
+                .map_err(|e| log_error(&format!("Wayland output unavailable: {}", e)))
+                .ok()
+        } else {
+            None
+        };
+        let keyboard = match open_keyboard(&backend, wayland.as_ref(), v_key) {
+            Ok(k) => Some(k),
+            Err(e) => {
+                log_error(&format!("Paste unavailable ({} backend): {}", backend, e));
+                None
+            }
+        };
+        let clipboard: Option<Box<dyn ClipboardSink>> = match wayland {
+            Some(w) => Some(Box::new(w)),
+            None => ArboardClipboard::new()
+                .map(|c| Box::new(c) as Box<dyn ClipboardSink>)
+                .map_err(|e| log_error(&format!("Clipboard unavailable: {}", e)))
+                .ok(),
+        };
+        Ok(Paster::new(keyboard, clipboard, chord, mode))
+    }
+
+    pub fn set_clipboard(&self, text: &str) -> bool {
+        *self.last_text.lock().unwrap() = text.to_string();
+        let mut clipboard = self.clipboard.lock().unwrap();
+        match clipboard.as_mut() {
+            Some(c) => match c.set_text(text) {
+                Ok(()) => true,
+                Err(e) => {
+                    log_error(&format!("Clipboard: {}", e));
+                    // Type mode doesn't need the clipboard
+                    self.mode == PasteMode::Type
+                }
+            },
+            None => self.mode == PasteMode::Type,
+        }
+    }
+
+    pub fn paste(&self) -> io::Result<()> {
+        let mut keyboard = self.keyboard.lock().unwrap();
+        let keyboard = keyboard.as_mut().ok_or_else(no_keyboard)?;
+        if self.mode == PasteMode::Type {
+            let text = self.last_text.lock().unwrap().clone();
+            return keyboard.type_text(&text);
+        }
+        let terminal = match self.chord {
+            PasteChord::Auto => keyboard.focused_class().is_some_and(|c| is_terminal(&c)),
+            PasteChord::CtrlV => false,
+            PasteChord::CtrlShiftV => true,
+        };
+        if terminal {
+            keyboard.chord(&[Key::Ctrl, Key::Shift], Key::V)
+        } else {
+            keyboard.chord(&[Key::Ctrl], Key::V)
+        }
+    }
+
+    pub fn press_return(&self) -> io::Result<()> {
+        let mut keyboard = self.keyboard.lock().unwrap();
+        keyboard.as_mut().ok_or_else(no_keyboard)?.chord(&[], Key::Return)
+    }
+
+    pub fn keycode_for_char(&self, ch: char) -> Option<u16> {
+        self.keyboard.lock().unwrap().as_ref()?.keycode_for_char(ch)
+    }
+}
+
+fn no_keyboard() -> io::Error {
+    io::Error::new(io::ErrorKind::NotConnected, "no keyboard backend")
+}
@@ -0,0 +1,296 @@
+//! StatusNotifierItem tray icon with a DBusMenu, mirroring the macOS status
+//! item: ○ while idle, ● while recording, and the same three menu entries.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use zbus::blocking::Connection;
+use zbus::object_server::SignalEmitter;
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, StructureBuilder, Type, Value};
+use zbus::{fdo, interface};
+
+const ITEM_PATH: &str = "/StatusNotifierItem";
+const MENU_PATH: &str = "/MenuBar";
+const ICON_SIZE: i32 = 22;
+
+/// DBusMenu item ids
+const MENU_ROOT: i32 = 0;
+const MENU_AUTO_RETURN: i32 = 1;
+const MENU_EDIT_KEYWORDS: i32 = 2;
+const MENU_SEPARATOR: i32 = 3;
+const MENU_QUIT: i32 = 4;
+
+/// (width, height, ARGB32 data)
+type Pixmap = (i32, i32, Vec<u8>);
+
+/// What the menu entries do; implemented by the front end.
+pub trait TrayHandler: Send + Sync {
+    /// Flip "Press Return after paste" and return the new value
+    fn toggle_auto_return(&self) -> bool;
+    fn edit_keywords(&self);
+    fn quit(&self);
+}
+
+pub struct Tray {
+    conn: Connection,
+}
+
+impl Tray {
+    /// Export the item on the session bus and register it with the host.
+    pub fn start(handler: Arc<dyn TrayHandler>, auto_return: bool) -> zbus::Result<Self> {
+        Self::start_on(Connection::session()?, handler, auto_return)
+    }
+
+    /// Export on an existing connection (e.g. a private bus in tests).
+    pub fn start_on(conn: Connection, handler: Arc<dyn TrayHandler>, auto_return: bool) -> zbus::Result<Self> {
+        let name = format!("org.kde.StatusNotifierItem-{}-1", std::process::id());
+        {
+            let server = conn.object_server();
+            server.at(ITEM_PATH, StatusNotifierItem { recording: false })?;
+            server.at(MENU_PATH, DbusMenu { handler, auto_return, revision: 1 })?;
+        }
+        conn.request_name(name.as_str())?;
+
+        let watcher = zbus::blocking::Proxy::new(
+            &conn,
+            "org.kde.StatusNotifierWatcher",
+            "/StatusNotifierWatcher",
+            "org.kde.StatusNotifierWatcher",
+        )?;
+        watcher.call_method("RegisterStatusNotifierItem", &(name.as_str(),))?;
+        Ok(Tray { conn })
+    }
+
+    /// Switch between the idle and recording icons.
+    pub fn set_recording(&self, recording: bool) -> zbus::Result<()> {
+        let iface = self.conn.object_server().interface::<_, StatusNotifierItem>(ITEM_PATH)?;
+        iface.get_mut().recording = recording;
+        for signal in ["NewIcon", "NewToolTip"] {
+            self.conn.emit_signal(None::<()>, ITEM_PATH, "org.kde.StatusNotifierItem", signal, &())?;
+        }
+        Ok(())
+    }
+}
+
+// ============================================================================
+// org.kde.StatusNotifierItem
+// ============================================================================
+
+struct StatusNotifierItem {
+    recording: bool,
+}
+
+/// ARGB32 (network byte order) ring or disc, like the ○ / ● menu bar titles
+fn circle_pixmap(filled: bool) -> Pixmap {
+    let size = ICON_SIZE;
+    let (r, g, b) = if filled { (0xe0, 0x1b, 0x24) } else { (0xdd, 0xdd, 0xdd) };
+    let center = (size as f32 - 1.0) / 2.0;
+    let outer = size as f32 * 0.4;
+    let inner = if filled { 0.0 } else { outer - 2.0 };
+    let mut data = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let d = ((x as f32 - center).powi(2) + (y as f32 - center).powi(2)).sqrt();
+            // One pixel of antialiasing on each edge
+            let coverage = (outer + 0.5 - d).clamp(0.0, 1.0) * (d - inner + 0.5).clamp(0.0, 1.0);
+            data.extend_from_slice(&[(coverage * 255.0) as u8, r, g, b]);
+        }
+    }
+    (size, size, data)
+}
+
+#[interface(name = "org.kde.StatusNotifierItem")]
+impl StatusNotifierItem {
+    #[zbus(property)]
+    fn category(&self) -> &str {
+        "ApplicationStatus"
+    }
+
+    #[zbus(property)]
+    fn id(&self) -> &str {
+        "fnkey"
+    }
+
+    #[zbus(property)]
+    fn title(&self) -> &str {
+        "FnKey"
+    }
+
+    #[zbus(property)]
+    fn status(&self) -> &str {
+        "Active"
+    }
+
+    #[zbus(property)]
+    fn icon_name(&self) -> &str {
+        ""
+    }
+
+    #[zbus(property)]
+    fn icon_pixmap(&self) -> Vec<Pixmap> {
+        vec![circle_pixmap(self.recording)]
+    }
+
+    #[zbus(property)]
+    fn tool_tip(&self) -> (String, Vec<Pixmap>, String, String) {
+        let text = if self.recording { "Recording…" } else { "Hold the hotkey to dictate" };
+        (String::new(), Vec::new(), "FnKey".to_string(), text.to_string())
+    }
+
+    #[zbus(property)]
+    fn item_is_menu(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn menu(&self) -> OwnedObjectPath {
+        OwnedObjectPath::try_from(MENU_PATH).unwrap()
+    }
+
+    fn activate(&self, _x: i32, _y: i32) {}
+
+    fn secondary_activate(&self, _x: i32, _y: i32) {}
+
+    fn context_menu(&self, _x: i32, _y: i32) {}
+
+    fn scroll(&self, _delta: i32, _orientation: &str) {}
+}
+
+// ============================================================================
+// com.canonical.dbusmenu
+// ============================================================================
+
+struct DbusMenu {
+    handler: Arc<dyn TrayHandler>,
+    auto_return: bool,
+    revision: u32,
+}
+
+/// One node of GetLayout's (ia{sv}av) tree
+#[derive(serde::Serialize, Type)]
+struct Layout {
+    id: i32,
+    properties: HashMap<String, OwnedValue>,
+    children: Vec<OwnedValue>,
+}
+
+fn owned<'a>(v: impl Into<Value<'a>>) -> OwnedValue {
+    OwnedValue::try_from(v.into()).expect("menu values carry no fds")
+}
+
+impl DbusMenu {
+    fn properties(&self, id: i32) -> HashMap<String, OwnedValue> {
+        let mut props = HashMap::new();
+        match id {
+            MENU_ROOT => {
+                props.insert("children-display".to_string(), owned("submenu"));
+            }
+            MENU_AUTO_RETURN => {
+                props.insert("label".to_string(), owned("Press Return after paste"));
+                props.insert("toggle-type".to_string(), owned("checkmark"));
+                props.insert("toggle-state".to_string(), owned(i32::from(self.auto_return)));
+            }
+            MENU_EDIT_KEYWORDS => {
+                props.insert("label".to_string(), owned("Edit Keywords…"));
+            }
+            MENU_SEPARATOR => {
+                props.insert("type".to_string(), owned("separator"));
+            }
+            MENU_QUIT => {
+                props.insert("label".to_string(), owned("Quit FnKey"));
+            }
+            _ => {}
+        }
+        props
+    }
+
+    fn child_value(&self, id: i32) -> OwnedValue {
+        let structure = StructureBuilder::new()
+            .add_field(id)
+            .add_field(self.properties(id))
+            .add_field(Vec::<OwnedValue>::new())
+            .build()
+            .expect("non-empty structure");
+        owned(structure)
+    }
+}
+
+#[interface(name = "com.canonical.dbusmenu")]
+impl DbusMenu {
+    fn get_layout(&self, parent_id: i32, _recursion_depth: i32, _property_names: Vec<String>) -> (u32, Layout) {
+        let children = if parent_id == MENU_ROOT {
+            [MENU_AUTO_RETURN, MENU_EDIT_KEYWORDS, MENU_SEPARATOR, MENU_QUIT]
+                .iter()
+                .map(|&id| self.child_value(id))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        (self.revision, Layout { id: parent_id, properties: self.properties(parent_id), children })
+    }
+
+    fn get_group_properties(
+        &self,
+        ids: Vec<i32>,
+        _property_names: Vec<String>,
+    ) -> Vec<(i32, HashMap<String, OwnedValue>)> {
+        ids.into_iter().map(|id| (id, self.properties(id))).collect()
+    }
+
+    fn get_property(&self, id: i32, name: &str) -> fdo::Result<OwnedValue> {
+        self.properties(id)
+            .remove(name)
+            .ok_or_else(|| fdo::Error::InvalidArgs(format!("no property {} on item {}", name, id)))
+    }
+
+    async fn event(
+        &mut self,
+        id: i32,
+        event_id: &str,
+        _data: Value<'_>,
+        _timestamp: u32,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        if event_id != "clicked" {
+            return Ok(());
+        }
+        match id {
+            MENU_AUTO_RETURN => {
+                self.auto_return = self.handler.toggle_auto_return();
+                self.revision += 1;
+                Self::layout_updated(&emitter, self.revision, MENU_ROOT).await?;
+            }
+            MENU_EDIT_KEYWORDS => self.handler.edit_keywords(),
+            MENU_QUIT => self.handler.quit(),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn about_to_show(&self, _id: i32) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn version(&self) -> u32 {
+        3
+    }
+
+    #[zbus(property)]
+    fn text_direction(&self) -> &str {
+        "ltr"
+    }
+
+    #[zbus(property)]
+    fn status(&self) -> &str {
+        "normal"
+    }
+
+    #[zbus(property)]
+    fn icon_theme_path(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    #[zbus(signal)]
+    async fn layout_updated(emitter: &SignalEmitter<'_>, revision: u32, parent: i32) -> zbus::Result<()>;
+}
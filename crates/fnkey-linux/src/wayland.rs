@@ -0,0 +1,394 @@
+//! Wayland-native output: clipboard through wlr data-control, key injection
+//! through zwp_virtual_keyboard (sway, Hyprland, river, KDE and other
+//! wlroots-style compositors).
+//!
+//! Wayland objects live on a dedicated thread that also serves clipboard
+//! reads; [`WaylandOutput`] is a cheap handle that sends it requests.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::fd::{AsFd, AsRawFd};
+use std::os::unix::net::UnixStream;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+use fnkey_core::log_error;
+use wayland_client::globals::{registry_queue_init, GlobalListContents};
+use wayland_client::protocol::{wl_registry, wl_seat};
+use wayland_client::{
+    delegate_noop, event_created_child, Connection, Dispatch, EventQueue, QueueHandle,
+};
+use wayland_protocols_misc::zwp_virtual_keyboard_v1::client::{
+    zwp_virtual_keyboard_manager_v1::ZwpVirtualKeyboardManagerV1,
+    zwp_virtual_keyboard_v1::ZwpVirtualKeyboardV1,
+};
+use wayland_protocols_wlr::data_control::v1::client::{
+    zwlr_data_control_device_v1::{self, ZwlrDataControlDeviceV1},
+    zwlr_data_control_manager_v1::ZwlrDataControlManagerV1,
+    zwlr_data_control_offer_v1::ZwlrDataControlOfferV1,
+    zwlr_data_control_source_v1::{self, ZwlrDataControlSourceV1},
+};
+
+use crate::paste::{ClipboardSink, Key, Keyboard};
+
+const TEXT_MIME_TYPES: &[&str] = &["text/plain;charset=utf-8", "text/plain", "UTF8_STRING", "TEXT", "STRING"];
+
+/// wl_keyboard keymap format
+const KEYMAP_FORMAT_XKB_V1: u32 = 1;
+const KEY_PRESSED: u32 = 1;
+const KEY_RELEASED: u32 = 0;
+/// Modifier masks for the "complete" xkb compat map
+const MOD_SHIFT: u32 = 1;
+const MOD_CONTROL: u32 = 4;
+/// Distinct keysyms per uploaded keymap (keycodes 9..=255)
+const MAX_KEYMAP_KEYS: usize = 240;
+
+/// Keys of the base keymap, in keycode order
+const BASE_KEYS: [(Key, &str); 4] =
+    [(Key::Ctrl, "Control_L"), (Key::Shift, "Shift_L"), (Key::V, "v"), (Key::Return, "Return")];
+
+enum Request {
+    SetClipboard(String),
+    Chord(Vec<Key>, Key),
+    Type(String),
+}
+
+type Reply = mpsc::Sender<io::Result<()>>;
+
+/// Handle to the Wayland thread; clone it to use as both keyboard and clipboard.
+#[derive(Clone)]
+pub struct WaylandOutput {
+    tx: mpsc::Sender<(Request, Reply)>,
+    /// Wakes the thread out of poll() when a request is queued
+    wake: Arc<Mutex<UnixStream>>,
+}
+
+impl WaylandOutput {
+    /// Connect to $WAYLAND_DISPLAY. Fails if the compositor lacks either protocol.
+    pub fn connect() -> io::Result<Self> {
+        Self::from_connection(Connection::connect_to_env().map_err(wl_error)?)
+    }
+
+    pub fn from_connection(conn: Connection) -> io::Result<Self> {
+        let (globals, mut queue) = registry_queue_init::<WlState>(&conn).map_err(wl_error)?;
+        let qh = queue.handle();
+        let seat: wl_seat::WlSeat = globals.bind(&qh, 1..=7, ()).map_err(wl_error)?;
+        let vk_manager: ZwpVirtualKeyboardManagerV1 = globals.bind(&qh, 1..=1, ()).map_err(wl_error)?;
+        let dc_manager: ZwlrDataControlManagerV1 = globals.bind(&qh, 1..=2, ()).map_err(wl_error)?;
+
+        let keyboard = vk_manager.create_virtual_keyboard(&seat, &qh, ());
+        let device = dc_manager.get_data_device(&seat, &qh, ());
+        let mut state = WlState {
+            keyboard,
+            dc_manager,
+            device,
+            source: None,
+            started: Instant::now(),
+        };
+        state.upload_keymap(&base_keymap())?;
+        queue.roundtrip(&mut state).map_err(wl_error)?;
+
+        let (tx, rx) = mpsc::channel();
+        let (wake_rx, wake_tx) = UnixStream::pair()?;
+        wake_rx.set_nonblocking(true)?;
+        thread::spawn(move || {
+            if let Err(e) = run_loop(conn, queue, state, rx, wake_rx) {
+                log_error(&format!("Wayland output stopped: {}", e));
+            }
+        });
+        Ok(WaylandOutput { tx, wake: Arc::new(Mutex::new(wake_tx)) })
+    }
+
+    fn request(&self, req: Request) -> io::Result<()> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+        self.tx.send((req, reply_tx)).map_err(|_| disconnected())?;
+        self.wake.lock().unwrap().write_all(&[1])?;
+        reply_rx.recv().map_err(|_| disconnected())?
+    }
+
+    /// Type `text` into the focused client, using a temporary keymap that
+    /// maps each distinct character to its own keycode.
+    pub fn type_text(&self, text: &str) -> io::Result<()> {
+        self.request(Request::Type(text.to_string()))
+    }
+}
+
+impl Keyboard for WaylandOutput {
+    fn chord(&mut self, modifiers: &[Key], key: Key) -> io::Result<()> {
+        self.request(Request::Chord(modifiers.to_vec(), key))
+    }
+
+    fn type_text(&mut self, text: &str) -> io::Result<()> {
+        WaylandOutput::type_text(self, text)
+    }
+}
+
+impl ClipboardSink for WaylandOutput {
+    fn set_text(&mut self, text: &str) -> io::Result<()> {
+        self.request(Request::SetClipboard(text.to_string()))
+    }
+}
+
+fn wl_error(e: impl std::fmt::Display) -> io::Error {
+    io::Error::other(format!("Wayland: {}", e))
+}
+
+fn disconnected() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "Wayland output thread has stopped")
+}
+
+fn run_loop(
+    conn: Connection,
+    mut queue: EventQueue<WlState>,
+    mut state: WlState,
+    rx: mpsc::Receiver<(Request, Reply)>,
+    mut wake: UnixStream,
+) -> io::Result<()> {
+    let qh = queue.handle();
+    loop {
+        loop {
+            match rx.try_recv() {
+                Ok((req, reply)) => {
+                    let result = state.handle(req, &qh);
+                    let result = result.and_then(|_| queue.roundtrip(&mut state).map(|_| ()).map_err(wl_error));
+                    let _ = reply.send(result);
+                }
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => return Ok(()),
+            }
+        }
+        queue.dispatch_pending(&mut state).map_err(wl_error)?;
+        conn.flush().map_err(wl_error)?;
+
+        let Some(guard) = queue.prepare_read() else { continue };
+        let mut fds = [
+            libc::pollfd { fd: guard.connection_fd().as_raw_fd(), events: libc::POLLIN, revents: 0 },
+            libc::pollfd { fd: wake.as_raw_fd(), events: libc::POLLIN, revents: 0 },
+        ];
+        let n = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, -1) };
+        if n < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err);
+        }
+        if fds[0].revents != 0 {
+            guard.read().map_err(wl_error)?;
+        } else {
+            drop(guard);
+        }
+        if fds[1].revents != 0 {
+            let mut buf = [0u8; 64];
+            while matches!(wake.read(&mut buf), Ok(n) if n > 0) {}
+        }
+    }
+}
+
+struct WlState {
+    keyboard: ZwpVirtualKeyboardV1,
+    dc_manager: ZwlrDataControlManagerV1,
+    device: ZwlrDataControlDeviceV1,
+    /// Current selection we own, with the text it serves
+    source: Option<(ZwlrDataControlSourceV1, Arc<String>)>,
+    started: Instant,
+}
+
+impl WlState {
+    fn handle(&mut self, req: Request, qh: &QueueHandle<WlState>) -> io::Result<()> {
+        match req {
+            Request::SetClipboard(text) => {
+                let source = self.dc_manager.create_data_source(qh, ());
+                for mime in TEXT_MIME_TYPES {
+                    source.offer(mime.to_string());
+                }
+                self.device.set_selection(Some(&source));
+                if let Some((old, _)) = self.source.replace((source, Arc::new(text))) {
+                    old.destroy();
+                }
+                Ok(())
+            }
+            Request::Chord(modifiers, key) => {
+                let keycode = |k: Key| BASE_KEYS.iter().position(|&(b, _)| b == k).unwrap() as u32 + 1;
+                let mut mask = 0;
+                for &m in &modifiers {
+                    self.key(keycode(m), KEY_PRESSED);
+                    mask |= match m {
+                        Key::Ctrl => MOD_CONTROL,
+                        Key::Shift => MOD_SHIFT,
+                        _ => 0,
+                    };
+                    self.keyboard.modifiers(mask, 0, 0, 0);
+                }
+                self.key(keycode(key), KEY_PRESSED);
+                self.key(keycode(key), KEY_RELEASED);
+                for &m in modifiers.iter().rev() {
+                    self.key(keycode(m), KEY_RELEASED);
+                }
+                self.keyboard.modifiers(0, 0, 0, 0);
+                Ok(())
+            }
+            Request::Type(text) => {
+                let chars: Vec<char> = text.chars().collect();
+                for chunk in chunks_by_distinct(&chars, MAX_KEYMAP_KEYS) {
+                    let (keymap, codes) = text_keymap(chunk);
+                    self.upload_keymap(&keymap)?;
+                    for ch in chunk {
+                        let code = codes[ch];
+                        self.key(code, KEY_PRESSED);
+                        self.key(code, KEY_RELEASED);
+                    }
+                }
+                self.upload_keymap(&base_keymap())
+            }
+        }
+    }
+
+    fn key(&self, evdev_code: u32, key_state: u32) {
+        let time = self.started.elapsed().as_millis() as u32;
+        self.keyboard.key(time, evdev_code, key_state);
+    }
+
+    fn upload_keymap(&self, keymap: &str) -> io::Result<()> {
+        // The compositor mmaps the fd, so it must be a real file; unlink it right away
+        let dir = std::env::var_os("XDG_RUNTIME_DIR").unwrap_or_else(|| "/tmp".into());
+        let path = std::path::Path::new(&dir).join(format!("fnkey-keymap-{}", std::process::id()));
+        let mut file = File::options().read(true).write(true).create(true).truncate(true).open(&path)?;
+        let _ = std::fs::remove_file(&path);
+        file.write_all(keymap.as_bytes())?;
+        file.write_all(&[0])?;
+        file.flush()?;
+        self.keyboard.keymap(KEYMAP_FORMAT_XKB_V1, file.as_fd(), keymap.len() as u32 + 1);
+        Ok(())
+    }
+}
+
+/// Split `chars` into runs that each fit in one keymap.
+fn chunks_by_distinct(chars: &[char], max: usize) -> Vec<&[char]> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut seen = std::collections::HashSet::new();
+    for (i, ch) in chars.iter().enumerate() {
+        if seen.insert(*ch) && seen.len() > max {
+            chunks.push(&chars[start..i]);
+            start = i;
+            seen.clear();
+            seen.insert(*ch);
+        }
+    }
+    if start < chars.len() {
+        chunks.push(&chars[start..]);
+    }
+    chunks
+}
+
+fn keymap(symbols: &[String]) -> String {
+    let mut keycodes = String::new();
+    let mut syms = String::new();
+    for (i, sym) in symbols.iter().enumerate() {
+        keycodes.push_str(&format!("<K{}> = {};\n", i + 1, i + 9));
+        syms.push_str(&format!("key <K{}> {{[ {} ]}};\n", i + 1, sym));
+    }
+    format!(
+        "xkb_keymap {{\n\
+         xkb_keycodes \"fnkey\" {{\nminimum = 8;\nmaximum = 255;\n{}}};\n\
+         xkb_types \"fnkey\" {{ include \"complete\" }};\n\
+         xkb_compatibility \"fnkey\" {{ include \"complete\" }};\n\
+         xkb_symbols \"fnkey\" {{\n{}\
+         modifier_map Control {{ Control_L }};\n\
+         modifier_map Shift {{ Shift_L }};\n}};\n}};\n",
+        keycodes, syms
+    )
+}
+
+fn base_keymap() -> String {
+    keymap(&BASE_KEYS.iter().map(|(_, sym)| sym.to_string()).collect::<Vec<_>>())
+}
+
+/// Keymap with one key per distinct char, plus the evdev code of each
+fn text_keymap(chars: &[char]) -> (String, HashMap<char, u32>) {
+    let mut codes = HashMap::new();
+    let mut symbols = Vec::new();
+    for &ch in chars {
+        if codes.contains_key(&ch) {
+            continue;
+        }
+        let sym = match ch {
+            '\n' => "Return".to_string(),
+            '\t' => "Tab".to_string(),
+            _ => format!("U{:04X}", ch as u32),
+        };
+        symbols.push(sym);
+        codes.insert(ch, symbols.len() as u32);
+    }
+    (keymap(&symbols), codes)
+}
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for WlState {
+    fn event(
+        _: &mut Self,
+        _: &wl_registry::WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwlrDataControlDeviceV1, ()> for WlState {
+    fn event(
+        _: &mut Self,
+        _: &ZwlrDataControlDeviceV1,
+        event: zwlr_data_control_device_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        // We only write the selection; offers from other clients are dropped
+        if let zwlr_data_control_device_v1::Event::DataOffer { id } = event {
+            id.destroy();
+        }
+    }
+
+    event_created_child!(WlState, ZwlrDataControlDeviceV1, [
+        zwlr_data_control_device_v1::EVT_DATA_OFFER_OPCODE => (ZwlrDataControlOfferV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwlrDataControlSourceV1, ()> for WlState {
+    fn event(
+        state: &mut Self,
+        source: &ZwlrDataControlSourceV1,
+        event: zwlr_data_control_source_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_data_control_source_v1::Event::Send { fd, .. } => {
+                let Some((_, text)) = state.source.as_ref().filter(|(s, _)| s == source) else { return };
+                let text = Arc::clone(text);
+                // Write off-thread: the reader may be slow to drain the pipe
+                thread::spawn(move || {
+                    let _ = File::from(fd).write_all(text.as_bytes());
+                });
+            }
+            zwlr_data_control_source_v1::Event::Cancelled => {
+                if state.source.as_ref().is_some_and(|(s, _)| s == source) {
+                    state.source = None;
+                }
+                source.destroy();
+            }
+            _ => {}
+        }
+    }
+}
+
+delegate_noop!(WlState: ignore wl_seat::WlSeat);
+delegate_noop!(WlState: ignore ZwlrDataControlOfferV1);
+delegate_noop!(WlState: ZwpVirtualKeyboardManagerV1);
+delegate_noop!(WlState: ZwpVirtualKeyboardV1);
+delegate_noop!(WlState: ZwlrDataControlManagerV1);
@@ -0,0 +1,76 @@
+//! Drives `EvdevHotkey` from a uinput virtual keyboard; skipped when
+//! /dev/uinput isn't available (containers, CI without the module).
+
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+use evdev::uinput::VirtualDevice;
+use evdev::{AttributeSet, EventType, InputEvent, KeyCode};
+use fnkey_linux::hotkey::{is_modifier, parse_key, EvdevHotkey, DEFAULT_KEY, FALLBACK_KEY};
+
+fn virtual_keyboard() -> Option<VirtualDevice> {
+    let mut keys = AttributeSet::<KeyCode>::new();
+    keys.insert(KeyCode::KEY_FN);
+    keys.insert(KeyCode::KEY_A);
+    let builder = match VirtualDevice::builder() {
+        Ok(b) => b,
+        Err(e) => {
+            eprintln!("skipping: /dev/uinput unavailable ({})", e);
+            return None;
+        }
+    };
+    builder.name("fnkey test keyboard").with_keys(&keys).ok()?.build().ok()
+}
+
+fn key(code: KeyCode, value: i32) -> InputEvent {
+    InputEvent::new(EventType::KEY.0, code.0, value)
+}
+
+#[test]
+fn press_and_release_reach_callback() {
+    let Some(mut keyboard) = virtual_keyboard() else { return };
+    // udev needs a moment to create the node
+    thread::sleep(Duration::from_millis(300));
+    let path = keyboard.enumerate_dev_nodes_blocking().unwrap().next().unwrap().unwrap();
+    let hotkey = EvdevHotkey::open(&path, KeyCode::KEY_FN).unwrap();
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = hotkey.run(Arc::new(move |pressed| {
+            let _ = tx.send(pressed);
+        }));
+    });
+
+    keyboard.emit(&[key(KeyCode::KEY_FN, 1)]).unwrap();
+    keyboard.emit(&[key(KeyCode::KEY_A, 1), key(KeyCode::KEY_A, 0)]).unwrap();
+    keyboard.emit(&[key(KeyCode::KEY_FN, 2)]).unwrap();
+    keyboard.emit(&[key(KeyCode::KEY_FN, 0)]).unwrap();
+
+    let timeout = Duration::from_secs(2);
+    assert_eq!(rx.recv_timeout(timeout), Ok(true));
+    assert_eq!(rx.recv_timeout(timeout), Ok(false));
+    assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+}
+
+#[test]
+fn device_without_hotkey_is_rejected() {
+    let Some(mut keyboard) = virtual_keyboard() else { return };
+    thread::sleep(Duration::from_millis(300));
+    let path = keyboard.enumerate_dev_nodes_blocking().unwrap().next().unwrap().unwrap();
+    assert!(EvdevHotkey::open(&path, KeyCode::KEY_RIGHTCTRL).is_err());
+}
+
+#[test]
+fn key_names_and_codes_parse() {
+    assert_eq!(parse_key("KEY_RIGHTCTRL"), Some(KeyCode::KEY_RIGHTCTRL));
+    assert_eq!(parse_key("rightalt"), Some(KeyCode::KEY_RIGHTALT));
+    assert_eq!(parse_key("464"), Some(KeyCode::KEY_FN));
+    assert_eq!(parse_key("not_a_key"), None);
+}
+
+#[test]
+fn right_alt_fallback_is_a_modifier() {
+    assert!(is_modifier(FALLBACK_KEY));
+    assert!(!is_modifier(DEFAULT_KEY));
+}
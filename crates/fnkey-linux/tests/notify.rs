@@ -0,0 +1,157 @@
+//! Runs the notifier against a stand-in notification server on a private
+//! dbus-daemon. Skipped when dbus-daemon isn't installed.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+
+use fnkey_core::platform::Notification;
+use fnkey_linux::notify::Notifier;
+use zbus::blocking::connection::Builder;
+use zbus::blocking::Connection;
+use zbus::interface;
+use zbus::zvariant::OwnedValue;
+
+struct Bus {
+    child: Child,
+    address: String,
+}
+
+impl Drop for Bus {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn private_bus() -> Option<Bus> {
+    let mut child = match Command::new("dbus-daemon")
+        .args(["--session", "--print-address", "--nofork"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+    {
+        Ok(c) => c,
+        Err(_) => {
+            eprintln!("skipping: dbus-daemon not installed");
+            return None;
+        }
+    };
+    let mut line = String::new();
+    BufReader::new(child.stdout.take()?).read_line(&mut line).ok()?;
+    Some(Bus { child, address: line.trim().to_string() })
+}
+
+fn connect(bus: &Bus) -> Connection {
+    Builder::address(bus.address.as_str()).unwrap().build().unwrap()
+}
+
+#[derive(Debug)]
+struct Shown {
+    body: String,
+    actions: Vec<String>,
+    urgency: u8,
+    timeout: i32,
+}
+
+struct Server {
+    capabilities: Vec<String>,
+    shown: Arc<Mutex<Vec<Shown>>>,
+}
+
+#[interface(name = "org.freedesktop.Notifications")]
+impl Server {
+    fn get_capabilities(&self) -> Vec<String> {
+        self.capabilities.clone()
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn notify(
+        &self,
+        _app_name: &str,
+        _replaces_id: u32,
+        _app_icon: &str,
+        _summary: &str,
+        body: &str,
+        actions: Vec<String>,
+        hints: HashMap<String, OwnedValue>,
+        timeout: i32,
+    ) -> u32 {
+        let urgency = hints.get("urgency").and_then(|v| u8::try_from(v).ok()).unwrap_or(1);
+        let mut shown = self.shown.lock().unwrap();
+        shown.push(Shown { body: body.to_string(), actions, urgency, timeout });
+        shown.len() as u32
+    }
+}
+
+fn start_server(bus: &Bus, capabilities: &[&str]) -> (Connection, Arc<Mutex<Vec<Shown>>>) {
+    let shown = Arc::new(Mutex::new(Vec::new()));
+    let conn = connect(bus);
+    let server = Server { capabilities: capabilities.iter().map(|c| c.to_string()).collect(), shown: Arc::clone(&shown) };
+    conn.object_server().at("/org/freedesktop/Notifications", server).unwrap();
+    conn.request_name("org.freedesktop.Notifications").unwrap();
+    (conn, shown)
+}
+
+fn invoke(server: &Connection, id: u32, key: &str) {
+    server
+        .emit_signal(
+            None::<()>,
+            "/org/freedesktop/Notifications",
+            "org.freedesktop.Notifications",
+            "ActionInvoked",
+            &(id, key),
+        )
+        .unwrap();
+}
+
+#[test]
+fn urgency_maps_to_hint() {
+    let Some(bus) = private_bus() else { return };
+    let (_server, shown) = start_server(&bus, &["body"]);
+    let notifier = Notifier::connect_on(connect(&bus)).unwrap();
+
+    notifier.show(Notification::info("Deepgram failed, using Groq fallback")).unwrap();
+    notifier.show(Notification::critical("Transcription failed (both backends)")).unwrap();
+
+    let shown = shown.lock().unwrap();
+    assert_eq!(shown[0].body, "Deepgram failed, using Groq fallback");
+    assert_eq!((shown[0].urgency, shown[0].timeout), (1, -1));
+    assert_eq!((shown[1].urgency, shown[1].timeout), (2, 0));
+}
+
+#[test]
+fn retry_runs_when_action_invoked() {
+    let Some(bus) = private_bus() else { return };
+    let (server, shown) = start_server(&bus, &["body", "actions"]);
+    let notifier = Notifier::connect_on(connect(&bus)).unwrap();
+
+    let (tx, rx) = mpsc::channel();
+    let id = notifier
+        .show(Notification::critical("Transcription failed").with_action("Retry", move || tx.send(()).unwrap()))
+        .unwrap();
+    let actions = shown.lock().unwrap()[0].actions.clone();
+    assert_eq!(actions.len(), 2);
+    assert_eq!(actions[1], "Retry");
+
+    invoke(&server, id, &actions[0]);
+    rx.recv_timeout(Duration::from_secs(5)).expect("action should run");
+}
+
+#[test]
+fn actions_dropped_without_server_support() {
+    let Some(bus) = private_bus() else { return };
+    let (_server, shown) = start_server(&bus, &["body"]);
+    let notifier = Notifier::connect_on(connect(&bus)).unwrap();
+
+    notifier.show(Notification::critical("Transcription failed").with_action("Retry", || {})).unwrap();
+    assert!(shown.lock().unwrap()[0].actions.is_empty());
+}
+
+#[test]
+fn no_server_is_an_error() {
+    let Some(bus) = private_bus() else { return };
+    assert!(Notifier::connect_on(connect(&bus)).is_err());
+}
@@ -0,0 +1,112 @@
+//! Checks the key sequences `Paster` injects by reading back a uinput
+//! keyboard; skipped when /dev/uinput isn't available.
+
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use evdev::{Device, EventSummary, KeyCode};
+use fnkey_linux::paste::{is_terminal, PasteChord, PasteMode, Paster, UinputKeyboard};
+
+/// Open the virtual keyboard and a reader on its /dev/input node
+fn keyboard_with_reader(v_key: Option<KeyCode>) -> Option<(UinputKeyboard, mpsc::Receiver<(KeyCode, i32)>)> {
+    let mut keyboard = match UinputKeyboard::new(v_key) {
+        Ok(k) => k,
+        Err(e) => {
+            eprintln!("skipping: /dev/uinput unavailable ({})", e);
+            return None;
+        }
+    };
+    let path = keyboard.device_mut().enumerate_dev_nodes_blocking().ok()?.next()?.ok()?;
+    let mut device = Device::open(path).ok()?;
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || loop {
+        let Ok(events) = device.fetch_events() else { return };
+        for event in events {
+            if let EventSummary::Key(_, code, value) = event.destructure() {
+                let _ = tx.send((code, value));
+            }
+        }
+    });
+    Some((keyboard, rx))
+}
+
+fn collect(rx: &mpsc::Receiver<(KeyCode, i32)>, n: usize) -> Vec<(KeyCode, i32)> {
+    (0..n).map_while(|_| rx.recv_timeout(Duration::from_secs(2)).ok()).collect()
+}
+
+#[test]
+fn ctrl_v_then_return() {
+    let Some((keyboard, rx)) = keyboard_with_reader(None) else { return };
+    let paster = Paster::new(Some(Box::new(keyboard)), None, PasteChord::CtrlV, PasteMode::Paste);
+
+    paster.paste().unwrap();
+    paster.press_return().unwrap();
+
+    assert_eq!(
+        collect(&rx, 6),
+        vec![
+            (KeyCode::KEY_LEFTCTRL, 1),
+            (KeyCode::KEY_V, 1),
+            (KeyCode::KEY_V, 0),
+            (KeyCode::KEY_LEFTCTRL, 0),
+            (KeyCode::KEY_ENTER, 1),
+            (KeyCode::KEY_ENTER, 0),
+        ]
+    );
+}
+
+#[test]
+fn terminal_chord_adds_shift() {
+    let Some((keyboard, rx)) = keyboard_with_reader(None) else { return };
+    let paster = Paster::new(Some(Box::new(keyboard)), None, PasteChord::CtrlShiftV, PasteMode::Paste);
+
+    paster.paste().unwrap();
+
+    assert_eq!(
+        collect(&rx, 6),
+        vec![
+            (KeyCode::KEY_LEFTCTRL, 1),
+            (KeyCode::KEY_LEFTSHIFT, 1),
+            (KeyCode::KEY_V, 1),
+            (KeyCode::KEY_V, 0),
+            (KeyCode::KEY_LEFTSHIFT, 0),
+            (KeyCode::KEY_LEFTCTRL, 0),
+        ]
+    );
+}
+
+#[test]
+fn configured_v_key_replaces_qwerty_v() {
+    // Dvorak types v on the QWERTY period key
+    let Some((keyboard, rx)) = keyboard_with_reader(Some(KeyCode::KEY_DOT)) else { return };
+    let paster = Paster::new(Some(Box::new(keyboard)), None, PasteChord::CtrlV, PasteMode::Paste);
+
+    paster.paste().unwrap();
+
+    assert_eq!(
+        collect(&rx, 4),
+        vec![
+            (KeyCode::KEY_LEFTCTRL, 1),
+            (KeyCode::KEY_DOT, 1),
+            (KeyCode::KEY_DOT, 0),
+            (KeyCode::KEY_LEFTCTRL, 0),
+        ]
+    );
+}
+
+#[test]
+fn no_backend_is_an_error_not_a_panic() {
+    let paster = Paster::new(None, None, PasteChord::Auto, PasteMode::Paste);
+    assert!(paster.paste().is_err());
+    assert!(paster.press_return().is_err());
+}
+
+#[test]
+fn terminal_classes_and_chord_names() {
+    assert!(is_terminal("kitty"));
+    assert!(is_terminal("Gnome-terminal"));
+    assert!(!is_terminal("firefox"));
+    assert_eq!(PasteChord::parse("ctrl-shift-v"), Some(PasteChord::CtrlShiftV));
+    assert_eq!(PasteChord::parse("cmd-v"), None);
+}
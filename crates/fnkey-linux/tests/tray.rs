@@ -0,0 +1,187 @@
+//! Runs the tray against a private dbus-daemon with a stand-in
+//! StatusNotifierWatcher. Skipped when dbus-daemon isn't installed.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use fnkey_linux::tray::{Tray, TrayHandler};
+use zbus::blocking::connection::Builder;
+use zbus::blocking::{proxy, Connection, Proxy};
+use zbus::interface;
+use zbus::proxy::CacheProperties;
+use zbus::zvariant::OwnedValue;
+
+struct Bus {
+    child: Child,
+    address: String,
+}
+
+impl Drop for Bus {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn private_bus() -> Option<Bus> {
+    let mut child = match Command::new("dbus-daemon")
+        .args(["--session", "--print-address", "--nofork"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+    {
+        Ok(c) => c,
+        Err(_) => {
+            eprintln!("skipping: dbus-daemon not installed");
+            return None;
+        }
+    };
+    let mut line = String::new();
+    BufReader::new(child.stdout.take()?).read_line(&mut line).ok()?;
+    Some(Bus { child, address: line.trim().to_string() })
+}
+
+fn connect(bus: &Bus) -> Connection {
+    Builder::address(bus.address.as_str()).unwrap().build().unwrap()
+}
+
+#[derive(Default)]
+struct Watcher {
+    registered: Arc<Mutex<Vec<String>>>,
+}
+
+#[interface(name = "org.kde.StatusNotifierWatcher")]
+impl Watcher {
+    fn register_status_notifier_item(&self, service: &str) {
+        self.registered.lock().unwrap().push(service.to_string());
+    }
+}
+
+/// Serve a watcher on its own connection; the returned vec fills with
+/// registered item names.
+fn start_watcher(bus: &Bus) -> (Connection, Arc<Mutex<Vec<String>>>) {
+    let registered = Arc::new(Mutex::new(Vec::new()));
+    let conn = connect(bus);
+    conn.object_server()
+        .at("/StatusNotifierWatcher", Watcher { registered: Arc::clone(&registered) })
+        .unwrap();
+    conn.request_name("org.kde.StatusNotifierWatcher").unwrap();
+    (conn, registered)
+}
+
+#[derive(Default)]
+struct TestHandler {
+    auto_return: AtomicBool,
+    edits: AtomicUsize,
+}
+
+impl TrayHandler for TestHandler {
+    fn toggle_auto_return(&self) -> bool {
+        !self.auto_return.fetch_xor(true, Ordering::SeqCst)
+    }
+
+    fn edit_keywords(&self) {
+        self.edits.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn quit(&self) {}
+}
+
+fn item_name() -> String {
+    format!("org.kde.StatusNotifierItem-{}-1", std::process::id())
+}
+
+fn uncached<'a>(conn: &Connection, path: &'a str, iface: &'a str) -> Proxy<'a> {
+    proxy::Builder::new(conn)
+        .destination(item_name())
+        .unwrap()
+        .path(path)
+        .unwrap()
+        .interface(iface)
+        .unwrap()
+        .cache_properties(CacheProperties::No)
+        .build()
+        .unwrap()
+}
+
+type Props = Vec<(i32, HashMap<String, OwnedValue>)>;
+/// GetLayout reply: (revision, (id, properties, children))
+type LayoutReply = (u32, (i32, HashMap<String, OwnedValue>, Vec<OwnedValue>));
+
+fn prop_str(props: &HashMap<String, OwnedValue>, key: &str) -> String {
+    String::try_from(props[key].try_clone().unwrap()).unwrap()
+}
+
+fn prop_i32(props: &HashMap<String, OwnedValue>, key: &str) -> i32 {
+    i32::try_from(&props[key]).unwrap()
+}
+
+#[test]
+fn registers_and_serves_menu() {
+    let Some(bus) = private_bus() else { return };
+    let (_watcher_conn, registered) = start_watcher(&bus);
+
+    let handler = Arc::new(TestHandler::default());
+    let _tray = Tray::start_on(connect(&bus), handler.clone(), false).unwrap();
+    assert_eq!(*registered.lock().unwrap(), vec![item_name()]);
+
+    let client = connect(&bus);
+    let item = uncached(&client, "/StatusNotifierItem", "org.kde.StatusNotifierItem");
+    assert!(item.get_property::<bool>("ItemIsMenu").unwrap());
+    let menu_path: zbus::zvariant::OwnedObjectPath = item.get_property("Menu").unwrap();
+
+    let menu = uncached(&client, menu_path.as_str(), "com.canonical.dbusmenu");
+    let (revision, (root, _, children)): LayoutReply = menu.call("GetLayout", &(0i32, -1i32, Vec::<String>::new())).unwrap();
+    assert_eq!(root, 0);
+    assert_eq!(children.len(), 4);
+
+    let props: Props = menu.call("GetGroupProperties", &(vec![1i32, 2, 3, 4], Vec::<String>::new())).unwrap();
+    assert_eq!(prop_str(&props[0].1, "label"), "Press Return after paste");
+    assert_eq!(prop_i32(&props[0].1, "toggle-state"), 0);
+    assert_eq!(prop_str(&props[1].1, "label"), "Edit Keywords…");
+    assert_eq!(prop_str(&props[2].1, "type"), "separator");
+    assert_eq!(prop_str(&props[3].1, "label"), "Quit FnKey");
+
+    // Clicking the checkmark toggles through the handler and bumps the revision
+    let data = zbus::zvariant::Value::from(0i32);
+    let () = menu.call("Event", &(1i32, "clicked", &data, 0u32)).unwrap();
+    assert!(handler.auto_return.load(Ordering::SeqCst));
+    let props: Props = menu.call("GetGroupProperties", &(vec![1i32], Vec::<String>::new())).unwrap();
+    assert_eq!(prop_i32(&props[0].1, "toggle-state"), 1);
+    let (new_revision, _): LayoutReply = menu.call("GetLayout", &(0i32, -1i32, Vec::<String>::new())).unwrap();
+    assert!(new_revision > revision);
+
+    let () = menu.call("Event", &(2i32, "clicked", &data, 0u32)).unwrap();
+    assert_eq!(handler.edits.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn recording_swaps_the_icon() {
+    let Some(bus) = private_bus() else { return };
+    let (_watcher_conn, _) = start_watcher(&bus);
+    let tray = Tray::start_on(connect(&bus), Arc::new(TestHandler::default()), false).unwrap();
+
+    let client = connect(&bus);
+    let item = uncached(&client, "/StatusNotifierItem", "org.kde.StatusNotifierItem");
+    let idle: Vec<(i32, i32, Vec<u8>)> = item.get_property("IconPixmap").unwrap();
+    assert_eq!(idle.len(), 1);
+    let (w, h, data) = &idle[0];
+    assert_eq!(data.len(), (w * h * 4) as usize);
+
+    tray.set_recording(true).unwrap();
+    let recording: Vec<(i32, i32, Vec<u8>)> = item.get_property("IconPixmap").unwrap();
+    assert_ne!(idle, recording);
+
+    tray.set_recording(false).unwrap();
+    let back: Vec<(i32, i32, Vec<u8>)> = item.get_property("IconPixmap").unwrap();
+    assert_eq!(idle, back);
+}
+
+#[test]
+fn no_watcher_is_an_error() {
+    let Some(bus) = private_bus() else { return };
+    assert!(Tray::start_on(connect(&bus), Arc::new(TestHandler::default()), false).is_err());
+}
@@ -0,0 +1,93 @@
+//! Runs `WaylandOutput` against a headless sway (wlroots implements both
+//! data-control and virtual-keyboard). Skipped when sway isn't installed.
+
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use fnkey_linux::paste::{ClipboardSink, Key, Keyboard};
+use fnkey_linux::wayland::WaylandOutput;
+use wayland_client::Connection;
+
+struct Compositor {
+    child: Child,
+    runtime_dir: PathBuf,
+    socket: PathBuf,
+}
+
+impl Drop for Compositor {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = std::fs::remove_dir_all(&self.runtime_dir);
+    }
+}
+
+fn on_path(bin: &str) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|paths| std::env::split_paths(&paths).any(|dir| dir.join(bin).is_file()))
+}
+
+fn headless_sway() -> Option<Compositor> {
+    if !on_path("sway") {
+        eprintln!("skipping: sway not installed");
+        return None;
+    }
+    let runtime_dir = std::env::temp_dir().join(format!("fnkey-wl-test-{}", std::process::id()));
+    std::fs::create_dir_all(&runtime_dir).ok()?;
+    let config = runtime_dir.join("sway.conf");
+    std::fs::write(&config, "").ok()?;
+    let child = Command::new("sway")
+        .arg("-c")
+        .arg(&config)
+        .env("XDG_RUNTIME_DIR", &runtime_dir)
+        .env("WLR_BACKENDS", "headless")
+        .env("WLR_LIBINPUT_NO_DEVICES", "1")
+        .env_remove("WAYLAND_DISPLAY")
+        .env_remove("DISPLAY")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    let mut compositor = Compositor { child, runtime_dir: runtime_dir.clone(), socket: PathBuf::new() };
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while Instant::now() < deadline {
+        if let Some(socket) = find_socket(&runtime_dir) {
+            compositor.socket = socket;
+            return Some(compositor);
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    eprintln!("skipping: headless sway did not start");
+    None
+}
+
+fn find_socket(dir: &Path) -> Option<PathBuf> {
+    std::fs::read_dir(dir).ok()?.flatten().map(|e| e.path()).find(|p| {
+        p.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with("wayland-") && !n.ends_with(".lock"))
+    })
+}
+
+#[test]
+fn clipboard_chord_and_typing_against_headless_sway() {
+    let Some(compositor) = headless_sway() else { return };
+    let socket = UnixStream::connect(&compositor.socket).unwrap();
+    let mut output = WaylandOutput::from_connection(Connection::from_socket(socket).unwrap()).unwrap();
+
+    output.set_text("hello from fnkey").unwrap();
+    output.chord(&[Key::Ctrl], Key::V).unwrap();
+    output.chord(&[], Key::Return).unwrap();
+    output.type_text("Grüße, мир\n").unwrap();
+
+    if on_path("wl-paste") {
+        let pasted = Command::new("wl-paste")
+            .arg("--no-newline")
+            .env("XDG_RUNTIME_DIR", &compositor.runtime_dir)
+            .env("WAYLAND_DISPLAY", &compositor.socket)
+            .output()
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&pasted.stdout), "hello from fnkey");
+    }
+}
@@ -0,0 +1,142 @@
+//! Headless subcommands that run the pipeline without a hotkey or GUI.
+//!
+//!   fnkey transcribe <file>...   decode WAV/FLAC/MP3/OGG and print the transcript
+//!   fnkey stream [options]       raw PCM on stdin, JSON-line transcripts on stdout
+//!   fnkey ctl <command>          talk to the running app over its control socket
+
+use std::path::Path;
+
+use fnkey_core::audio::{decode_file, PcmFormat};
+use fnkey_core::clip::transcribe_clip;
+use fnkey_core::pipe::{run_pipe, PipeOptions};
+use fnkey_core::Config;
+
+const USAGE: &str = "usage: fnkey transcribe <file>...
+       fnkey stream [--rate 16000] [--format s16le|f32le] [--channels 1]
+       fnkey ctl start|stop|cancel|status|last-transcript|events";
+
+/// Run the subcommand named in `args` (argv without the program name).
+/// Returns None when `args` doesn't name one, so the GUI should start.
+pub fn dispatch(args: &[String]) -> Option<i32> {
+    let (command, rest) = args.split_first()?;
+    match command.as_str() {
+        "transcribe" => Some(transcribe(rest)),
+        "stream" => Some(stream(rest)),
+        #[cfg(unix)]
+        "ctl" => Some(ctl(rest)),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Some(0)
+        }
+        _ => None,
+    }
+}
+
+fn transcribe(files: &[String]) -> i32 {
+    if files.is_empty() || files.iter().any(|f| f == "--help" || f == "-h") {
+        eprintln!("{}", USAGE);
+        return 2;
+    }
+    let config = Config::load();
+    let mut status = 0;
+    for file in files {
+        let result = decode_file(Path::new(file))
+            .and_then(|(samples, rate)| transcribe_clip(&config, &samples, rate).map_err(|e| format!("{}: {}", file, e)));
+        match result {
+            Ok(text) => println!("{}", text),
+            Err(e) => {
+                eprintln!("[fnkey] {}", e);
+                status = 1;
+            }
+        }
+    }
+    status
+}
+
+fn stream(args: &[String]) -> i32 {
+    let opts = match parse_stream_args(args) {
+        Ok(opts) => opts,
+        Err(e) => {
+            eprintln!("[fnkey] {}\n{}", e, USAGE);
+            return 2;
+        }
+    };
+    let config = Config::load();
+    match run_pipe(&config, opts, std::io::stdin().lock(), std::io::stdout()) {
+        Ok(_) => 0,
+        Err(e) => {
+            eprintln!("[fnkey] {}", e);
+            1
+        }
+    }
+}
+
+/// `--rate N`, `--format NAME`, `--channels N`, also as `--flag=value`
+fn parse_stream_args(args: &[String]) -> Result<PipeOptions, String> {
+    let mut opts = PipeOptions { sample_rate: 16000, format: PcmFormat::S16Le, channels: 1 };
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) => (flag, Some(value.to_string())),
+            None => (arg.as_str(), None),
+        };
+        let mut value = || inline.clone().or_else(|| iter.next().cloned()).ok_or(format!("{} needs a value", flag));
+        match flag {
+            "--rate" => opts.sample_rate = value()?.parse().map_err(|_| "--rate must be a number")?,
+            "--channels" => opts.channels = value()?.parse().map_err(|_| "--channels must be a number")?,
+            "--format" => {
+                let name = value()?;
+                opts.format = PcmFormat::parse(&name).ok_or(format!("unknown format '{}' (f32le or s16le)", name))?;
+            }
+            _ => return Err(format!("unknown option '{}'", arg)),
+        }
+    }
+    if opts.sample_rate == 0 || opts.channels == 0 {
+        return Err("--rate and --channels must be non-zero".to_string());
+    }
+    Ok(opts)
+}
+
+/// Send one command to the running app and print its reply; `events` keeps
+/// printing session events until interrupted.
+#[cfg(unix)]
+fn ctl(args: &[String]) -> i32 {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+
+    let cmd = match args {
+        [cmd] if cmd == "events" => "subscribe",
+        [cmd] if ["start", "stop", "cancel", "status", "last-transcript"].contains(&cmd.as_str()) => cmd.as_str(),
+        _ => {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    };
+    let Some(path) = fnkey_core::control::socket_path() else {
+        eprintln!("[fnkey] HOME is not set");
+        return 1;
+    };
+    let mut stream = match UnixStream::connect(&path) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("[fnkey] {}: {} (is fnkey running?)", path.display(), e);
+            return 1;
+        }
+    };
+    if writeln!(stream, "{}", serde_json::json!({ "cmd": cmd })).is_err() {
+        return 1;
+    }
+    let mut status = 1;
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else { break };
+        println!("{}", line);
+        if status == 1 {
+            let ok = serde_json::from_str::<serde_json::Value>(&line).is_ok_and(|v| v["ok"] == true);
+            status = if ok { 0 } else { 1 };
+        }
+        if cmd != "subscribe" {
+            break;
+        }
+    }
+    status
+}
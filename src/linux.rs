@@ -0,0 +1,154 @@
+//! Linux front end: evdev hotkey, clipboard + XTest/uinput paste, tray icon,
+//! desktop notifications.
+
+use std::sync::{Arc, OnceLock};
+
+use fnkey_core::config::keywords_file;
+use fnkey_core::platform::{HotkeyCallback, Notification};
+use fnkey_core::{log_error, AppState, Config, Platform};
+use fnkey_linux::hotkey::{is_modifier, EvdevHotkey};
+use fnkey_linux::notify::Notifier;
+use fnkey_linux::paste::Paster;
+use fnkey_linux::tray::{Tray, TrayHandler};
+
+/// Set once in main so tray menu callbacks can reach the session
+static STATE: OnceLock<Arc<AppState>> = OnceLock::new();
+
+pub fn main() {
+    let config = Config::load();
+
+    if let Some(ref e) = config.providers_error {
+        eprintln!("[fnkey] Not starting: {}
+
+Fix or remove ~/.config/fnkey/providers.", e);
+        std::process::exit(1);
+    }
+
+    if !config.has_backend() {
+        eprintln!(
+            "[fnkey] No API key configured.\n\n\
+             Please create ~/.config/fnkey/deepgram_key with your Deepgram API key:\n  \
+             mkdir -p ~/.config/fnkey\n  echo 'your_key' > ~/.config/fnkey/deepgram_key"
+        );
+        std::process::exit(1);
+    }
+
+    let hotkey = match EvdevHotkey::from_config() {
+        Ok(h) => h,
+        Err(e) => {
+            log_error(&format!("Hotkey unavailable: {}", e));
+            std::process::exit(1);
+        }
+    };
+    eprintln!("[fnkey] watching {:?} on {}", hotkey.key(), hotkey.path().display());
+
+    let paster = match Paster::from_config() {
+        Ok(p) => p,
+        Err(e) => {
+            log_error(&format!("Paste config: {}", e));
+            std::process::exit(1);
+        }
+    };
+
+    // Every key press would record nothing; say so now instead
+    if !cfg!(feature = "cpal") {
+        log_error("Built without microphone support; rebuild with --features cpal");
+        std::process::exit(1);
+    }
+
+    // No tray host (or no session bus) just means no icon
+    let tray = match Tray::start(Arc::new(MenuHandler), config.auto_return) {
+        Ok(t) => Some(t),
+        Err(e) => {
+            log_error(&format!("Tray icon unavailable: {}", e));
+            None
+        }
+    };
+
+    let notifier = match Notifier::connect() {
+        Ok(n) => Some(n),
+        Err(e) => {
+            log_error(&format!("Notifications unavailable: {}", e));
+            None
+        }
+    };
+
+    let state = AppState::new(Arc::new(LinuxPlatform { hotkey, paster, tray, notifier }), config);
+    let _ = STATE.set(Arc::clone(&state));
+    fnkey_core::run(&state);
+}
+
+struct LinuxPlatform {
+    hotkey: EvdevHotkey,
+    paster: Paster,
+    tray: Option<Tray>,
+    notifier: Option<Notifier>,
+}
+
+impl Platform for LinuxPlatform {
+    fn run(&self, on_key: HotkeyCallback) {
+        if let Err(e) = self.hotkey.run(on_key) {
+            log_error(&format!("Hotkey device {} lost: {}", self.hotkey.path().display(), e));
+        }
+    }
+
+    fn set_recording(&self, recording: bool) {
+        if let Some(tray) = &self.tray {
+            if let Err(e) = tray.set_recording(recording) {
+                log_error(&format!("Tray update failed: {}", e));
+            }
+        }
+    }
+
+    fn show_notification(&self, notification: Notification) {
+        match &self.notifier {
+            Some(notifier) => notifier.show_or_log(notification),
+            None => eprintln!("[fnkey] {}", notification.message),
+        }
+    }
+
+    fn set_clipboard(&self, text: &str) -> bool {
+        self.paster.set_clipboard(text)
+    }
+
+    fn paste(&self) {
+        if let Err(e) = self.paster.paste() {
+            log_error(&format!("Paste failed: {}", e));
+        }
+    }
+
+    fn press_return(&self) {
+        if let Err(e) = self.paster.press_return() {
+            log_error(&format!("Return keypress failed: {}", e));
+        }
+    }
+
+    fn keycode_for_char(&self, ch: char) -> Option<u16> {
+        self.paster.keycode_for_char(ch)
+    }
+
+    fn hotkey_is_modifier(&self) -> bool {
+        is_modifier(self.hotkey.key())
+    }
+}
+
+struct MenuHandler;
+
+impl TrayHandler for MenuHandler {
+    fn toggle_auto_return(&self) -> bool {
+        let Some(state) = STATE.get() else { return false };
+        let new_val = !state.auto_return();
+        state.set_auto_return(new_val);
+        new_val
+    }
+
+    fn edit_keywords(&self) {
+        if let Some(path) = keywords_file() {
+            let _ = std::process::Command::new("xdg-open").arg(&path).spawn();
+        }
+    }
+
+    fn quit(&self) {
+        std::process::exit(0);
+    }
+}
@@ -0,0 +1,403 @@
+//! macOS front end: menu bar status item, CGEventTap hotkey, CGEvent paste.
+
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+use arboard::Clipboard;
+use cocoa::appkit::{NSApp, NSApplication, NSApplicationActivationPolicyAccessory, NSMenu};
+use cocoa::base::{id, nil};
+use cocoa::foundation::{NSAutoreleasePool, NSString};
+use core_foundation::runloop::{kCFRunLoopCommonModes, CFRunLoop};
+use core_graphics::event::{
+    CGEvent, CGEventFlags, CGEventTap, CGEventTapLocation, CGEventTapOptions, CGEventTapPlacement,
+    CGEventType,
+};
+use core_graphics::event_source::{CGEventSource, CGEventSourceStateID};
+use fnkey_core::config::keywords_file;
+use fnkey_core::platform::{HotkeyCallback, Notification};
+use fnkey_core::{AppState, Config, Platform};
+use objc::declare::ClassDecl;
+use objc::runtime::{Class, Object, Sel};
+use objc::{class, msg_send, sel, sel_impl};
+
+// ============================================================================
+// Keyboard layout detection (for non-Latin layouts like Russian)
+// ============================================================================
+
+static KEYCODE_MAP: OnceLock<HashMap<char, u16>> = OnceLock::new();
+
+#[repr(C)]
+struct UCKeyboardLayout {
+    _opaque: [u8; 0],
+}
+
+#[link(name = "Carbon", kind = "framework")]
+extern "C" {
+    fn TISCopyCurrentASCIICapableKeyboardLayoutInputSource() -> *const c_void;
+    fn TISGetInputSourceProperty(input_source: *const c_void, property_key: *const c_void) -> *const c_void;
+    fn LMGetKbdType() -> u32;
+    static kTISPropertyUnicodeKeyLayoutData: *const c_void;
+}
+
+#[link(name = "CoreServices", kind = "framework")]
+extern "C" {
+    fn UCKeyTranslate(
+        key_layout_ptr: *const UCKeyboardLayout,
+        virtual_key_code: u16,
+        key_action: u16,
+        modifier_key_state: u32,
+        keyboard_type: u32,
+        key_translate_options: u32,
+        dead_key_state: *mut u32,
+        max_string_length: usize,
+        actual_string_length: *mut usize,
+        unicode_string: *mut u16,
+    ) -> i32;
+}
+
+const KUC_KEY_ACTION_DISPLAY: u16 = 3;
+const QWERTY_V_KEYCODE: u16 = 9;
+
+fn build_char_to_keycode_map() -> HashMap<char, u16> {
+    let mut map = HashMap::new();
+    unsafe {
+        let input_source = TISCopyCurrentASCIICapableKeyboardLayoutInputSource();
+        if input_source.is_null() {
+            return map;
+        }
+        let layout_data_ref = TISGetInputSourceProperty(input_source, kTISPropertyUnicodeKeyLayoutData);
+        if layout_data_ref.is_null() {
+            core_foundation::base::CFRelease(input_source);
+            return map;
+        }
+        let layout_data: core_foundation::data::CFData =
+            core_foundation::base::TCFType::wrap_under_get_rule(layout_data_ref as *const _);
+        let layout_ptr = layout_data.bytes().as_ptr() as *const UCKeyboardLayout;
+        let kbd_type = LMGetKbdType();
+        for keycode in 0u16..128 {
+            let mut dead_key_state: u32 = 0;
+            let mut char_buf: [u16; 4] = [0; 4];
+            let mut actual_len: usize = 0;
+            let result = UCKeyTranslate(
+                layout_ptr, keycode, KUC_KEY_ACTION_DISPLAY, 0, kbd_type, 0,
+                &mut dead_key_state, char_buf.len(), &mut actual_len, char_buf.as_mut_ptr(),
+            );
+            if result == 0 && actual_len == 1 {
+                if let Some(ch) = char::from_u32(u32::from(char_buf[0])) {
+                    map.entry(ch.to_ascii_lowercase()).or_insert(keycode);
+                }
+            }
+        }
+        core_foundation::base::CFRelease(input_source);
+    }
+    map
+}
+
+// ============================================================================
+// Main application
+// ============================================================================
+
+const FN_KEY_FLAG: u64 = 0x800000;
+const OPTION_KEY_FLAG: u64 = 0x80000;
+
+static mut STATUS_ITEM: *mut Object = std::ptr::null_mut();
+static mut AUTO_RETURN_ITEM: *mut Object = std::ptr::null_mut();
+/// Pipeline state, reachable from Objective-C menu callbacks
+static STATE: OnceLock<Arc<AppState>> = OnceLock::new();
+
+pub fn main() {
+    let config = Config::load();
+
+    if let Some(ref e) = config.providers_error {
+        show_alert("Invalid providers file", &format!("{}\n\nFix or remove ~/.config/fnkey/providers.", e));
+        std::process::exit(1);
+    }
+
+    if !config.has_backend() {
+        show_alert(
+            "No API key configured",
+            "Please create ~/.config/fnkey/deepgram_key with your Deepgram API key.\n\n\
+             Example:\n  mkdir -p ~/.config/fnkey\n  echo 'your_key' > ~/.config/fnkey/deepgram_key\n\n\
+             Get a key at https://console.deepgram.com (includes $200 free credit)"
+        );
+        std::process::exit(1);
+    }
+
+    if !check_input_monitoring_permission() {
+        std::process::exit(1);
+    }
+
+    let state = AppState::new(Arc::new(MacPlatform::new()), config);
+    let _ = STATE.set(Arc::clone(&state));
+
+    unsafe {
+        let _pool = NSAutoreleasePool::new(nil);
+        let app = NSApp();
+        app.setActivationPolicy_(NSApplicationActivationPolicyAccessory);
+        create_status_item(state.auto_return());
+    }
+
+    fnkey_core::run(&state);
+}
+
+struct MacPlatform {
+    /// Falls back to Option if no Fn press is seen in the first 5s
+    use_fn_key: Arc<AtomicBool>,
+}
+
+impl MacPlatform {
+    fn new() -> Self {
+        MacPlatform { use_fn_key: Arc::new(AtomicBool::new(true)) }
+    }
+}
+
+impl Platform for MacPlatform {
+    fn run(&self, on_key: HotkeyCallback) {
+        run_event_tap(Arc::clone(&self.use_fn_key), on_key);
+    }
+
+    fn set_recording(&self, recording: bool) {
+        update_status_icon(recording);
+    }
+
+    fn show_notification(&self, notification: Notification) {
+        // NSUserNotification action buttons need a delegate; show the text only
+        show_notification(&notification.message);
+    }
+
+    fn set_clipboard(&self, text: &str) -> bool {
+        match Clipboard::new() {
+            Ok(mut clipboard) => clipboard.set_text(text).is_ok(),
+            Err(_) => false,
+        }
+    }
+
+    fn paste(&self) {
+        paste_with_cgevent(self.keycode_for_char('v').unwrap_or(QWERTY_V_KEYCODE));
+    }
+
+    fn press_return(&self) {
+        press_return();
+    }
+
+    fn keycode_for_char(&self, ch: char) -> Option<u16> {
+        let map = KEYCODE_MAP.get_or_init(build_char_to_keycode_map);
+        map.get(&ch).copied()
+    }
+}
+
+fn show_notification(msg: &str) {
+    unsafe {
+        let _pool = NSAutoreleasePool::new(nil);
+        let center: id = msg_send![class!(NSUserNotificationCenter), defaultUserNotificationCenter];
+        let notification: id = msg_send![class!(NSUserNotification), new];
+        let title = NSString::alloc(nil).init_str("FnKey");
+        let body = NSString::alloc(nil).init_str(msg);
+        let _: () = msg_send![notification, setTitle: title];
+        let _: () = msg_send![notification, setInformativeText: body];
+        let _: () = msg_send![center, deliverNotification: notification];
+    }
+}
+
+// ============================================================================
+// UI, permissions, event tap
+// ============================================================================
+
+fn check_input_monitoring_permission() -> bool {
+    unsafe {
+        #[link(name = "CoreGraphics", kind = "framework")]
+        extern "C" {
+            fn CGPreflightListenEventAccess() -> bool;
+            fn CGRequestListenEventAccess() -> bool;
+        }
+        if CGPreflightListenEventAccess() {
+            return true;
+        }
+        if CGRequestListenEventAccess() {
+            return true;
+        }
+        show_alert(
+            "Input Monitoring Required",
+            "FnKey needs Input Monitoring permission to detect the Fn key.\n\n\
+             Please grant access in System Settings → Privacy & Security → Input Monitoring, then relaunch FnKey.",
+        );
+        false
+    }
+}
+
+fn show_alert(title: &str, message: &str) {
+    unsafe {
+        let _pool = NSAutoreleasePool::new(nil);
+        let alert: id = msg_send![class!(NSAlert), new];
+        let title_str = NSString::alloc(nil).init_str(title);
+        let msg_str = NSString::alloc(nil).init_str(message);
+        let _: () = msg_send![alert, setMessageText: title_str];
+        let _: () = msg_send![alert, setInformativeText: msg_str];
+        let _: () = msg_send![alert, runModal];
+    }
+}
+
+extern "C" fn toggle_auto_return(_this: &Object, _cmd: Sel, _sender: id) {
+    let Some(state) = STATE.get() else { return };
+    let new_val = !state.auto_return();
+    state.set_auto_return(new_val);
+    unsafe {
+        if !AUTO_RETURN_ITEM.is_null() {
+            let item_state: i64 = if new_val { 1 } else { 0 };
+            let _: () = msg_send![AUTO_RETURN_ITEM as id, setState: item_state];
+        }
+    }
+}
+
+extern "C" fn edit_keywords(_this: &Object, _cmd: Sel, _sender: id) {
+    if let Some(path) = keywords_file() {
+        let _ = std::process::Command::new("open").arg("-t").arg(&path).spawn();
+    }
+}
+
+fn register_menu_handler_class() {
+    let superclass = Class::get("NSObject").unwrap();
+    let mut decl = ClassDecl::new("FnKeyMenuHandler", superclass).unwrap();
+    unsafe {
+        decl.add_method(
+            sel!(toggleAutoReturn:),
+            toggle_auto_return as extern "C" fn(&Object, Sel, id),
+        );
+        decl.add_method(
+            sel!(editKeywords:),
+            edit_keywords as extern "C" fn(&Object, Sel, id),
+        );
+    }
+    decl.register();
+}
+
+unsafe fn create_status_item(auto_return: bool) {
+    register_menu_handler_class();
+
+    let status_bar: id = msg_send![class!(NSStatusBar), systemStatusBar];
+    let status_item: id = msg_send![status_bar, statusItemWithLength: -1.0_f64];
+    let _: () = msg_send![status_item, retain];
+    STATUS_ITEM = status_item as *mut Object;
+    let title = NSString::alloc(nil).init_str("○");
+    let button: id = msg_send![status_item, button];
+    let _: () = msg_send![button, setTitle: title];
+    let menu: id = NSMenu::new(nil);
+
+    // Auto Return toggle
+    let handler_class = Class::get("FnKeyMenuHandler").unwrap();
+    let handler: id = msg_send![handler_class, new];
+    let _: () = msg_send![handler, retain];
+    let auto_return_title = NSString::alloc(nil).init_str("Press Return after paste");
+    let empty_key = NSString::alloc(nil).init_str("");
+    let auto_return_item: id = msg_send![class!(NSMenuItem), alloc];
+    let auto_return_item: id = msg_send![auto_return_item, initWithTitle: auto_return_title action: sel!(toggleAutoReturn:) keyEquivalent: empty_key];
+    let _: () = msg_send![auto_return_item, setTarget: handler];
+    if auto_return {
+        let _: () = msg_send![auto_return_item, setState: 1_i64];
+    }
+    AUTO_RETURN_ITEM = auto_return_item as *mut Object;
+    let _: () = msg_send![menu, addItem: auto_return_item];
+
+    // Edit Keywords
+    let keywords_title = NSString::alloc(nil).init_str("Edit Keywords…");
+    let keywords_item: id = msg_send![class!(NSMenuItem), alloc];
+    let keywords_item: id = msg_send![keywords_item, initWithTitle: keywords_title action: sel!(editKeywords:) keyEquivalent: empty_key];
+    let _: () = msg_send![keywords_item, setTarget: handler];
+    let _: () = msg_send![menu, addItem: keywords_item];
+
+    // Separator
+    let separator: id = msg_send![class!(NSMenuItem), separatorItem];
+    let _: () = msg_send![menu, addItem: separator];
+
+    // Quit
+    let quit_title = NSString::alloc(nil).init_str("Quit FnKey");
+    let quit_key = NSString::alloc(nil).init_str("q");
+    let quit_item: id = msg_send![class!(NSMenuItem), alloc];
+    let quit_item: id = msg_send![quit_item, initWithTitle: quit_title action: sel!(terminate:) keyEquivalent: quit_key];
+    let _: () = msg_send![quit_item, setTarget: NSApp()];
+    let _: () = msg_send![menu, addItem: quit_item];
+    let _: () = msg_send![status_item, setMenu: menu];
+}
+
+fn update_status_icon(recording: bool) {
+    unsafe {
+        if STATUS_ITEM.is_null() {
+            return;
+        }
+        let title = if recording { "●" } else { "○" };
+        let title_str = NSString::alloc(nil).init_str(title);
+        let button: id = msg_send![STATUS_ITEM as id, button];
+        let _: () = msg_send![button, setTitle: title_str];
+    }
+}
+
+fn run_event_tap(use_fn_key: Arc<AtomicBool>, on_key: HotkeyCallback) {
+    let fn_detected = Arc::new(AtomicBool::new(false));
+
+    let fn_detected_clone = Arc::clone(&fn_detected);
+    let use_fn_key_clone = Arc::clone(&use_fn_key);
+
+    let tap = CGEventTap::new(
+        CGEventTapLocation::HID,
+        CGEventTapPlacement::HeadInsertEventTap,
+        CGEventTapOptions::ListenOnly,
+        vec![CGEventType::FlagsChanged],
+        move |_, _, event| {
+            let flags = event.get_flags().bits();
+            let fn_pressed = (flags & FN_KEY_FLAG) != 0;
+            let option_pressed = (flags & OPTION_KEY_FLAG) != 0;
+            let use_fn = use_fn_key_clone.load(Ordering::SeqCst);
+            let key_pressed = if use_fn { fn_pressed } else { option_pressed };
+
+            if fn_pressed && !fn_detected_clone.load(Ordering::SeqCst) {
+                fn_detected_clone.store(true, Ordering::SeqCst);
+            }
+
+            on_key(key_pressed);
+            None
+        },
+    )
+    .expect("Failed to create event tap - check Input Monitoring permissions");
+
+    let source = tap.mach_port.create_runloop_source(0)
+        .expect("Failed to create runloop source");
+    let run_loop = CFRunLoop::get_current();
+    run_loop.add_source(&source, unsafe { kCFRunLoopCommonModes });
+    tap.enable();
+
+    let fn_detected_fallback = Arc::clone(&fn_detected);
+    thread::spawn(move || {
+        thread::sleep(Duration::from_secs(5));
+        if !fn_detected_fallback.load(Ordering::SeqCst) && use_fn_key.load(Ordering::SeqCst) {
+            use_fn_key.store(false, Ordering::SeqCst);
+        }
+    });
+
+    unsafe {
+        NSApp().run();
+    }
+}
+
+fn paste_with_cgevent(v_keycode: u16) {
+    if let Ok(source) = CGEventSource::new(CGEventSourceStateID::HIDSystemState) {
+        if let Ok(key_down) = CGEvent::new_keyboard_event(source.clone(), v_keycode, true) {
+            if let Ok(key_up) = CGEvent::new_keyboard_event(source, v_keycode, false) {
+                key_down.set_flags(CGEventFlags::CGEventFlagCommand);
+                key_up.set_flags(CGEventFlags::CGEventFlagCommand);
+                key_down.post(CGEventTapLocation::HID);
+                key_up.post(CGEventTapLocation::HID);
+            }
+        }
+    }
+}
+
+fn press_return() {
+    let _ = std::process::Command::new("osascript")
+        .arg("-e")
+        .arg("tell application \"System Events\" to key code 36")
+        .output();
+}